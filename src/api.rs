@@ -0,0 +1,421 @@
+use crate::config::{Character, Config};
+use crate::events::Event;
+use crate::launcher::Launcher;
+use crate::proxy::{self, HttpRequest};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// Give up on clients that connect but don't finish sending a request
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum State {
+    #[default]
+    Idle,
+    Running,
+    Cancelling,
+}
+
+/// What `GET /status` reports: the current run, or the last one when idle
+#[derive(Default, Serialize)]
+struct Progress {
+    state: State,
+    /// Characters requested for the current or last run
+    characters: Vec<String>,
+    /// Character being logged in right now
+    current: Option<String>,
+    logged_in: Vec<String>,
+    failed: Vec<String>,
+    /// Report of the last finished run
+    report: Option<Value>,
+}
+
+/// State shared between the API server, the login worker and the event stream
+#[derive(Default)]
+pub struct ApiState {
+    progress: Mutex<Progress>,
+    cancel: AtomicBool,
+}
+
+impl ApiState {
+    /// Track per-character progress from launcher events. Subscribe this to `Output`.
+    pub fn observe(&self, event: &Event) {
+        let mut progress = self.progress.lock().unwrap();
+        match event {
+            Event::LoggingIn { character } => progress.current = Some(character.to_string()),
+            Event::LoginComplete { character } => {
+                progress.current = None;
+                progress.logged_in.push(character.to_string());
+            }
+            Event::LoginFailed { character } | Event::LaunchFailed { character } => {
+                progress.current = None;
+                progress.failed.push(character.to_string());
+            }
+            Event::GameWindowNotFound { character, .. } | Event::LobbyFailed { character, .. } => {
+                // Its login went through, but it never made it into the game
+                progress.logged_in.retain(|name| name != character);
+                progress.failed.push(character.to_string());
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct LoginRequest {
+    /// Characters or groups to log in by name. Empty means all.
+    #[serde(default)]
+    characters: Vec<String>,
+}
+
+/// Serve the control API on `listener` until the process exits.
+///
+/// Endpoints (all require `Authorization: Bearer <token>`):
+/// - `GET /characters` - characters in the config
+/// - `POST /login` - start logging in `{"characters": [...]}`, or everyone if omitted
+/// - `GET /status` - progress of the current run, or the result of the last one
+/// - `POST /cancel` - stop the current run before its next character
+///
+/// Only one run happens at a time; it runs on a worker thread using `launcher`.
+pub fn serve<L: Launcher + Send>(
+    listener: TcpListener,
+    config: &Config,
+    token: &str,
+    state: &ApiState,
+    launcher: &Mutex<L>,
+) -> ! {
+    thread::scope(|scope| loop {
+        let mut stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                log::warn!("Control API failed to accept a connection: {}", e);
+                continue;
+            }
+        };
+
+        let (status, body) = match read(&mut stream) {
+            Ok(request) if !authorized(&request, token) => {
+                (401, json!({ "error": "missing or wrong bearer token" }))
+            }
+            Ok(request) => {
+                log::info!("Control API: {} {}", request.method, request.path);
+                handle(&request, config, state, |characters| {
+                    scope.spawn(move || {
+                        let report = launcher
+                            .lock()
+                            .unwrap()
+                            .login(config, &characters, &state.cancel);
+                        let mut progress = state.progress.lock().unwrap();
+                        progress.state = State::Idle;
+                        progress.current = None;
+                        progress.report = serde_json::to_value(&report).ok();
+                    });
+                })
+            }
+            Err(e) => (400, json!({ "error": e })),
+        };
+
+        if let Err(e) = proxy::write_response(&mut stream, status, "application/json", &body.to_string()) {
+            log::warn!("Control API failed to send a response: {}", e);
+        }
+    })
+}
+
+fn read(stream: &mut TcpStream) -> Result<HttpRequest, String> {
+    stream
+        .set_read_timeout(Some(READ_TIMEOUT))
+        .map_err(|e| e.to_string())?;
+    proxy::read_request(stream)
+}
+
+fn authorized(request: &HttpRequest, token: &str) -> bool {
+    let given = request
+        .header("authorization")
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    // Compare every byte so the time taken doesn't reveal how much of the token matched
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Route one authorized request. `start` launches a login run in the background.
+fn handle<'c>(
+    request: &HttpRequest,
+    config: &'c Config,
+    state: &ApiState,
+    start: impl FnOnce(Vec<&'c Character>),
+) -> (u16, Value) {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/characters") => {
+            let characters: Vec<Value> = config
+                .characters
+                .iter()
+                .map(|ch| json!({ "name": ch.name, "slot": ch.slot }))
+                .collect();
+            (200, Value::Array(characters))
+        }
+        ("GET", "/status") => {
+            let progress = state.progress.lock().unwrap();
+            (200, serde_json::to_value(&*progress).unwrap_or_default())
+        }
+        ("POST", "/login") => {
+            let body: LoginRequest = if request.body.iter().all(u8::is_ascii_whitespace) {
+                LoginRequest::default()
+            } else {
+                match serde_json::from_slice(&request.body) {
+                    Ok(b) => b,
+                    Err(e) => return (400, json!({ "error": format!("invalid request body: {}", e) })),
+                }
+            };
+
+            if let Err(e) = config.check_names(&body.characters) {
+                return (400, json!({ "error": e }));
+            }
+
+            let mut progress = state.progress.lock().unwrap();
+            if progress.state != State::Idle {
+                return (409, json!({ "error": "a login run is already in progress" }));
+            }
+
+            let characters = config.filter_characters(&body.characters);
+            let names: Vec<String> = characters.iter().map(|ch| ch.name.clone()).collect();
+            *progress = Progress {
+                state: State::Running,
+                characters: names.clone(),
+                ..Progress::default()
+            };
+            state.cancel.store(false, Ordering::SeqCst);
+            drop(progress);
+
+            start(characters);
+            (202, json!({ "started": names }))
+        }
+        ("POST", "/cancel") => {
+            let mut progress = state.progress.lock().unwrap();
+            if progress.state == State::Idle {
+                return (409, json!({ "error": "no login run in progress" }));
+            }
+            progress.state = State::Cancelling;
+            state.cancel.store(true, Ordering::SeqCst);
+            (202, json!({ "cancelling": progress.characters }))
+        }
+        (_, "/characters" | "/status" | "/login" | "/cancel") => {
+            (405, json!({ "error": format!("{} is not allowed on {}", request.method, request.path) }))
+        }
+        _ => (404, json!({ "error": format!("no endpoint {}", request.path) })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigFormat;
+    use crate::launcher::MockLauncher;
+    use crate::report::RunReport;
+    use std::io::{Read, Write};
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    const TOKEN: &str = "test-token";
+
+    /// Holds each run until `release` is set or the run is cancelled
+    struct GatedLauncher {
+        inner: MockLauncher,
+        release: Arc<AtomicBool>,
+    }
+
+    impl Launcher for GatedLauncher {
+        fn login(&mut self, config: &Config, characters: &[&Character], cancel: &AtomicBool) -> RunReport {
+            while !self.release.load(Ordering::SeqCst) && !cancel.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(5));
+            }
+            self.inner.login(config, characters, cancel)
+        }
+    }
+
+    struct Server {
+        addr: SocketAddr,
+        launcher: &'static Mutex<GatedLauncher>,
+        release: Arc<AtomicBool>,
+    }
+
+    /// Serve the API on a free port for the rest of the test process
+    fn start() -> Server {
+        let json = r#"{
+            "version": 2,
+            "groups": {"mages": ["MyMage"]},
+            "characters": [
+                {"name": "MyWarrior", "slot": 1, "password": "Passw0rd!"},
+                {"name": "MyMage", "slot": 2, "password": "Passw0rd!"}
+            ]
+        }"#;
+        let config = Config::parse(json, ConfigFormat::Json).unwrap();
+        let config: &'static Config = Box::leak(Box::new(config));
+        let state: &'static ApiState = Box::leak(Box::default());
+        let release = Arc::new(AtomicBool::new(false));
+        let launcher: &'static Mutex<GatedLauncher> = Box::leak(Box::new(Mutex::new(GatedLauncher {
+            inner: MockLauncher::default(),
+            release: Arc::clone(&release),
+        })));
+
+        let listener = proxy::bind_localhost(0).unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || serve(listener, config, TOKEN, state, launcher));
+        Server { addr, launcher, release }
+    }
+
+    impl Server {
+        fn send(&self, raw: &str) -> (u16, Value) {
+            let mut stream = TcpStream::connect(self.addr).unwrap();
+            stream.write_all(raw.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            let status = response[9..12].parse().unwrap();
+            let (_, body) = response.split_once("\r\n\r\n").unwrap();
+            (status, serde_json::from_str(body).unwrap())
+        }
+
+        fn request(&self, method: &str, path: &str, body: &str) -> (u16, Value) {
+            self.send(&format!(
+                "{} {} HTTP/1.1\r\nAuthorization: Bearer {}\r\nContent-Length: {}\r\n\r\n{}",
+                method,
+                path,
+                TOKEN,
+                body.len(),
+                body
+            ))
+        }
+
+        /// Poll `GET /status` until the run is idle again
+        fn finished_status(&self) -> Value {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            loop {
+                let (_, status) = self.request("GET", "/status", "");
+                if status["state"] == "idle" {
+                    return status;
+                }
+                assert!(Instant::now() < deadline, "run never finished: {}", status);
+                thread::sleep(Duration::from_millis(5));
+            }
+        }
+
+        fn runs(&self) -> Vec<Vec<String>> {
+            self.launcher.lock().unwrap().inner.runs.clone()
+        }
+    }
+
+    #[test]
+    fn requests_need_the_bearer_token() {
+        let server = start();
+        let (status, body) = server.send("GET /characters HTTP/1.1\r\n\r\n");
+        assert_eq!(status, 401);
+        assert_eq!(body["error"], "missing or wrong bearer token");
+
+        let wrong = "GET /characters HTTP/1.1\r\nAuthorization: Bearer test-tokem\r\n\r\n";
+        assert_eq!(server.send(wrong).0, 401);
+        let unauthorized_login = "POST /login HTTP/1.1\r\nContent-Length: 0\r\n\r\n";
+        assert_eq!(server.send(unauthorized_login).0, 401);
+        assert!(server.runs().is_empty());
+    }
+
+    #[test]
+    fn lists_characters() {
+        let server = start();
+        let (status, body) = server.request("GET", "/characters", "");
+        assert_eq!(status, 200);
+        assert_eq!(
+            body,
+            json!([{"name": "MyWarrior", "slot": 1}, {"name": "MyMage", "slot": 2}])
+        );
+    }
+
+    #[test]
+    fn login_runs_in_the_background_and_reports_its_result() {
+        let server = start();
+        server.release.store(true, Ordering::SeqCst);
+
+        let (status, body) = server.request("POST", "/login", r#"{"characters": ["mages"]}"#);
+        assert_eq!((status, body), (202, json!({"started": ["MyMage"]})));
+
+        let status = server.finished_status();
+        assert_eq!(status["characters"], json!(["MyMage"]));
+        assert_eq!(status["report"]["status"], "success");
+        assert_eq!(status["report"]["characters"][0]["name"], "MyMage");
+
+        // No body logs in everyone
+        assert_eq!(server.request("POST", "/login", "").0, 202);
+        server.finished_status();
+        assert_eq!(server.runs(), [vec!["MyMage"], vec!["MyWarrior", "MyMage"]]);
+    }
+
+    #[test]
+    fn one_run_at_a_time_and_cancel_stops_it() {
+        let server = start();
+        assert_eq!(server.request("POST", "/cancel", "").0, 409);
+
+        assert_eq!(server.request("POST", "/login", "").0, 202);
+        let (status, body) = server.request("POST", "/login", "");
+        assert_eq!(status, 409);
+        assert_eq!(body["error"], "a login run is already in progress");
+
+        let (status, body) = server.request("POST", "/cancel", "");
+        assert_eq!((status, body), (202, json!({"cancelling": ["MyWarrior", "MyMage"]})));
+        let status = server.finished_status();
+        assert_eq!(status["report"]["characters"][0]["launched"], false);
+        assert_eq!(server.request("POST", "/cancel", "").0, 409);
+
+        // A cancelled run doesn't cancel the next one
+        server.release.store(true, Ordering::SeqCst);
+        assert_eq!(server.request("POST", "/login", r#"{"characters": ["MyWarrior"]}"#).0, 202);
+        let status = server.finished_status();
+        assert_eq!(status["report"]["status"], "success");
+    }
+
+    #[test]
+    fn bad_requests_are_rejected() {
+        let server = start();
+        let (status, body) = server.request("POST", "/login", r#"{"characters": ["Nobody"]}"#);
+        assert_eq!(status, 400);
+        assert!(body["error"].as_str().unwrap().contains("Nobody"), "{}", body);
+
+        let (status, body) = server.request("POST", "/login", r#"{"names": []}"#);
+        assert_eq!(status, 400);
+        assert!(body["error"].as_str().unwrap().starts_with("invalid request body"));
+
+        assert_eq!(server.request("DELETE", "/status", "").0, 405);
+        assert_eq!(server.request("GET", "/nowhere", "").0, 404);
+        assert_eq!(server.send("nonsense\r\n\r\n").0, 400);
+        assert!(server.runs().is_empty());
+    }
+
+    #[test]
+    fn status_follows_launcher_events() {
+        let state = ApiState::default();
+        state.observe(&Event::LoggingIn { character: "MyWarrior" });
+        assert_eq!(state.progress.lock().unwrap().current.as_deref(), Some("MyWarrior"));
+        state.observe(&Event::LoginComplete { character: "MyWarrior" });
+        state.observe(&Event::LoggingIn { character: "MyMage" });
+        state.observe(&Event::LoginFailed { character: "MyMage" });
+        state.observe(&Event::GameWindowNotFound {
+            character: "MyWarrior",
+            seconds: 120,
+        });
+
+        let progress = state.progress.lock().unwrap();
+        assert_eq!(progress.current, None);
+        assert!(progress.logged_in.is_empty());
+        assert_eq!(progress.failed, ["MyMage", "MyWarrior"]);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::proxy;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::net::Ipv4Addr;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// A single line of a `validate` or `doctor` checklist
+#[derive(Debug, Serialize)]
+pub struct CheckResult {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl CheckResult {
+    pub fn pass(name: &str, detail: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Pass, detail)
+    }
+
+    pub fn warn(name: &str, detail: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Warn, detail)
+    }
+
+    pub fn fail(name: &str, detail: impl Into<String>) -> Self {
+        Self::new(name, CheckStatus::Fail, detail)
+    }
+
+    fn new(name: &str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// True if any check in the list failed
+pub fn has_failures(results: &[CheckResult]) -> bool {
+    results.iter().any(|r| r.status == CheckStatus::Fail)
+}
+
+/// Filesystem access used by the checks, so they can run against a fake in tests
+pub trait FileSystem {
+    fn is_file(&self, path: &Path) -> bool;
+    fn is_dir(&self, path: &Path) -> bool;
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    /// Open the file for appending without writing anything, to probe write access
+    fn probe_writable(&self, path: &Path) -> io::Result<()>;
+}
+
+/// Network access used by the checks
+pub trait Network {
+    /// Try to bind the port and immediately release it
+    fn probe_port(&self, port: u16) -> io::Result<()>;
+}
+
+pub struct RealFileSystem;
+
+impl FileSystem for RealFileSystem {
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn probe_writable(&self, path: &Path) -> io::Result<()> {
+        fs::OpenOptions::new().append(true).open(path).map(|_| ())
+    }
+}
+
+pub struct RealNetwork;
+
+impl Network for RealNetwork {
+    fn probe_port(&self, port: u16) -> io::Result<()> {
+        // Bind exactly as the proxy will, since SO_REUSEADDR changes what counts as taken
+        proxy::bind_with_reuse(Ipv4Addr::UNSPECIFIED, port)
+            .map(|_| ())
+            .map_err(|e| io::Error::other(e.to_string()))
+    }
+}
@@ -0,0 +1,370 @@
+use crate::config::ConfigFormat;
+use crate::events::OutputFormat;
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(
+    name = "login-rs",
+    version,
+    about = "Automated FFXI multi-character login"
+)]
+pub struct Cli {
+    #[command(flatten)]
+    pub global: GlobalArgs,
+
+    /// Options for the default `login` command when no subcommand is given
+    #[command(flatten)]
+    pub login: LoginArgs,
+
+    /// Deprecated: use `login-rs record`
+    #[arg(long, hide = true)]
+    pub record: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Args)]
+pub struct GlobalArgs {
+    /// Path to config file
+    #[arg(short, long, default_value = "config.json", global = true)]
+    pub config: PathBuf,
+
+    /// Override a config value, e.g. `--set stagger_delay_seconds=5` or
+    /// `--set characters[MyWarrior].slot=3`. Can be given more than once.
+    #[arg(long = "set", value_name = "KEY=VALUE", global = true)]
+    pub set: Vec<String>,
+
+    /// Config file format. Detected from the file extension if omitted.
+    #[arg(long, value_enum, global = true)]
+    pub config_format: Option<ConfigFormat>,
+
+    /// Log level (overrides RUST_LOG)
+    #[arg(long, value_enum, global = true)]
+    pub log_level: Option<LogLevel>,
+
+    /// Output format for status updates
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text, global = true)]
+    pub output: OutputFormat,
+}
+
+impl GlobalArgs {
+    /// Format of the config file: `--config-format`, else the file extension, else JSON
+    pub fn config_format(&self) -> ConfigFormat {
+        ConfigFormat::resolve(&self.config, self.config_format)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for log::LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Off => log::LevelFilter::Off,
+            LogLevel::Error => log::LevelFilter::Error,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Debug => log::LevelFilter::Debug,
+            LogLevel::Trace => log::LevelFilter::Trace,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Launch and log in characters (the default when no subcommand is given)
+    Login(LoginArgs),
+    /// Launch one character and record keypresses for debugging login automation
+    Record(RecordArgs),
+    /// Launch one character and replay a keypress recording made with `record`
+    Replay(ReplayArgs),
+    /// Log in all characters, then keep running and apply config changes as they are saved
+    Daemon,
+    /// Run the local HTTP control API configured in the `api` section
+    Serve,
+    /// Wait and log characters in at the times in the `schedule` section
+    Scheduler,
+    /// Check the config file for mistakes without launching anything
+    Validate,
+    /// Check that this machine is ready to run login-rs
+    Doctor,
+    /// List the characters defined in the config
+    List,
+    /// Manage login-rs entries in the Windows hosts file
+    Hosts {
+        #[command(subcommand)]
+        command: HostsCommand,
+    },
+    /// Create and manage config files
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Args, Default)]
+pub struct LoginArgs {
+    /// Specific characters or groups to log in (by name). If omitted, logs in all.
+    #[arg(long, num_args = 1..)]
+    pub characters: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct RecordArgs {
+    /// Character to launch. Defaults to the first character in the config.
+    pub character: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ReplayArgs {
+    /// Recording file (the table printed by `record`, saved to a file)
+    pub recording: PathBuf,
+
+    /// Character to launch. Defaults to the first character in the config.
+    pub character: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum HostsCommand {
+    /// Remove all `# login-rs` entries left in the hosts file
+    Clean,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Create a config file by answering a few questions
+    Init {
+        /// Overwrite the file if it already exists without asking
+        #[arg(long)]
+        force: bool,
+
+        /// Write the sample config instead of asking questions
+        #[arg(long)]
+        sample: bool,
+    },
+    /// Print the effective config after includes, the local override file and `--set`
+    Show {
+        /// Show which file each value came from
+        #[arg(long)]
+        origin: bool,
+    },
+    /// Upgrade the config file to the current schema version, keeping a backup
+    Migrate,
+    /// Convert the config file to another format
+    Convert {
+        /// Format to convert to
+        #[arg(long, value_enum)]
+        to: ConfigFormat,
+
+        /// Where to write the converted file. Defaults to the config path with the new extension.
+        destination: Option<PathBuf>,
+
+        /// Overwrite the destination if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+impl Cli {
+    /// The command to run, treating a bare invocation as `login`. Fails if login
+    /// options were given before a subcommand.
+    pub fn take_command(&mut self) -> Result<Command, clap::Error> {
+        match self.command.take() {
+            Some(_) if !self.login.characters.is_empty() || self.record => Err(Cli::command().error(
+                ErrorKind::ArgumentConflict,
+                "--characters and --record go after the subcommand (e.g. `login-rs login --characters ...`)",
+            )),
+            Some(command) => Ok(command),
+            None if self.record => Ok(Command::Record(RecordArgs {
+                character: self.login.characters.first().cloned(),
+            })),
+            None => Ok(Command::Login(std::mem::take(&mut self.login))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(args: &[&str]) -> Result<Command, clap::Error> {
+        let mut cli = Cli::try_parse_from(std::iter::once("login-rs").chain(args.iter().copied()))?;
+        cli.take_command()
+    }
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from(std::iter::once("login-rs").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn bare_invocation_logs_in() {
+        match command(&[]).unwrap() {
+            Command::Login(args) => assert!(args.characters.is_empty()),
+            _ => panic!("expected login"),
+        }
+        match command(&["--characters", "MyWarrior", "mules"]).unwrap() {
+            Command::Login(args) => assert_eq!(args.characters, ["MyWarrior", "mules"]),
+            _ => panic!("expected login"),
+        }
+    }
+
+    #[test]
+    fn login_subcommand() {
+        match command(&["login", "--characters", "MyWarrior"]).unwrap() {
+            Command::Login(args) => assert_eq!(args.characters, ["MyWarrior"]),
+            _ => panic!("expected login"),
+        }
+    }
+
+    #[test]
+    fn deprecated_record_flag() {
+        match command(&["--record"]).unwrap() {
+            Command::Record(args) => assert_eq!(args.character, None),
+            _ => panic!("expected record"),
+        }
+        match command(&["--record", "--characters", "MyWarrior"]).unwrap() {
+            Command::Record(args) => assert_eq!(args.character.as_deref(), Some("MyWarrior")),
+            _ => panic!("expected record"),
+        }
+    }
+
+    #[test]
+    fn login_options_before_a_subcommand_conflict() {
+        for args in [
+            &["--record", "replay", "keys.txt"][..],
+            &["--record", "validate"],
+            &["--characters=MyWarrior", "daemon"],
+        ] {
+            let err = command(args).err().unwrap_or_else(|| panic!("{:?} parsed", args));
+            assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+        }
+    }
+
+    #[test]
+    fn record_and_replay() {
+        match command(&["record", "MyWarrior"]).unwrap() {
+            Command::Record(args) => assert_eq!(args.character.as_deref(), Some("MyWarrior")),
+            _ => panic!("expected record"),
+        }
+        match command(&["replay", "keys.txt"]).unwrap() {
+            Command::Replay(args) => {
+                assert_eq!(args.recording, PathBuf::from("keys.txt"));
+                assert_eq!(args.character, None);
+            }
+            _ => panic!("expected replay"),
+        }
+        assert!(command(&["replay"]).is_err());
+    }
+
+    #[test]
+    fn plain_subcommands() {
+        for (arg, expected) in [
+            ("daemon", "daemon"),
+            ("serve", "serve"),
+            ("scheduler", "scheduler"),
+            ("validate", "validate"),
+            ("doctor", "doctor"),
+            ("list", "list"),
+        ] {
+            let name = match command(&[arg]).unwrap() {
+                Command::Daemon => "daemon",
+                Command::Serve => "serve",
+                Command::Scheduler => "scheduler",
+                Command::Validate => "validate",
+                Command::Doctor => "doctor",
+                Command::List => "list",
+                _ => "other",
+            };
+            assert_eq!(name, expected);
+        }
+        assert!(matches!(
+            command(&["hosts", "clean"]).unwrap(),
+            Command::Hosts {
+                command: HostsCommand::Clean
+            }
+        ));
+        assert!(command(&["hosts"]).is_err());
+    }
+
+    #[test]
+    fn config_subcommands() {
+        assert!(matches!(
+            command(&["config", "init", "--sample"]).unwrap(),
+            Command::Config {
+                command: ConfigCommand::Init {
+                    force: false,
+                    sample: true
+                }
+            }
+        ));
+        assert!(matches!(
+            command(&["config", "show", "--origin"]).unwrap(),
+            Command::Config {
+                command: ConfigCommand::Show { origin: true }
+            }
+        ));
+        assert!(matches!(
+            command(&["config", "migrate"]).unwrap(),
+            Command::Config {
+                command: ConfigCommand::Migrate
+            }
+        ));
+        match command(&["config", "convert", "--to", "toml", "out.toml", "--force"]).unwrap() {
+            Command::Config {
+                command:
+                    ConfigCommand::Convert {
+                        to,
+                        destination,
+                        force,
+                    },
+            } => {
+                assert_eq!(to, ConfigFormat::Toml);
+                assert_eq!(destination, Some(PathBuf::from("out.toml")));
+                assert!(force);
+            }
+            _ => panic!("expected config convert"),
+        }
+        assert!(command(&["config", "convert"]).is_err());
+    }
+
+    #[test]
+    fn global_options_work_after_the_subcommand() {
+        let cli = parse(&[
+            "validate",
+            "-c",
+            "ffxi.yaml",
+            "--set",
+            "region=jp",
+            "--set",
+            "stagger_delay_seconds=5",
+            "--output",
+            "json",
+            "--log-level",
+            "debug",
+        ]);
+        assert_eq!(cli.global.config, PathBuf::from("ffxi.yaml"));
+        assert_eq!(cli.global.set, ["region=jp", "stagger_delay_seconds=5"]);
+        assert_eq!(cli.global.output, OutputFormat::Json);
+        assert_eq!(cli.global.log_level, Some(LogLevel::Debug));
+        assert_eq!(cli.global.config_format(), ConfigFormat::Yaml);
+    }
+
+    #[test]
+    fn defaults() {
+        let cli = parse(&[]);
+        assert_eq!(cli.global.config, PathBuf::from("config.json"));
+        assert_eq!(cli.global.output, OutputFormat::Text);
+        assert_eq!(cli.global.config_format(), ConfigFormat::Json);
+        assert!(Cli::try_parse_from(["login-rs", "--output", "xml"]).is_err());
+    }
+}
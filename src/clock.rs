@@ -0,0 +1,70 @@
+use chrono::{DateTime, Utc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Source of time for long-running modes, so their timing can be driven by a fake clock
+pub trait Clock {
+    fn now(&self) -> Instant;
+    /// Wall-clock time, for schedules
+    fn utc_now(&self) -> DateTime<Utc>;
+    fn sleep(&self, duration: Duration);
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn utc_now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// A clock that only moves when slept on or advanced, for tests
+#[cfg(test)]
+pub struct FakeClock {
+    start: Instant,
+    utc_start: DateTime<Utc>,
+    elapsed: std::sync::Mutex<Duration>,
+}
+
+#[cfg(test)]
+impl FakeClock {
+    pub fn new(utc_start: DateTime<Utc>) -> Self {
+        Self {
+            start: Instant::now(),
+            utc_start,
+            elapsed: std::sync::Mutex::new(Duration::ZERO),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+
+    /// Time passed since the clock was created
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+impl Clock for FakeClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn utc_now(&self) -> DateTime<Utc> {
+        self.utc_start + self.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
@@ -0,0 +1,191 @@
+use std::path::{Path, PathBuf};
+
+/// Expand a path field from the config:
+///
+/// - `${VAR}` and `%VAR%` are replaced with the environment variable's value
+///   (`%%` is a literal `%`, and a `%` with no closing `%` is kept as-is)
+/// - a leading `~` is replaced with the user's home directory
+/// - a path that is still relative is resolved against `base_dir` (the config file's directory)
+///
+/// `env` looks up environment variables, so expansion can be exercised without touching
+/// the real environment.
+pub fn expand_path(
+    raw: &Path,
+    base_dir: &Path,
+    env: &dyn Fn(&str) -> Option<String>,
+) -> Result<PathBuf, String> {
+    let raw = raw.to_string_lossy();
+    let expanded = expand_vars(&raw, env)?;
+    let expanded = expand_home(&expanded, env)?;
+
+    let path = PathBuf::from(expanded);
+    if path.is_absolute() || has_windows_root(&path) {
+        Ok(path)
+    } else {
+        Ok(base_dir.join(path))
+    }
+}
+
+/// Look up variables in the real process environment
+pub fn process_env(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+fn expand_vars(input: &str, env: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    let lookup = |name: &str| {
+        env(name).ok_or_else(|| format!("environment variable {} is not set", name))
+    };
+
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(i) = rest.find(['$', '%']) {
+        out.push_str(&rest[..i]);
+        let tail = &rest[i..];
+
+        if let Some(after) = tail.strip_prefix("${") {
+            let end = after
+                .find('}')
+                .ok_or_else(|| format!("unterminated '${{' in {:?}", input))?;
+            let name = &after[..end];
+            if name.is_empty() {
+                return Err(format!("empty variable name '${{}}' in {:?}", input));
+            }
+            out.push_str(&lookup(name)?);
+            rest = &after[end + 1..];
+        } else if let Some(after) = tail.strip_prefix("%%") {
+            out.push('%');
+            rest = after;
+        } else if let Some(after) = tail.strip_prefix('%') {
+            match after.find('%') {
+                Some(end) if is_var_name(&after[..end]) => {
+                    out.push_str(&lookup(&after[..end])?);
+                    rest = &after[end + 1..];
+                }
+                _ => {
+                    out.push('%');
+                    rest = after;
+                }
+            }
+        } else {
+            // A '$' not followed by '{' is literal
+            out.push('$');
+            rest = &tail[1..];
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn is_var_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '(' | ')'))
+}
+
+fn expand_home(input: &str, env: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    let Some(rest) = input.strip_prefix('~') else {
+        return Ok(input.to_string());
+    };
+    if !(rest.is_empty() || rest.starts_with('/') || rest.starts_with('\\')) {
+        // `~user` style paths aren't supported; leave them alone
+        return Ok(input.to_string());
+    }
+    let home = env("USERPROFILE")
+        .or_else(|| env("HOME"))
+        .ok_or("cannot expand '~': neither USERPROFILE nor HOME is set")?;
+    Ok(format!("{}{}", home, rest))
+}
+
+/// `C:\...` or `\\server\share` paths count as absolute even when login-rs is built for
+/// another platform, so configs written for Windows aren't re-rooted under the config directory.
+fn has_windows_root(path: &Path) -> bool {
+    let s = path.to_string_lossy();
+    let bytes = s.as_bytes();
+    let drive = bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && (bytes[2] == b'\\' || bytes[2] == b'/');
+    drive || s.starts_with(r"\\")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(name: &str) -> Option<String> {
+        match name {
+            "DRIVE" => Some("D:".into()),
+            "PROGRAMFILES(X86)" => Some(r"C:\Program Files (x86)".into()),
+            "USERPROFILE" => Some(r"C:\Users\me".into()),
+            _ => None,
+        }
+    }
+
+    fn expand(raw: &str) -> Result<String, String> {
+        expand_path(Path::new(raw), Path::new("/configs"), &env).map(|p| p.display().to_string())
+    }
+
+    #[test]
+    fn braced_and_percent_variables_are_replaced() {
+        let windower = r"D:\Windower4\Windower.exe";
+        assert_eq!(expand(r"${DRIVE}\Windower4\Windower.exe").unwrap(), windower);
+        assert_eq!(expand(r"%DRIVE%\Windower4\Windower.exe").unwrap(), windower);
+        assert_eq!(
+            expand(r"%PROGRAMFILES(X86)%\PlayOnline").unwrap(),
+            r"C:\Program Files (x86)\PlayOnline"
+        );
+        assert_eq!(expand(r"%DRIVE%\${DRIVE}").unwrap(), r"D:\D:");
+    }
+
+    #[test]
+    fn unbraced_dollar_and_unmatched_percent_are_literal() {
+        // `$VAR` isn't a variable: `$` is common in Windows paths, e.g. `C:\$Recycle.Bin`
+        assert_eq!(expand(r"C:\$DRIVE\x").unwrap(), r"C:\$DRIVE\x");
+        assert_eq!(expand(r"C:\100%%\x").unwrap(), r"C:\100%\x");
+        assert_eq!(expand(r"C:\50% off\x").unwrap(), r"C:\50% off\x");
+        assert_eq!(expand(r"C:\a%b c%d").unwrap(), r"C:\a%b c%d");
+    }
+
+    #[test]
+    fn undefined_variables_are_errors() {
+        assert_eq!(
+            expand(r"${MISSING}\Windower.exe").unwrap_err(),
+            "environment variable MISSING is not set"
+        );
+        assert_eq!(
+            expand(r"%MISSING%\Windower.exe").unwrap_err(),
+            "environment variable MISSING is not set"
+        );
+        assert!(expand(r"${DRIVE\x").unwrap_err().starts_with("unterminated '${'"));
+        assert!(expand(r"${}\x").unwrap_err().starts_with("empty variable name"));
+    }
+
+    #[test]
+    fn tilde_is_the_home_directory() {
+        assert_eq!(
+            expand(r"~\Windower4\Windower.exe").unwrap(),
+            r"C:\Users\me\Windower4\Windower.exe"
+        );
+        assert_eq!(expand("~").unwrap(), r"C:\Users\me");
+        // Only a leading `~` on its own, not `~user` or one mid-path
+        assert_eq!(expand(r"D:\~\x").unwrap(), r"D:\~\x");
+        assert_eq!(expand(r"~user\x").unwrap(), r"/configs/~user\x");
+
+        let home_only = |name: &str| (name == "HOME").then(|| "/home/me".to_string());
+        let path = expand_path(Path::new("~/ffxi"), Path::new("/configs"), &home_only).unwrap();
+        assert_eq!(path, PathBuf::from("/home/me/ffxi"));
+        let no_home = expand_path(Path::new("~/ffxi"), Path::new("/configs"), &|_| None);
+        assert!(no_home.unwrap_err().contains("neither USERPROFILE nor HOME is set"));
+    }
+
+    #[test]
+    fn relative_paths_resolve_against_the_base_dir() {
+        assert_eq!(expand("Windower4/Windower.exe").unwrap(), "/configs/Windower4/Windower.exe");
+        assert_eq!(expand("/opt/Windower.exe").unwrap(), "/opt/Windower.exe");
+        assert_eq!(expand(r"C:/Windower4/Windower.exe").unwrap(), r"C:/Windower4/Windower.exe");
+        assert_eq!(expand(r"\\server\share\Windower.exe").unwrap(), r"\\server\share\Windower.exe");
+        // A variable that expands to an absolute path isn't re-rooted
+        assert_eq!(expand(r"${DRIVE}\x").unwrap(), r"D:\x");
+    }
+}
@@ -0,0 +1,236 @@
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::path::Path;
+
+/// On-disk config file formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Detect the format from a file extension, if it is one we recognize
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "json" => Some(ConfigFormat::Json),
+            "toml" => Some(ConfigFormat::Toml),
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            _ => None,
+        }
+    }
+
+    /// The explicit override if given, else the format implied by the extension, else JSON
+    pub fn resolve(path: &Path, explicit: Option<Self>) -> Self {
+        explicit
+            .or_else(|| Self::from_path(path))
+            .unwrap_or(ConfigFormat::Json)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ConfigFormat::Json => "JSON",
+            ConfigFormat::Toml => "TOML",
+            ConfigFormat::Yaml => "YAML",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ConfigFormat::Json => "json",
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Yaml => "yaml",
+        }
+    }
+
+    /// Deserialize directly from text. Errors include line/column where the format supports it.
+    pub fn deserialize<T: DeserializeOwned>(self, contents: &str) -> Result<T, String> {
+        match self {
+            ConfigFormat::Json => serde_json::from_str(contents).map_err(|e| e.to_string()),
+            ConfigFormat::Toml => toml::from_str(contents).map_err(|e| e.to_string().trim_end().to_string()),
+            ConfigFormat::Yaml => serde_yaml::from_str(contents).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Parse text into a format-neutral JSON value, for migration and merging
+    pub fn parse_value(self, contents: &str) -> Result<Value, String> {
+        let value: Value = self.deserialize(contents)?;
+        // An empty YAML document parses as null; treat it like an empty object
+        Ok(if value.is_null() { Value::Object(Default::default()) } else { value })
+    }
+
+    /// Serialize a config value in this format
+    pub fn serialize(self, value: &Value) -> Result<String, String> {
+        match self {
+            ConfigFormat::Json => serde_json::to_string_pretty(value)
+                .map(|s| s + "\n")
+                .map_err(|e| e.to_string()),
+            // TOML has no null, so unset optional values are dropped
+            ConfigFormat::Toml => toml::to_string_pretty(&strip_nulls(value)).map_err(|e| e.to_string()),
+            ConfigFormat::Yaml => serde_yaml::to_string(value).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+fn strip_nulls(value: &Value) -> Value {
+    match value {
+        Value::Object(obj) => Value::Object(
+            obj.iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k.clone(), strip_nulls(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(strip_nulls).collect()),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{convert, Config};
+
+    const JSON: &str = r#"{
+  "version": 2,
+  "windower_path": "D:\\Windower4\\Windower.exe",
+  "windower_profile": null,
+  "stagger_delay_seconds": 5,
+  "region": "eu",
+  "groups": { "mules": ["MyMule"] },
+  "webhooks": [{ "url": "http://localhost:9000/hook", "events": ["character_failed"] }],
+  "input": { "strategy": "unicode", "max_block_seconds": 30 },
+  "characters": [
+    { "name": "MyWarrior", "slot": 1, "password": "Passw0rd!", "job": "WAR/NIN" },
+    { "name": "MyMule", "slot": 2, "password_env": "MULE_PASSWORD" }
+  ]
+}"#;
+
+    const TOML: &str = r#"version = 2
+windower_path = 'D:\Windower4\Windower.exe'
+stagger_delay_seconds = 5
+region = "eu"
+
+[groups]
+mules = ["MyMule"]
+
+[[webhooks]]
+url = "http://localhost:9000/hook"
+events = ["character_failed"]
+
+[input]
+strategy = "unicode"
+max_block_seconds = 30
+
+[[characters]]
+name = "MyWarrior"
+slot = 1
+password = "Passw0rd!"
+job = "WAR/NIN"
+
+[[characters]]
+name = "MyMule"
+slot = 2
+password_env = "MULE_PASSWORD"
+"#;
+
+    const YAML: &str = r#"version: 2
+windower_path: 'D:\Windower4\Windower.exe'
+windower_profile: ~
+stagger_delay_seconds: 5
+region: eu
+groups:
+  mules: [MyMule]
+webhooks:
+  - url: http://localhost:9000/hook
+    events: [character_failed]
+input:
+  strategy: unicode
+  max_block_seconds: 30
+characters:
+  - name: MyWarrior
+    slot: 1
+    password: Passw0rd!
+    job: WAR/NIN
+  - name: MyMule
+    slot: 2
+    password_env: MULE_PASSWORD
+"#;
+
+    const FIXTURES: [(ConfigFormat, &str); 3] = [
+        (ConfigFormat::Json, JSON),
+        (ConfigFormat::Toml, TOML),
+        (ConfigFormat::Yaml, YAML),
+    ];
+
+    fn load(format: ConfigFormat, contents: &str) -> Config {
+        Config::parse(contents, format).unwrap_or_else(|e| panic!("{}: {}", format.name(), e))
+    }
+
+    #[test]
+    fn formats_load_the_same_config() {
+        let json = load(ConfigFormat::Json, JSON);
+        assert_eq!(json.windower_profile, None);
+        assert_eq!(json.characters[1].password_env.as_deref(), Some("MULE_PASSWORD"));
+        assert_eq!(load(ConfigFormat::Toml, TOML), json);
+        assert_eq!(load(ConfigFormat::Yaml, YAML), json);
+    }
+
+    #[test]
+    fn convert_round_trips_between_every_format() {
+        let expected = load(ConfigFormat::Json, JSON);
+        for (from, contents) in FIXTURES {
+            for to in [ConfigFormat::Json, ConfigFormat::Toml, ConfigFormat::Yaml] {
+                let converted = convert(contents, from, to)
+                    .unwrap_or_else(|e| panic!("{} -> {}: {}", from.name(), to.name(), e));
+                assert_eq!(load(to, &converted), expected, "{} -> {}", from.name(), to.name());
+                let back = convert(&converted, to, from).unwrap();
+                assert_eq!(load(from, &back), expected, "{} -> {} -> back", from.name(), to.name());
+            }
+        }
+    }
+
+    #[test]
+    fn toml_drops_nulls() {
+        let toml = convert(JSON, ConfigFormat::Json, ConfigFormat::Toml).unwrap();
+        assert!(!toml.contains("windower_profile"), "{}", toml);
+        // JSON and YAML keep them
+        let yaml = convert(JSON, ConfigFormat::Json, ConfigFormat::Yaml).unwrap();
+        assert!(yaml.contains("windower_profile: null"), "{}", yaml);
+
+        let nested = serde_json::json!({ "a": null, "b": [{ "c": null, "d": 1 }], "e": { "f": null } });
+        assert_eq!(
+            strip_nulls(&nested),
+            serde_json::json!({ "b": [{ "d": 1 }], "e": {} })
+        );
+    }
+
+    #[test]
+    fn convert_upgrades_old_configs() {
+        let v1 = r#"{ "characters": [{ "name": "A", "slot": 1, "password": "Passw0rd!" }] }"#;
+        let toml = convert(v1, ConfigFormat::Json, ConfigFormat::Toml).unwrap();
+        assert!(toml.starts_with("version = 2\n"), "{}", toml);
+    }
+
+    #[test]
+    fn errors_carry_the_position() {
+        let err = ConfigFormat::Toml.parse_value("version = 2\nregion = \n").unwrap_err();
+        assert!(err.contains("line 2"), "{}", err);
+        let err = ConfigFormat::Json.parse_value("{\n  \"version\": 2,\n}").unwrap_err();
+        assert!(err.contains("line 3"), "{}", err);
+        assert_eq!(ConfigFormat::Yaml.parse_value("").unwrap(), serde_json::json!({}));
+    }
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(ConfigFormat::from_path(Path::new("a/config.YML")), Some(ConfigFormat::Yaml));
+        assert_eq!(ConfigFormat::from_path(Path::new("config.toml")), Some(ConfigFormat::Toml));
+        assert_eq!(ConfigFormat::from_path(Path::new("config")), None);
+        assert_eq!(ConfigFormat::resolve(Path::new("config.txt"), None), ConfigFormat::Json);
+        assert_eq!(
+            ConfigFormat::resolve(Path::new("config.json"), Some(ConfigFormat::Yaml)),
+            ConfigFormat::Yaml
+        );
+    }
+}
@@ -0,0 +1,671 @@
+use super::{config_dir, expand, find_key_line, find_unknown_fields, migrate, ConfigFormat};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Origin recorded for values given with `--set`
+pub const SET_ORIGIN: &str = "--set";
+
+/// One source of config values. Layers are merged in order, later ones winning.
+pub struct Layer {
+    /// File the values came from, or `--set`
+    pub origin: String,
+    pub format: ConfigFormat,
+    /// Original text, for line numbers in errors. `None` for `--set`.
+    pub contents: Option<String>,
+    pub value: Value,
+    /// The config file named on the command line, as opposed to an include or override
+    pub is_main: bool,
+}
+
+/// The result of merging every layer
+pub struct Merged {
+    pub value: Value,
+    /// Which layer set each value, keyed like `region` or `characters[MyWarrior].slot`
+    pub origins: HashMap<String, String>,
+}
+
+/// `config.json` -> `config.local.json`
+pub fn local_override_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}.local.{}", stem, ext.to_string_lossy()),
+        None => format!("{}.local", stem),
+    };
+    path.with_file_name(name)
+}
+
+/// Read the config at `path` along with everything layered on it, in merge order:
+/// its includes (recursively, each before the file that includes it), the file itself,
+/// the local override file if present, and finally `--set` overrides.
+pub fn read_layers(path: &Path, format: ConfigFormat, overrides: &[String]) -> Result<Vec<Layer>, String> {
+    let mut layers = Vec::new();
+    read_file(path, format, true, &mut Vec::new(), &mut layers)?;
+
+    let local = local_override_path(path);
+    if local.is_file() {
+        let local_format = ConfigFormat::from_path(&local).unwrap_or(format);
+        read_file(&local, local_format, false, &mut Vec::new(), &mut layers)?;
+    }
+
+    if !overrides.is_empty() {
+        let mut value = Value::Object(Map::new());
+        for arg in overrides {
+            let set = parse_set(arg)?;
+            merge_value(&mut value, &set, "", SET_ORIGIN, &mut HashMap::new())?;
+        }
+        layers.push(Layer {
+            origin: SET_ORIGIN.to_string(),
+            format: ConfigFormat::Json,
+            contents: None,
+            value,
+            is_main: false,
+        });
+    }
+
+    Ok(layers)
+}
+
+/// Files whose changes affect the config at `path`: every file layer, plus the local
+/// override file even if it doesn't exist yet
+pub fn source_files(path: &Path, layers: &[Layer]) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = layers
+        .iter()
+        .filter(|l| l.contents.is_some())
+        .map(|l| PathBuf::from(&l.origin))
+        .collect();
+    let local = local_override_path(path);
+    if !files.contains(&local) {
+        files.push(local);
+    }
+    files
+}
+
+fn read_file(
+    path: &Path,
+    format: ConfigFormat,
+    is_main: bool,
+    including: &mut Vec<PathBuf>,
+    layers: &mut Vec<Layer>,
+) -> Result<(), String> {
+    // Errors in the main file are reported by the caller alongside its path
+    let in_file = |e: String| {
+        if is_main {
+            e
+        } else {
+            format!("{}: {}", path.display(), e)
+        }
+    };
+
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if including.contains(&canonical) {
+        let chain: Vec<String> = including
+            .iter()
+            .chain(std::iter::once(&canonical))
+            .map(|p| p.display().to_string())
+            .collect();
+        return Err(format!("include cycle: {}", chain.join(" -> ")));
+    }
+
+    let contents = fs::read_to_string(path).map_err(|e| in_file(e.to_string()))?;
+    let value = format
+        .parse_value(&contents)
+        .map_err(|e| in_file(format!("Invalid {}: {}", format.name(), e)))?;
+
+    let includes = match value.get("include") {
+        None => Vec::new(),
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| {
+                let raw = item.as_str().ok_or("'include' entries must be file paths")?;
+                expand::expand_path(Path::new(raw), config_dir(path), &expand::process_env)
+            })
+            .collect::<Result<Vec<_>, String>>()
+            .map_err(|e| in_file(format!("include: {}", e)))?,
+        Some(_) => return Err(in_file("'include' must be a list of file paths".into())),
+    };
+
+    including.push(canonical);
+    for include in includes {
+        let include_format = ConfigFormat::from_path(&include).unwrap_or(format);
+        read_file(&include, include_format, false, including, layers)?;
+    }
+    including.pop();
+
+    layers.push(Layer {
+        origin: path.display().to_string(),
+        format,
+        contents: Some(contents),
+        value,
+        is_main,
+    });
+    Ok(())
+}
+
+/// Directory a relative path in the top-level `field` is resolved against: that of the
+/// file that set it last. Values from `--set`, and defaults, use `config_dir`.
+pub fn base_dir(layers: &[Layer], field: &str, config_dir: &Path) -> PathBuf {
+    let setter = layers.iter().rev().find(|l| l.value.get(field).is_some());
+    match setter {
+        Some(layer) if layer.contents.is_some() && !layer.value[field].is_null() => {
+            super::config_dir(Path::new(&layer.origin)).to_path_buf()
+        }
+        _ => config_dir.to_path_buf(),
+    }
+}
+
+/// Version of the main config file, which layers without a `version` are assumed to match
+pub fn main_version(layers: &[Layer]) -> Result<u32, String> {
+    Ok(layers
+        .iter()
+        .find(|l| l.is_main)
+        .map(|l| migrate::detect_version(&l.value))
+        .transpose()?
+        .unwrap_or(migrate::CURRENT_VERSION))
+}
+
+/// A layer's values upgraded to the current schema, without `include`, and the version
+/// it was at
+pub fn upgrade(layer: &Layer, main_version: u32) -> Result<(Value, u32), String> {
+    let mut value = layer.value.clone();
+    let has_version = value.get("version").is_some();
+    if let Some(obj) = value.as_object_mut() {
+        obj.remove("include");
+        if !has_version {
+            obj.insert("version".into(), Value::from(main_version));
+        }
+    }
+    let from = migrate::migrate(&mut value).map_err(|e| format!("{}: {}", layer.origin, e))?;
+    if !has_version {
+        // Only the layers that set `version` should show up as its origin
+        if let Some(obj) = value.as_object_mut() {
+            obj.remove("version");
+        }
+    }
+    Ok((value, from))
+}
+
+/// Turn `key=value` into a partial config. Keys are dotted paths, with characters picked
+/// by name: `characters[MyWarrior].slot=3`. Values are parsed as JSON if possible, so
+/// `5` is a number and `jp` a string; quote a value (`"123"`) to force a string.
+pub fn parse_set(arg: &str) -> Result<Value, String> {
+    let (key, raw) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("--set {}: expected KEY=VALUE", arg))?;
+    let raw = raw.trim();
+    let mut value = serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()));
+
+    for segment in key.trim().split('.').rev() {
+        let (field, name) = match segment.split_once('[') {
+            Some((field, rest)) => {
+                let name = rest
+                    .strip_suffix(']')
+                    .filter(|n| !n.is_empty())
+                    .ok_or_else(|| format!("--set {}: expected {}[Name]", arg, field))?;
+                (field, Some(name))
+            }
+            None => (segment, None),
+        };
+        if field.is_empty() {
+            return Err(format!("--set {}: empty key", arg));
+        }
+
+        value = match name {
+            Some(name) => {
+                let Value::Object(mut entry) = value else {
+                    return Err(format!(
+                        "--set {}: {}[{}] must be followed by a field, e.g. {}[{}].slot=3",
+                        arg, field, name, field, name
+                    ));
+                };
+                entry.insert("name".into(), Value::String(name.to_string()));
+                single(field, Value::Array(vec![Value::Object(entry)]))
+            }
+            None => single(field, value),
+        };
+    }
+    Ok(value)
+}
+
+fn single(key: &str, value: Value) -> Value {
+    let mut obj = Map::new();
+    obj.insert(key.to_string(), value);
+    Value::Object(obj)
+}
+
+/// First unknown key in any layer, with the layer and line it appears on. Layers are
+/// upgraded first, so keys that older versions ignored are dropped rather than reported.
+pub fn check_unknown_fields(layers: &[Layer]) -> Result<(), String> {
+    let main_version = main_version(layers)?;
+    for layer in layers {
+        let (value, _) = upgrade(layer, main_version)?;
+        if let Some(unknown) = find_unknown_fields(&value).into_iter().next() {
+            let line = layer
+                .contents
+                .as_deref()
+                .and_then(|c| find_key_line(c, &unknown.field))
+                .map(|l| format!(" at line {}", l))
+                .unwrap_or_default();
+            let origin = if layer.is_main {
+                String::new()
+            } else {
+                format!("{}: ", layer.origin)
+            };
+            return Err(format!("{}{}{}", origin, unknown, line));
+        }
+    }
+    Ok(())
+}
+
+/// Upgrade each layer to the current schema and merge them.
+///
+/// Objects are merged key by key and `characters` entries are matched by name
+/// (case-insensitively), so an override only needs the fields it changes. Any other
+/// value, including other lists, replaces what came before, and `null` removes it.
+/// Layers without a `version` are assumed to match the main file.
+pub fn merge(layers: &[Layer]) -> Result<Merged, String> {
+    let main_version = main_version(layers)?;
+
+    let mut merged = Merged {
+        value: Value::Object(Map::new()),
+        origins: HashMap::new(),
+    };
+    for layer in layers {
+        let (value, from) = upgrade(layer, main_version)?;
+        if from != migrate::CURRENT_VERSION {
+            log::info!(
+                "{} is version {}, upgraded to {} in memory (run `login-rs config migrate` to update the file)",
+                layer.origin,
+                from,
+                migrate::CURRENT_VERSION
+            );
+        }
+        merge_value(&mut merged.value, &value, "", &layer.origin, &mut merged.origins)
+            .map_err(|e| format!("{}: {}", layer.origin, e))?;
+    }
+    Ok(merged)
+}
+
+fn merge_value(
+    base: &mut Value,
+    overlay: &Value,
+    path: &str,
+    origin: &str,
+    origins: &mut HashMap<String, String>,
+) -> Result<(), String> {
+    let (Value::Object(base), Value::Object(overlay)) = (&mut *base, overlay) else {
+        forget(origins, path);
+        record(overlay, path, origin, origins);
+        *base = overlay.clone();
+        return Ok(());
+    };
+
+    for (key, value) in overlay {
+        let key_path = join(path, key);
+        if value.is_null() {
+            base.remove(key);
+            forget(origins, &key_path);
+            continue;
+        }
+        if key_path == "characters" {
+            if let Value::Array(entries) = value {
+                if !base.get(key).is_some_and(Value::is_array) {
+                    base.insert(key.clone(), Value::Array(Vec::new()));
+                }
+                if let Some(Value::Array(existing)) = base.get_mut(key) {
+                    merge_characters(existing, entries, origin, origins)?;
+                }
+                continue;
+            }
+        }
+        match base.get_mut(key) {
+            Some(existing) if existing.is_object() && value.is_object() => {
+                merge_value(existing, value, &key_path, origin, origins)?
+            }
+            _ => {
+                forget(origins, &key_path);
+                record(value, &key_path, origin, origins);
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn merge_characters(
+    base: &mut Vec<Value>,
+    overlay: &[Value],
+    origin: &str,
+    origins: &mut HashMap<String, String>,
+) -> Result<(), String> {
+    for (i, entry) in overlay.iter().enumerate() {
+        let name = entry
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| format!("characters[{}] has no name to merge by", i))?;
+
+        let existing = base.iter_mut().find(|c| {
+            c.get("name")
+                .and_then(Value::as_str)
+                .is_some_and(|n| n.eq_ignore_ascii_case(name))
+        });
+        match existing {
+            Some(existing) => {
+                // The name only picks the entry to merge into; keep the original spelling
+                let mut entry = entry.clone();
+                if let Some(obj) = entry.as_object_mut() {
+                    obj.remove("name");
+                }
+                let path = format!("characters[{}]", character_name(existing));
+                merge_value(existing, &entry, &path, origin, origins)?;
+            }
+            None => {
+                let path = format!("characters[{}]", name);
+                record(entry, &path, origin, origins);
+                base.push(entry.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Key for a value nested under `path`
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn character_name(character: &Value) -> &str {
+    character.get("name").and_then(Value::as_str).unwrap_or_default()
+}
+
+/// Every leaf under `path`, in document order, keyed the same way as `Merged::origins`
+pub fn flatten(value: &Value, path: &str) -> Vec<(String, Value)> {
+    let mut leaves = Vec::new();
+    flatten_into(value, path, &mut leaves);
+    leaves
+}
+
+fn flatten_into(value: &Value, path: &str, leaves: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Object(obj) => {
+            for (key, v) in obj {
+                flatten_into(v, &join(path, key), leaves);
+            }
+        }
+        Value::Array(items) if path == "characters" => {
+            for item in items {
+                flatten_into(item, &format!("characters[{}]", character_name(item)), leaves);
+            }
+        }
+        other => leaves.push((path.to_string(), other.clone())),
+    }
+}
+
+fn record(value: &Value, path: &str, origin: &str, origins: &mut HashMap<String, String>) {
+    for (key, _) in flatten(value, path) {
+        origins.insert(key, origin.to_string());
+    }
+}
+
+/// Drop origins for `path` and everything under it
+fn forget(origins: &mut HashMap<String, String>, path: &str) {
+    if path.is_empty() {
+        origins.clear();
+        return;
+    }
+    origins.retain(|key, _| {
+        !(key == path
+            || key.strip_prefix(path).is_some_and(|rest| rest.starts_with('.') || rest.starts_with('[')))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    /// A fresh directory of config files, removed on drop
+    struct Dir(PathBuf);
+
+    impl Dir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("login-rs-layers-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Dir(dir)
+        }
+
+        fn write(&self, name: &str, contents: &str) -> PathBuf {
+            let path = self.0.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for Dir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const MAIN: &str = r#"{
+        "version": 2,
+        "include": ["team/shared.json"],
+        "playonline_dir": "pol",
+        "characters": [{"name": "MyWarrior", "slot": 1, "password": "Passw0rd!"}]
+    }"#;
+
+    fn origin(merged: &Merged, key: &str) -> String {
+        merged.origins.get(key).cloned().unwrap_or_default()
+    }
+
+    fn shown(path: &Path) -> String {
+        path.display().to_string()
+    }
+
+    #[test]
+    fn includes_merge_before_the_file_that_includes_them() {
+        let dir = Dir::new("order");
+        let main = dir.write(
+            "config.json",
+            r#"{"version": 2, "include": ["a.json", "b.json"], "region": "jp"}"#,
+        );
+        let a = dir.write(
+            "a.json",
+            r#"{"include": ["base.json"], "stagger_delay_seconds": 2, "region": "eu"}"#,
+        );
+        let b = dir.write("b.json", r#"{"stagger_delay_seconds": 3}"#);
+        let base = dir.write(
+            "base.json",
+            r#"{"stagger_delay_seconds": 1, "login_delay_seconds": 9}"#,
+        );
+
+        let layers = read_layers(&main, ConfigFormat::Json, &[]).unwrap();
+        let order: Vec<&str> = layers.iter().map(|l| l.origin.as_str()).collect();
+        assert_eq!(order, [shown(&base), shown(&a), shown(&b), shown(&main)]);
+        assert!(layers.iter().filter(|l| l.is_main).map(|l| &l.origin).eq([&shown(&main)]));
+
+        let merged = merge(&layers).unwrap();
+        assert_eq!(merged.value["stagger_delay_seconds"], 3);
+        assert_eq!(merged.value["login_delay_seconds"], 9);
+        assert_eq!(merged.value["region"], "jp");
+        assert!(merged.value.get("include").is_none());
+        assert_eq!(origin(&merged, "stagger_delay_seconds"), shown(&b));
+        assert_eq!(origin(&merged, "login_delay_seconds"), shown(&base));
+        assert_eq!(origin(&merged, "region"), shown(&main));
+        assert_eq!(origin(&merged, "version"), shown(&main));
+    }
+
+    #[test]
+    fn include_cycles_are_errors() {
+        let dir = Dir::new("cycle");
+        let main = dir.write("config.json", r#"{"include": ["a.json"]}"#);
+        dir.write("a.json", r#"{"include": ["config.json"]}"#);
+
+        let err = read_layers(&main, ConfigFormat::Json, &[]).err().unwrap();
+        assert!(err.contains("include cycle:"), "{}", err);
+        assert!(err.contains("a.json ->"), "{}", err);
+    }
+
+    #[test]
+    fn local_file_merges_characters_by_name() {
+        let dir = Dir::new("local");
+        let main = dir.write(
+            "config.json",
+            r#"{
+                "version": 2,
+                "stagger_delay_seconds": 2,
+                "characters": [
+                    {"name": "MyWarrior", "slot": 1, "password": "Passw0rd!", "job": "WAR"},
+                    {"name": "MyMage", "slot": 2, "password": "Passw0rd!"}
+                ]
+            }"#,
+        );
+        let local = dir.write(
+            "config.local.json",
+            r#"{
+                "stagger_delay_seconds": 4,
+                "characters": [
+                    {"name": "mywarrior", "password": null, "password_env": "WAR_PW", "job": null},
+                    {"name": "MyThief", "slot": 3, "password": "Passw0rd!"}
+                ]
+            }"#,
+        );
+        assert_eq!(local_override_path(&main), local);
+
+        let layers = read_layers(&main, ConfigFormat::Json, &[]).unwrap();
+        assert_eq!(layers.len(), 2);
+        let merged = merge(&layers).unwrap();
+        assert_eq!(merged.value["stagger_delay_seconds"], 4);
+        assert_eq!(
+            merged.value["characters"],
+            serde_json::json!([
+                {"name": "MyWarrior", "slot": 1, "password_env": "WAR_PW"},
+                {"name": "MyMage", "slot": 2, "password": "Passw0rd!"},
+                {"name": "MyThief", "slot": 3, "password": "Passw0rd!"}
+            ])
+        );
+        assert_eq!(origin(&merged, "characters[MyWarrior].slot"), shown(&main));
+        assert_eq!(origin(&merged, "characters[MyWarrior].password_env"), shown(&local));
+        assert_eq!(origin(&merged, "characters[MyThief].slot"), shown(&local));
+        assert!(!merged.origins.contains_key("characters[MyWarrior].password"));
+        assert!(!merged.origins.contains_key("characters[MyWarrior].job"));
+
+        let unnamed = Layer {
+            origin: "extra.json".into(),
+            format: ConfigFormat::Json,
+            contents: Some(String::new()),
+            value: serde_json::json!({"characters": [{"slot": 4}]}),
+            is_main: false,
+        };
+        let mut layers = layers;
+        layers.push(unnamed);
+        assert_eq!(
+            merge(&layers).err().unwrap(),
+            "extra.json: characters[0] has no name to merge by"
+        );
+    }
+
+    #[test]
+    fn set_overrides_win_over_every_file() {
+        let dir = Dir::new("set");
+        let main = dir.write("config.json", MAIN);
+        dir.write("team/shared.json", r#"{"region": "eu"}"#);
+        dir.write(
+            "config.local.json",
+            r#"{"region": "jp", "characters": [{"name": "MyWarrior", "slot": 2}]}"#,
+        );
+
+        let set = [
+            "region=us".to_string(),
+            "characters[mywarrior].slot=3".to_string(),
+            "characters[MyWarrior].job=\"123\"".to_string(),
+        ];
+        let layers = read_layers(&main, ConfigFormat::Json, &set).unwrap();
+        assert_eq!(layers.last().unwrap().origin, SET_ORIGIN);
+
+        let merged = merge(&layers).unwrap();
+        assert_eq!(merged.value["region"], "us");
+        assert_eq!(merged.value["characters"][0]["name"], "MyWarrior");
+        assert_eq!(merged.value["characters"][0]["slot"], 3);
+        assert_eq!(merged.value["characters"][0]["job"], "123");
+        assert_eq!(origin(&merged, "region"), SET_ORIGIN);
+        assert_eq!(origin(&merged, "characters[MyWarrior].slot"), SET_ORIGIN);
+        assert_eq!(origin(&merged, "characters[MyWarrior].password"), shown(&main));
+    }
+
+    #[test]
+    fn set_values_are_parsed_as_json_where_possible() {
+        let parse = |arg: &str| parse_set(arg).unwrap();
+        use serde_json::json;
+        assert_eq!(parse("stagger_delay_seconds=5"), json!({"stagger_delay_seconds": 5}));
+        assert_eq!(parse("region = jp"), json!({"region": "jp"}));
+        assert_eq!(parse("windower_profile=\"123\""), json!({"windower_profile": "123"}));
+        assert_eq!(parse("layout.gap=8"), json!({"layout": {"gap": 8}}));
+
+        for (arg, error) in [
+            ("region", "expected KEY=VALUE"),
+            ("=jp", "empty key"),
+            ("characters[].slot=3", "expected characters[Name]"),
+            ("characters[MyWarrior]=3", "must be followed by a field"),
+        ] {
+            let err = parse_set(arg).unwrap_err();
+            assert!(err.contains(error), "{}: {}", arg, err);
+        }
+    }
+
+    #[test]
+    fn show_origin_lists_every_value_with_its_layer() {
+        let dir = Dir::new("show");
+        let main = dir.write("config.json", MAIN);
+        let shared = dir.write("team/shared.json", r#"{"api": {"token": "s3cret-token"}}"#);
+
+        let set = ["stagger_delay_seconds=7".to_string()];
+        let layers = read_layers(&main, ConfigFormat::Json, &set).unwrap();
+        let effective = crate::config::effective(&layers).unwrap();
+
+        let leaves: HashMap<String, Value> = flatten(&effective.value, "").into_iter().collect();
+        assert_eq!(leaves["characters[MyWarrior].password"], "********");
+        assert_eq!(leaves["api.token"], "********");
+        assert_eq!(leaves["stagger_delay_seconds"], 7);
+
+        assert_eq!(origin(&effective, "stagger_delay_seconds"), SET_ORIGIN);
+        assert_eq!(origin(&effective, "api.token"), shown(&shared));
+        assert_eq!(origin(&effective, "playonline_dir"), shown(&main));
+        assert_eq!(origin(&effective, "characters[MyWarrior].name"), shown(&main));
+        // Defaults have no origin; `config show` labels them "default"
+        assert!(leaves.contains_key("region"));
+        assert!(!effective.origins.contains_key("region"));
+    }
+
+    #[test]
+    fn relative_paths_resolve_against_the_file_that_set_them() {
+        let dir = Dir::new("paths");
+        let main = dir.write("config.json", MAIN);
+        dir.write("team/shared.json", r#"{"windower_path": "Windower4/Windower.exe"}"#);
+
+        let layers = read_layers(&main, ConfigFormat::Json, &[]).unwrap();
+        let config = Config::build(&layers, &dir.0).unwrap();
+        let shared_dir = dir.0.join("team");
+        assert_eq!(config.windower_path, shared_dir.join("Windower4/Windower.exe"));
+        assert_eq!(config.playonline_dir, dir.0.join("pol"));
+
+        // `--set` values, like the main file's, are relative to the main file
+        let set = ["windower_path=bin/Windower.exe".to_string()];
+        let layers = read_layers(&main, ConfigFormat::Json, &set).unwrap();
+        let config = Config::build(&layers, &dir.0).unwrap();
+        assert_eq!(config.windower_path, dir.0.join("bin/Windower.exe"));
+
+        // Removed with `null`, the absolute default is left alone
+        dir.write("config.local.json", r#"{"windower_path": null}"#);
+        let layers = read_layers(&main, ConfigFormat::Json, &[]).unwrap();
+        assert_eq!(base_dir(&layers, "windower_path", &dir.0), dir.0);
+    }
+}
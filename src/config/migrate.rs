@@ -0,0 +1,246 @@
+use super::{find_unknown_fields, ConfigFormat};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Schema version written by this build. Bump this and append to `MIGRATIONS`
+/// whenever the config layout changes.
+pub const CURRENT_VERSION: u32 = 2;
+
+/// Configs without a `version` field predate versioning and are treated as version 1
+const UNVERSIONED: u32 = 1;
+
+type Migration = fn(&mut Map<String, Value>) -> Result<(), String>;
+
+/// `MIGRATIONS[i]` upgrades a config from version `i + 1` to `i + 2`
+const MIGRATIONS: &[Migration] = &[v1_to_v2];
+
+/// Version 1 (the original release layout) ignored keys it didn't know, where
+/// version 2 rejects them. Drop them, as version 1 did, so old files still load.
+fn v1_to_v2(config: &mut Map<String, Value>) -> Result<(), String> {
+    let mut value = Value::Object(std::mem::take(config));
+    for unknown in find_unknown_fields(&value) {
+        log::warn!("Ignoring {} (removed when the config is migrated)", unknown);
+        if let Some(obj) = value.pointer_mut(&unknown.pointer).and_then(Value::as_object_mut) {
+            obj.remove(&unknown.field);
+        }
+    }
+    if let Value::Object(obj) = value {
+        *config = obj;
+    }
+    Ok(())
+}
+
+/// Read the `version` field of a raw config
+pub fn detect_version(value: &Value) -> Result<u32, String> {
+    match value.get("version") {
+        None => Ok(UNVERSIONED),
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .filter(|v| *v >= 1)
+            .ok_or_else(|| format!("'version' must be a positive integer, found {}", v)),
+    }
+}
+
+/// Upgrade a raw config in place to `CURRENT_VERSION`, returning the version it started at
+pub fn migrate(value: &mut Value) -> Result<u32, String> {
+    let from = detect_version(value)?;
+    if from > CURRENT_VERSION {
+        return Err(format!(
+            "config version {} is newer than this build of login-rs supports ({}); please upgrade login-rs",
+            from, CURRENT_VERSION
+        ));
+    }
+
+    let obj = value
+        .as_object_mut()
+        .ok_or("config must be a table/object at the top level")?;
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(from as usize - 1) {
+        migration(obj).map_err(|e| format!("migrating from version {}: {}", i + 1, e))?;
+    }
+
+    if from != CURRENT_VERSION {
+        // Rebuild the object so `version` is the first key when written back
+        let mut upgraded = Map::new();
+        upgraded.insert("version".to_string(), Value::from(CURRENT_VERSION));
+        for (k, v) in std::mem::take(obj) {
+            if k != "version" {
+                upgraded.insert(k, v);
+            }
+        }
+        *obj = upgraded;
+    }
+
+    Ok(from)
+}
+
+/// Upgrade a config file on disk, keeping its format. The original is copied to
+/// `<file>.v<N>.bak` first, or `<file>.v<N>.<k>.bak` if an earlier backup already has
+/// that name. Returns the original version and backup path, or `None` if the file was
+/// already current. The file is rewritten from the parsed values, so TOML and YAML
+/// comments are not kept.
+pub fn migrate_file(
+    path: &Path,
+    format: ConfigFormat,
+) -> Result<Option<(u32, PathBuf)>, Box<dyn std::error::Error>> {
+    let contents = fs::read_to_string(path)?;
+    let mut value = format.parse_value(&contents)?;
+
+    let from = migrate(&mut value)?;
+    if from == CURRENT_VERSION {
+        return Ok(None);
+    }
+
+    let backup = free_backup_path(path, from);
+    fs::copy(path, &backup)?;
+
+    fs::write(path, format.serialize(&value)?)?;
+    Ok(Some((from, backup)))
+}
+
+/// The first of `<file>.v<N>.bak`, `<file>.v<N>.2.bak`, ... that doesn't exist yet
+fn free_backup_path(path: &Path, version: u32) -> PathBuf {
+    let named = |suffix: String| {
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(suffix);
+        PathBuf::from(backup)
+    };
+    let first = named(format!(".v{}.bak", version));
+    if !first.exists() {
+        return first;
+    }
+    (2..)
+        .map(|k| named(format!(".v{}.{}.bak", version, k)))
+        .find(|p| !p.exists())
+        .expect("ran out of backup names")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// `config.json` as shipped with the first release
+    fn v1_fixture() -> Value {
+        json!({
+            "windower_path": "C:\\Windower4\\Windower.exe",
+            "playonline_dir": "C:\\PlayOnline\\usr\\all",
+            "stagger_delay_seconds": 10,
+            "launch_delay_seconds": 2,
+            "region": "us",
+            "characters": [
+                { "name": "MyWarrior", "slot": 1, "password": "Passw0rd!" }
+            ]
+        })
+    }
+
+    fn v2_fixture() -> Value {
+        json!({
+            "version": 2,
+            "region": "jp",
+            "input": { "strategy": "unicode" },
+            "characters": [
+                { "name": "MyWarrior", "slot": 1, "password_env": "MY_WARRIOR" }
+            ]
+        })
+    }
+
+    #[test]
+    fn v1_gains_a_version_first() {
+        let mut value = v1_fixture();
+        assert_eq!(migrate(&mut value), Ok(1));
+        let keys: Vec<&String> = value.as_object().unwrap().keys().collect();
+        assert_eq!(keys[0], "version");
+        assert_eq!(value["version"], 2);
+        let mut expected = v1_fixture();
+        expected["version"] = json!(2);
+        assert_eq!(value, expected);
+    }
+
+    #[test]
+    fn v1_drops_the_keys_it_ignored() {
+        let mut value = v1_fixture();
+        value["stagger_delay"] = json!(5);
+        value["characters"][0]["job"] = json!("WAR");
+        value["characters"][0]["nickname"] = json!("war");
+        assert_eq!(migrate(&mut value), Ok(1));
+        assert!(value.get("stagger_delay").is_none());
+        assert!(value["characters"][0].get("nickname").is_none());
+        // Fields added since are kept
+        assert_eq!(value["characters"][0]["job"], "WAR");
+        assert_eq!(value["stagger_delay_seconds"], 10);
+    }
+
+    #[test]
+    fn current_version_is_untouched() {
+        let mut value = v2_fixture();
+        assert_eq!(migrate(&mut value), Ok(2));
+        assert_eq!(value, v2_fixture());
+
+        // Unknown keys are left for the caller to reject
+        let mut value = v2_fixture();
+        value["regoin"] = json!("us");
+        assert_eq!(migrate(&mut value), Ok(2));
+        assert_eq!(value["regoin"], "us");
+    }
+
+    #[test]
+    fn bad_versions() {
+        let mut newer = json!({ "version": 3, "characters": [] });
+        assert!(migrate(&mut newer).unwrap_err().contains("newer than this build"));
+        for version in [json!(0), json!(-1), json!("2"), json!(1.5)] {
+            let mut value = json!({ "version": version, "characters": [] });
+            assert!(migrate(&mut value).unwrap_err().starts_with("'version' must be a positive integer"));
+        }
+        assert!(migrate(&mut json!([1, 2])).is_err());
+    }
+
+    #[test]
+    fn fixtures_load_as_the_same_config() {
+        use crate::config::Config;
+        let v1 = Config::parse(&v1_fixture().to_string(), ConfigFormat::Json).unwrap();
+        assert_eq!(v1.version, CURRENT_VERSION);
+        assert_eq!(v1.characters[0].name, "MyWarrior");
+        let v2 = Config::parse(&v2_fixture().to_string(), ConfigFormat::Json).unwrap();
+        assert_eq!(v2.characters[0].password_env.as_deref(), Some("MY_WARRIOR"));
+    }
+
+    #[test]
+    fn migrate_file_keeps_a_backup() {
+        let dir = std::env::temp_dir().join(format!("login-rs-migrate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json");
+        let original = serde_json::to_string_pretty(&v1_fixture()).unwrap();
+        fs::write(&path, &original).unwrap();
+
+        let (from, backup) = migrate_file(&path, ConfigFormat::Json).unwrap().unwrap();
+        assert_eq!(from, 1);
+        assert_eq!(backup, dir.join("config.json.v1.bak"));
+        assert_eq!(fs::read_to_string(&backup).unwrap(), original);
+        let upgraded: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(upgraded["version"], 2);
+
+        // Nothing to do the second time
+        assert!(migrate_file(&path, ConfigFormat::Json).unwrap().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn migrate_file_keeps_earlier_backups() {
+        let dir = std::env::temp_dir().join(format!("login-rs-migrate-again-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json");
+        let original = serde_json::to_string_pretty(&v1_fixture()).unwrap();
+        fs::write(dir.join("config.json.v1.bak"), "earlier").unwrap();
+        fs::write(dir.join("config.json.v1.2.bak"), "earlier").unwrap();
+        fs::write(&path, &original).unwrap();
+
+        let (_, backup) = migrate_file(&path, ConfigFormat::Json).unwrap().unwrap();
+        assert_eq!(backup, dir.join("config.json.v1.3.bak"));
+        assert_eq!(fs::read_to_string(&backup).unwrap(), original);
+        assert_eq!(fs::read_to_string(dir.join("config.json.v1.bak")).unwrap(), "earlier");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
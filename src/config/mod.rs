@@ -0,0 +1,1233 @@
+pub mod expand;
+pub mod format;
+pub mod layers;
+pub mod migrate;
+
+pub use format::ConfigFormat;
+
+use layers::Layer;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_WINDOWER_PATH: &str = r"C:\Windower4\Windower.exe";
+const DEFAULT_PLAYONLINE_DIR: &str =
+    r"C:\Program Files (x86)\PlayOnline\SquareEnix\PlayOnlineViewer\usr\all";
+
+/// Top-level keys understood in config.json
+pub const CONFIG_FIELDS: &[&str] = &[
+    "version",
+    "include",
+    "windower_path",
+    "playonline_dir",
+    "windower_profile",
+    "stagger_delay_seconds",
+    "launch_delay_seconds",
+    "game_window_timeout_seconds",
+    "login_retries",
+    "region",
+    "api",
+    "webhooks",
+    "groups",
+    "schedule",
+    "watchdog",
+    "input",
+    "layout",
+    "titles",
+    "lobby",
+    "characters",
+];
+
+/// Keys understood in each `characters` entry
+pub const CHARACTER_FIELDS: &[&str] = &[
+    "name",
+    "slot",
+    "password",
+    "password_env",
+    "job",
+    "ffxi_character_index",
+];
+
+/// Keys understood in the `api` section
+pub const API_FIELDS: &[&str] = &["port", "token"];
+
+/// Keys understood in each `webhooks` entry
+pub const WEBHOOK_FIELDS: &[&str] = &["url", "method", "events", "headers", "body", "timeout_seconds", "retries"];
+
+/// Keys understood in each `schedule` entry
+pub const SCHEDULE_FIELDS: &[&str] = &["cron", "characters", "timezone", "overlap"];
+
+/// Keys understood in the `watchdog` section
+pub const WATCHDOG_FIELDS: &[&str] = &["max_restarts_per_hour", "check_interval_seconds"];
+
+/// Keys understood in the `input` section
+pub const INPUT_FIELDS: &[&str] = &["strategy", "layout", "max_block_seconds", "backend", "parallel_logins"];
+
+/// Keys understood in the `layout` section
+pub const LAYOUT_FIELDS: &[&str] = &[
+    "mode",
+    "monitor",
+    "columns",
+    "gap",
+    "cascade_offset",
+    "width",
+    "height",
+    "windows",
+];
+
+/// Keys understood in each `layout.windows` entry
+pub const WINDOW_RECT_FIELDS: &[&str] = &["x", "y", "width", "height", "monitor"];
+
+/// Keys understood in the `titles` section
+pub const TITLE_FIELDS: &[&str] = &["playonline", "game"];
+
+/// Keys understood in the `lobby` section
+pub const LOBBY_FIELDS: &[&str] = &[
+    "skip_opening",
+    "title_screen_delay_seconds",
+    "step_timeout_seconds",
+];
+
+/// Length limits of a PlayOnline member password
+pub const PASSWORD_LENGTH: std::ops::RangeInclusive<usize> = 8..=16;
+
+/// Accepted values for `region`
+pub const REGIONS: &[&str] = &["us", "jp", "eu"];
+
+fn default_windower_path() -> PathBuf {
+    PathBuf::from(DEFAULT_WINDOWER_PATH)
+}
+
+fn default_playonline_dir() -> PathBuf {
+    PathBuf::from(DEFAULT_PLAYONLINE_DIR)
+}
+
+fn default_stagger_delay() -> u64 {
+    10
+}
+
+fn default_launch_delay() -> u64 {
+    2
+}
+
+fn default_game_window_timeout() -> u64 {
+    120
+}
+
+fn default_login_retries() -> u32 {
+    2
+}
+
+fn default_region() -> Region {
+    Region::Us
+}
+
+fn default_api_port() -> u16 {
+    51320
+}
+
+fn default_webhook_method() -> String {
+    "POST".to_string()
+}
+
+fn default_webhook_timeout() -> u64 {
+    10
+}
+
+fn default_webhook_retries() -> u32 {
+    2
+}
+
+fn default_max_restarts() -> u32 {
+    3
+}
+
+fn default_watchdog_interval() -> u64 {
+    15
+}
+
+fn default_max_block() -> u64 {
+    60
+}
+
+fn default_cascade_offset() -> u32 {
+    32
+}
+
+fn default_pol_title() -> String {
+    "PlayOnline Viewer - {name}".to_string()
+}
+
+fn default_game_title() -> String {
+    "FINAL FANTASY XI - {name}".to_string()
+}
+
+fn default_skip_opening() -> bool {
+    true
+}
+
+fn default_title_screen_delay() -> u64 {
+    20
+}
+
+fn default_lobby_step_timeout() -> u64 {
+    30
+}
+
+fn default_version() -> u32 {
+    migrate::CURRENT_VERSION
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default = "default_version")]
+    pub version: u32,
+    /// Other config files merged underneath this one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<PathBuf>,
+    #[serde(default = "default_windower_path")]
+    pub windower_path: PathBuf,
+    #[serde(default = "default_playonline_dir")]
+    pub playonline_dir: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub windower_profile: Option<String>,
+    #[serde(default = "default_stagger_delay")]
+    pub stagger_delay_seconds: u64,
+    #[serde(default = "default_launch_delay")]
+    pub launch_delay_seconds: u64,
+    /// A character whose game window hasn't appeared this long after its login counts as failed
+    #[serde(default = "default_game_window_timeout")]
+    pub game_window_timeout_seconds: u64,
+    /// Times a failed login is retried without asking, in modes that run unattended
+    #[serde(default = "default_login_retries")]
+    pub login_retries: u32,
+    #[serde(default = "default_region")]
+    pub region: Region,
+    /// Local HTTP control API, used by `login-rs serve`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api: Option<ApiConfig>,
+    /// HTTP callbacks fired on run and character events
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<WebhookConfig>,
+    /// Named sets of characters, usable anywhere character names are accepted
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: BTreeMap<String, Vec<String>>,
+    /// Timed logins, used by `login-rs scheduler`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<ScheduleConfig>,
+    /// Relaunch characters whose game closes, in `login` and `daemon`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watchdog: Option<WatchdogConfig>,
+    /// How keystrokes are sent to PlayOnline
+    #[serde(default, skip_serializing_if = "InputConfig::is_default")]
+    pub input: InputConfig,
+    /// Where game windows are placed once they appear
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<LayoutConfig>,
+    /// What PlayOnline and game windows are renamed to
+    #[serde(default, skip_serializing_if = "TitleConfig::is_default")]
+    pub titles: TitleConfig,
+    /// How FFXI characters are picked for characters with `ffxi_character_index`
+    #[serde(default, skip_serializing_if = "LobbyConfig::is_default")]
+    pub lobby: LobbyConfig,
+    pub characters: Vec<Character>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiConfig {
+    #[serde(default = "default_api_port")]
+    pub port: u16,
+    /// Clients must send `Authorization: Bearer <token>`
+    pub token: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: String,
+    #[serde(default = "default_webhook_method")]
+    pub method: String,
+    /// Events to fire on (see `webhooks::WEBHOOK_EVENTS`). Empty means all.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// JSON body with `{{placeholder}}`s. Defaults to the event itself.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+    #[serde(default = "default_webhook_timeout")]
+    pub timeout_seconds: u64,
+    #[serde(default = "default_webhook_retries")]
+    pub retries: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchdogConfig {
+    /// Stop relaunching a character after this many restarts within an hour
+    #[serde(default = "default_max_restarts")]
+    pub max_restarts_per_hour: u32,
+    #[serde(default = "default_watchdog_interval")]
+    pub check_interval_seconds: u64,
+}
+
+/// Sizes and positions are in unscaled pixels (as at 100% display scaling) and are
+/// scaled by each monitor's DPI
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutConfig {
+    #[serde(default)]
+    pub mode: LayoutMode,
+    /// Monitor to arrange windows on: 0 is the primary, then the rest left to right
+    #[serde(default)]
+    pub monitor: usize,
+    /// Grid columns. Defaults to as square a grid as fits every character.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub columns: Option<u32>,
+    /// Space between grid cells
+    #[serde(default)]
+    pub gap: u32,
+    /// How far each cascaded window is shifted from the last
+    #[serde(default = "default_cascade_offset")]
+    pub cascade_offset: u32,
+    /// Size of cascaded windows. Defaults to as large as lets every window fit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// Fixed rectangles for particular characters, who are left out of the grid or cascade
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub windows: BTreeMap<String, WindowRect>,
+}
+
+/// Window title templates (see `titles::render`). An empty template leaves the title alone.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TitleConfig {
+    #[serde(default = "default_pol_title")]
+    pub playonline: String,
+    #[serde(default = "default_game_title")]
+    pub game: String,
+}
+
+impl Default for TitleConfig {
+    fn default() -> Self {
+        Self {
+            playonline: default_pol_title(),
+            game: default_game_title(),
+        }
+    }
+}
+
+impl TitleConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LobbyConfig {
+    /// Skip the opening movie rather than wait for it to end
+    #[serde(default = "default_skip_opening")]
+    pub skip_opening: bool,
+    /// Without `skip_opening`, time from the game window appearing to the title
+    /// screen taking keys
+    #[serde(default = "default_title_screen_delay")]
+    pub title_screen_delay_seconds: u64,
+    /// How long a step may wait for the game window to take keys
+    #[serde(default = "default_lobby_step_timeout")]
+    pub step_timeout_seconds: u64,
+}
+
+impl Default for LobbyConfig {
+    fn default() -> Self {
+        Self {
+            skip_opening: default_skip_opening(),
+            title_screen_delay_seconds: default_title_screen_delay(),
+            step_timeout_seconds: default_lobby_step_timeout(),
+        }
+    }
+}
+
+impl LobbyConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// How `layout` arranges the windows of characters without a `windows` entry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutMode {
+    /// Side by side in equal cells filling the monitor
+    #[default]
+    Grid,
+    /// Overlapping, each shifted down and right from the last
+    Cascade,
+    /// Left where they are; only `windows` entries are moved
+    Manual,
+}
+
+/// One character's window, relative to the top left of its monitor's work area
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WindowRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// Defaults to `layout.monitor`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleConfig {
+    /// Five-field cron expression, e.g. `"30 18 * * fri"`
+    pub cron: String,
+    /// Characters or groups to log in. Empty means all.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub characters: Vec<String>,
+    #[serde(default)]
+    pub timezone: ScheduleTimezone,
+    #[serde(default)]
+    pub overlap: Overlap,
+}
+
+/// Clock a schedule's cron expression is read against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleTimezone {
+    /// The machine's time zone, following its DST changes
+    #[default]
+    Local,
+    Utc,
+}
+
+/// What to do when a schedule comes due while another run is still going
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Overlap {
+    /// Drop this firing
+    #[default]
+    Skip,
+    /// Run as soon as the current run finishes
+    Queue,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct InputConfig {
+    #[serde(default)]
+    pub strategy: TypingStrategy,
+    #[serde(default)]
+    pub layout: KeyboardLayout,
+    /// Unblock the user's keyboard and mouse after this long, even mid-login
+    #[serde(default = "default_max_block")]
+    pub max_block_seconds: u64,
+    #[serde(default)]
+    pub backend: InputBackendKind,
+    /// Logins run at the same time with the `post_message` backend
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parallel_logins: Option<usize>,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            strategy: TypingStrategy::default(),
+            layout: KeyboardLayout::default(),
+            max_block_seconds: default_max_block(),
+            backend: InputBackendKind::default(),
+            parallel_logins: None,
+        }
+    }
+}
+
+impl InputConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// How text is typed. Window messages carry characters, not key states,
+    /// so the `post_message` backend always sends text as characters.
+    pub fn typing_strategy(&self) -> TypingStrategy {
+        match self.backend {
+            InputBackendKind::SendInput => self.strategy,
+            InputBackendKind::PostMessage => TypingStrategy::Unicode,
+        }
+    }
+
+    /// How many logins may run at once
+    pub fn parallel_logins(&self) -> usize {
+        match self.backend {
+            InputBackendKind::SendInput => 1,
+            InputBackendKind::PostMessage => self.parallel_logins.unwrap_or(DEFAULT_PARALLEL_LOGINS),
+        }
+    }
+}
+
+/// Logins run at once with the `post_message` backend when `parallel_logins` is not set
+const DEFAULT_PARALLEL_LOGINS: usize = 4;
+
+/// How keys reach PlayOnline
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputBackendKind {
+    /// Simulated keyboard and mouse input to the foreground window. Needs focus
+    /// and blocks the user's input, so logins run one at a time.
+    #[default]
+    SendInput,
+    /// Window messages posted to each PlayOnline window, which works in the
+    /// background and lets several logins run at once
+    PostMessage,
+}
+
+/// How text such as passwords is typed (see `typing::translate`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TypingStrategy {
+    /// Virtual-key presses looked up in the keyboard layout
+    #[default]
+    VirtualKey,
+    /// Hardware scan codes looked up in the keyboard layout
+    ScanCode,
+    /// Each character sent as-is, whatever the layout
+    Unicode,
+    /// Put the text on the clipboard and press Ctrl+V
+    Clipboard,
+}
+
+/// Keyboard layout the `virtual_key` and `scan_code` strategies type with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyboardLayout {
+    /// The layout active on this machine
+    #[default]
+    Auto,
+    Us,
+    Uk,
+    De,
+    Fr,
+    Jp,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Character {
+    pub name: String,
+    pub slot: u8,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub password: String,
+    /// Read the password from this environment variable instead of storing it in the config
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_env: Option<String>,
+    /// Shown in window titles with `{job}`, e.g. `"WAR/NIN"`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job: Option<String>,
+    /// Position (1-16) of the FFXI character to select in the lobby, in the
+    /// account's character list. Unset leaves the game at the title screen.
+    /// The list can't be read, so there is no choosing by name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ffxi_character_index: Option<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Region {
+    Us,
+    Jp,
+    Eu,
+}
+
+impl Region {
+    pub fn proxy_port(&self) -> u16 {
+        match self {
+            Region::Us => 51304,
+            Region::Jp => 51300,
+            Region::Eu => 51302,
+        }
+    }
+
+    pub fn hosts_entry(&self) -> &'static str {
+        // All regions use the same hostname pattern
+        "127.0.0.1 wh000.pol.com"
+    }
+}
+
+impl Config {
+    /// Load `path` with its includes, local override file and `--set` overrides merged in
+    pub fn load(
+        path: &Path,
+        format: ConfigFormat,
+        overrides: &[String],
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let layers = layers::read_layers(path, format, overrides)?;
+        Self::build(&layers, config_dir(path))
+    }
+
+    /// Merge layers read with `read_layers`, validate the result and fill in paths and passwords
+    pub fn build(layers: &[Layer], config_dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut config = Self::from_layers(layers)?;
+        config.validate()?;
+        let base_dir = |field: &str| layers::base_dir(layers, field, config_dir);
+        config.expand_paths(&base_dir, &expand::process_env)?;
+        config.resolve_passwords()?;
+        config.validate_passwords()?;
+        Ok(config)
+    }
+
+    /// Expand variables and `~` in path fields and resolve relative paths against
+    /// `base_dir(field)`, the directory of the file that set the field
+    pub fn expand_paths(
+        &mut self,
+        base_dir: &dyn Fn(&str) -> PathBuf,
+        env: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<(), String> {
+        for (field, path) in [
+            ("windower_path", &mut self.windower_path),
+            ("playonline_dir", &mut self.playonline_dir),
+        ] {
+            *path = expand::expand_path(path, &base_dir(field), env)
+                .map_err(|e| format!("{}: {}", field, e))?;
+        }
+        Ok(())
+    }
+
+    /// Fill in `password` for characters that use `password_env`
+    fn resolve_passwords(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        for ch in &mut self.characters {
+            if let Some(var) = &ch.password_env {
+                ch.password = std::env::var(var).map_err(|_| {
+                    format!(
+                        "Character '{}' reads its password from environment variable {}, which is not set",
+                        ch.name, var
+                    )
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Every password must be one PlayOnline accepts and `input` can type.
+    /// Run after `resolve_passwords`.
+    pub fn validate_passwords(&self) -> Result<(), String> {
+        for ch in &self.characters {
+            check_password(&ch.password, &self.input)
+                .map_err(|e| format!("Character '{}': {}", ch.name, e))?;
+        }
+        Ok(())
+    }
+
+    /// Parse config file contents, upgrading older layouts in memory.
+    /// Does not run `validate`.
+    pub fn parse(contents: &str, format: ConfigFormat) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut value = format.parse_value(contents)?;
+        let from = migrate::migrate(&mut value)?;
+
+        if let Some(unknown) = find_unknown_fields(&value).into_iter().next() {
+            let line = find_key_line(contents, &unknown.field)
+                .map(|l| format!(" at line {}", l))
+                .unwrap_or_default();
+            return Err(format!("{}{}", unknown, line).into());
+        }
+
+        if from == migrate::CURRENT_VERSION {
+            // Deserialize from the original text so errors carry line/column
+            Ok(format.deserialize(contents)?)
+        } else {
+            log::info!(
+                "Config is version {}, upgraded to {} in memory (run `login-rs config migrate` to update the file)",
+                from,
+                migrate::CURRENT_VERSION
+            );
+            Ok(serde_json::from_value(value)?)
+        }
+    }
+
+    /// Merge config layers into a `Config`. Does not run `validate`.
+    pub fn from_layers(layers: &[Layer]) -> Result<Self, Box<dyn std::error::Error>> {
+        if let [Layer {
+            contents: Some(contents),
+            format,
+            ..
+        }] = layers
+        {
+            // Nothing to merge; parse the text directly so errors carry line/column
+            return Self::parse(contents, *format);
+        }
+        layers::check_unknown_fields(layers)?;
+        let merged = layers::merge(layers)?;
+        Ok(serde_json::from_value(merged.value)?)
+    }
+
+    pub fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.version != migrate::CURRENT_VERSION {
+            return Err(format!(
+                "Config version {} was not migrated to {}",
+                self.version,
+                migrate::CURRENT_VERSION
+            )
+            .into());
+        }
+        if self.characters.is_empty() {
+            return Err("No characters defined in config".into());
+        }
+        for ch in &self.characters {
+            if ch.password.is_empty() == ch.password_env.is_none() {
+                return Err(format!(
+                    "Character '{}' must set exactly one of password or password_env",
+                    ch.name
+                )
+                .into());
+            }
+            if ch.slot < 1 || ch.slot > 20 {
+                return Err(format!(
+                    "Character '{}' has invalid slot {} (must be 1-20)",
+                    ch.name, ch.slot
+                )
+                .into());
+            }
+        }
+        if self.stagger_delay_seconds == 0 {
+            return Err("stagger_delay_seconds must be > 0".into());
+        }
+        if self.game_window_timeout_seconds == 0 {
+            return Err("game_window_timeout_seconds must be > 0".into());
+        }
+        for (i, hook) in self.webhooks.iter().enumerate() {
+            crate::webhooks::validate_hook(hook).map_err(|e| format!("webhooks[{}]: {}", i, e))?;
+        }
+        if self.input.max_block_seconds == 0 {
+            return Err("input.max_block_seconds must be > 0".into());
+        }
+        self.validate_input_backend()?;
+        if self.watchdog.as_ref().is_some_and(|w| w.check_interval_seconds == 0) {
+            return Err("watchdog.check_interval_seconds must be > 0".into());
+        }
+        self.validate_layout()?;
+        self.validate_titles()?;
+        self.validate_lobby()?;
+        self.validate_groups()?;
+        for (i, entry) in self.schedule.iter().enumerate() {
+            let cron = crate::schedule::Cron::parse(&entry.cron)
+                .map_err(|e| format!("schedule[{}]: cron '{}': {}", i, entry.cron, e))?;
+            if cron.next_after(&chrono::Utc::now()).is_none() {
+                return Err(format!("schedule[{}]: cron '{}' never fires", i, entry.cron).into());
+            }
+            self.check_names(&entry.characters)
+                .map_err(|e| format!("schedule[{}]: {}", i, e))?;
+        }
+        if let Some(api) = &self.api {
+            if api.token.trim().is_empty() {
+                return Err("api.token must not be empty".into());
+            }
+            if api.port == self.region.proxy_port() {
+                return Err(format!("api.port {} is the PlayOnline proxy port", api.port).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// `parallel_logins` only works with window messages
+    pub fn validate_input_backend(&self) -> Result<(), String> {
+        match (self.input.backend, self.input.parallel_logins) {
+            (_, Some(0)) => Err("input.parallel_logins must be > 0".into()),
+            (InputBackendKind::SendInput, Some(n)) if n > 1 => Err(
+                "input.parallel_logins needs input.backend \"post_message\"; send_input logs in one character at a time"
+                    .into(),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Layout sizes must be positive, and `windows` entries must name characters
+    pub fn validate_layout(&self) -> Result<(), String> {
+        let Some(layout) = &self.layout else {
+            return Ok(());
+        };
+        for (field, value) in [
+            ("columns", layout.columns),
+            ("width", layout.width),
+            ("height", layout.height),
+        ] {
+            if value == Some(0) {
+                return Err(format!("layout.{} must be > 0", field));
+            }
+        }
+        for (name, rect) in &layout.windows {
+            if self.find_character(name).is_none() {
+                return Err(format!("layout.windows: unknown character '{}'", name));
+            }
+            if rect.width == 0 || rect.height == 0 {
+                return Err(format!("layout.windows.{}: width and height must be > 0", name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Title templates must only use known placeholders, filled in for every character
+    pub fn validate_titles(&self) -> Result<(), String> {
+        for (field, template) in [
+            ("playonline", &self.titles.playonline),
+            ("game", &self.titles.game),
+        ] {
+            crate::titles::check_template(template, &self.characters)
+                .map_err(|e| format!("titles.{}: {}", field, e))?;
+        }
+        Ok(())
+    }
+
+    /// FFXI character indexes must be positions in a character list
+    pub fn validate_lobby(&self) -> Result<(), String> {
+        for ch in &self.characters {
+            if let Some(index) = ch.ffxi_character_index {
+                if !crate::lobby::FFXI_CHARACTERS.contains(&index) {
+                    return Err(format!(
+                        "Character '{}' has invalid ffxi_character_index {} (must be 1-16)",
+                        ch.name, index
+                    ));
+                }
+            }
+        }
+        if self.lobby.step_timeout_seconds == 0 {
+            return Err("lobby.step_timeout_seconds must be > 0".into());
+        }
+        Ok(())
+    }
+
+    /// Group members must be characters, and group names must not shadow one
+    pub fn validate_groups(&self) -> Result<(), String> {
+        for (group, members) in &self.groups {
+            if self.find_character(group).is_some() {
+                return Err(format!("groups: '{}' is also a character name", group));
+            }
+            if let Some(unknown) = members.iter().find(|m| self.find_character(m).is_none()) {
+                return Err(format!("groups.{}: unknown character '{}'", group, unknown));
+            }
+        }
+        Ok(())
+    }
+
+    /// Every name must be a character or a group
+    pub fn check_names(&self, names: &[String]) -> Result<(), String> {
+        let unknown: Vec<&str> = names
+            .iter()
+            .filter(|n| self.find_character(n).is_none() && self.find_group(n).is_none())
+            .map(String::as_str)
+            .collect();
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("unknown character or group: {}", unknown.join(", ")))
+        }
+    }
+
+    fn find_character(&self, name: &str) -> Option<&Character> {
+        self.characters.iter().find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    fn find_group(&self, name: &str) -> Option<&Vec<String>> {
+        self.groups
+            .iter()
+            .find(|(group, _)| group.eq_ignore_ascii_case(name))
+            .map(|(_, members)| members)
+    }
+
+    /// Passwords and tokens that must never appear in logs or outgoing payloads
+    pub fn secrets(&self) -> Vec<String> {
+        self.characters
+            .iter()
+            .map(|ch| ch.password.clone())
+            .chain(self.api.iter().map(|api| api.token.clone()))
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+
+    /// Characters named in `names`, in config order. Group names select their members.
+    /// An empty list selects everyone.
+    pub fn filter_characters(&self, names: &[String]) -> Vec<&Character> {
+        if names.is_empty() {
+            return self.characters.iter().collect();
+        }
+        let selected = |c: &Character| {
+            names.iter().any(|n| {
+                n.eq_ignore_ascii_case(&c.name)
+                    || self
+                        .find_group(n)
+                        .is_some_and(|members| members.iter().any(|m| m.eq_ignore_ascii_case(&c.name)))
+            })
+        };
+        self.characters.iter().filter(|c| selected(c)).collect()
+    }
+}
+
+/// Shown in place of passwords by `config show`
+const REDACTED: &str = "********";
+
+/// Origin shown for values nobody set
+pub const DEFAULT_ORIGIN: &str = "default";
+
+/// The effective config as `config show` prints it: merged, with defaults filled in and
+/// secrets hidden, plus which layer each value came from.
+pub fn effective(layers: &[Layer]) -> Result<layers::Merged, Box<dyn std::error::Error>> {
+    layers::check_unknown_fields(layers)?;
+    let merged = layers::merge(layers)?;
+    let config: Config = serde_json::from_value(merged.value)?;
+
+    let mut value = serde_json::to_value(&config)?;
+    if let Some(token) = value.pointer_mut("/api/token") {
+        *token = Value::from(REDACTED);
+    }
+    if let Some(characters) = value.get_mut("characters").and_then(Value::as_array_mut) {
+        for ch in characters {
+            if let Some(password) = ch.get_mut("password") {
+                *password = Value::from(REDACTED);
+            }
+        }
+    }
+    Ok(layers::Merged {
+        value,
+        origins: merged.origins,
+    })
+}
+
+/// Check a password against PlayOnline's rules and whether `input` can type it.
+/// Errors never include the password.
+pub fn check_password(password: &str, input: &InputConfig) -> Result<(), String> {
+    if !PASSWORD_LENGTH.contains(&password.chars().count()) {
+        return Err(format!(
+            "password must be {} to {} characters long",
+            PASSWORD_LENGTH.start(),
+            PASSWORD_LENGTH.end()
+        ));
+    }
+    // Letters, digits and symbols; no spaces or characters outside ASCII
+    let rejected: Vec<String> = password
+        .chars()
+        .enumerate()
+        .filter(|(_, c)| !c.is_ascii_graphic())
+        .map(|(i, _)| (i + 1).to_string())
+        .collect();
+    if !rejected.is_empty() {
+        let noun = if rejected.len() == 1 { "position" } else { "positions" };
+        return Err(format!(
+            "password has characters PlayOnline does not accept ({} {}); use letters, digits and symbols",
+            noun,
+            rejected.join(", ")
+        ));
+    }
+    crate::typing::check_typeable(password, input).map_err(|e| format!("password {}", e))
+}
+
+/// Directory relative config paths are resolved against
+pub fn config_dir(config_path: &Path) -> &Path {
+    // A bare file name has an empty parent, which joins as the current directory
+    config_path.parent().unwrap_or(Path::new(""))
+}
+
+/// Convert config text between formats. The config is validated and upgraded to the
+/// current version on the way through, so the result always loads cleanly.
+pub fn convert(contents: &str, from: ConfigFormat, to: ConfigFormat) -> Result<String, String> {
+    Config::parse(contents, from)
+        .and_then(|config| config.validate().map(|_| config))
+        .map_err(|e| e.to_string())?;
+
+    let mut value = from.parse_value(contents)?;
+    migrate::migrate(&mut value)?;
+    to.serialize(&value)
+}
+
+/// A key in the config that login-rs does not recognize
+pub struct UnknownField {
+    /// Where the key was found, e.g. `characters[1]`, or empty for the top level
+    pub location: String,
+    /// JSON pointer to the object holding the key, e.g. `/characters/1`
+    pub pointer: String,
+    pub field: String,
+    pub suggestion: Option<&'static str>,
+}
+
+impl std::fmt::Display for UnknownField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.location.is_empty() {
+            write!(f, "{}: ", self.location)?;
+        }
+        write!(f, "unknown field '{}'", self.field)?;
+        if let Some(s) = self.suggestion {
+            write!(f, " (did you mean '{}'?)", s)?;
+        }
+        Ok(())
+    }
+}
+
+/// Find keys in a raw config that don't correspond to any `Config` or `Character` field
+pub fn find_unknown_fields(value: &Value) -> Vec<UnknownField> {
+    let mut unknown = Vec::new();
+
+    // `location` is for messages, `pointer` finds the object again
+    let mut check = |location: String, pointer: String, obj: &serde_json::Map<String, Value>, known: &'static [&'static str]| {
+        for key in obj.keys() {
+            if !known.contains(&key.as_str()) {
+                unknown.push(UnknownField {
+                    location: location.clone(),
+                    pointer: pointer.clone(),
+                    field: key.clone(),
+                    suggestion: suggest(key, known),
+                });
+            }
+        }
+    };
+    let section = |name: &str| (name.to_string(), format!("/{}", name));
+    let entry = |name: &str, i: usize| (format!("{}[{}]", name, i), format!("/{}/{}", name, i));
+
+    if let Some(obj) = value.as_object() {
+        check(String::new(), String::new(), obj, CONFIG_FIELDS);
+    }
+    for (name, known) in [
+        ("api", API_FIELDS),
+        ("watchdog", WATCHDOG_FIELDS),
+        ("input", INPUT_FIELDS),
+        ("layout", LAYOUT_FIELDS),
+        ("titles", TITLE_FIELDS),
+        ("lobby", LOBBY_FIELDS),
+    ] {
+        if let Some(obj) = value.get(name).and_then(Value::as_object) {
+            let (location, pointer) = section(name);
+            check(location, pointer, obj, known);
+        }
+    }
+    if let Some(windows) = value.pointer("/layout/windows").and_then(Value::as_object) {
+        for (name, rect) in windows {
+            if let Some(obj) = rect.as_object() {
+                let escaped = name.replace('~', "~0").replace('/', "~1");
+                check(
+                    format!("layout.windows.{}", name),
+                    format!("/layout/windows/{}", escaped),
+                    obj,
+                    WINDOW_RECT_FIELDS,
+                );
+            }
+        }
+    }
+    for (name, known) in [
+        ("webhooks", WEBHOOK_FIELDS),
+        ("schedule", SCHEDULE_FIELDS),
+        ("characters", CHARACTER_FIELDS),
+    ] {
+        if let Some(items) = value.get(name).and_then(Value::as_array) {
+            for (i, item) in items.iter().enumerate() {
+                if let Some(obj) = item.as_object() {
+                    let (location, pointer) = entry(name, i);
+                    check(location, pointer, obj, known);
+                }
+            }
+        }
+    }
+
+    unknown
+}
+
+/// 1-based line where `key` is first used as a key (`"key":` in JSON, `key =` in TOML, `key:` in YAML)
+fn find_key_line(contents: &str, key: &str) -> Option<usize> {
+    let is_key_at = |line: &str, i: usize| {
+        let before = line[..i].trim_end_matches('"');
+        let starts_token = before
+            .chars()
+            .last()
+            .is_none_or(|c| c.is_whitespace() || matches!(c, '{' | ',' | '-'));
+        let after = &line[i + key.len()..];
+        let after = after.strip_prefix('"').unwrap_or(after).trim_start();
+        starts_token && (after.starts_with(':') || after.starts_with('='))
+    };
+
+    contents
+        .lines()
+        .position(|line| line.match_indices(key).any(|(i, _)| is_key_at(line, i)))
+        .map(|i| i + 1)
+}
+
+/// Closest candidate within a small edit distance, for "did you mean" hints
+pub fn suggest<'a>(input: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let input = input.to_ascii_lowercase();
+    candidates
+        .iter()
+        .map(|c| (*c, edit_distance(&input, c)))
+        .filter(|(c, d)| *d <= (c.len() / 3).max(1))
+        .min_by_key(|(_, d)| *d)
+        .map(|(c, _)| c)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut cur = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            cur[j] = (prev[j] + 1).min(cur[j - 1] + 1).min(prev[j - 1] + cost);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::DeserializeOwned;
+    use serde_json::json;
+
+    /// Field names serde accepts for `T`, read from its unknown-field error
+    fn serde_fields<T: DeserializeOwned>() -> Vec<String> {
+        let err = serde_json::from_value::<T>(json!({ "__not_a_field__": 0 }))
+            .err()
+            .expect("deny_unknown_fields")
+            .to_string();
+        let expected = err
+            .split_once("expected ")
+            .map(|(_, rest)| rest)
+            .unwrap_or_else(|| panic!("not an unknown-field error: {}", err));
+        expected
+            .split('`')
+            .skip(1)
+            .step_by(2)
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn assert_fields<T: DeserializeOwned>(list: &[&str], name: &str) {
+        let mut listed: Vec<&str> = list.to_vec();
+        listed.sort_unstable();
+        let mut fields = serde_fields::<T>();
+        fields.sort_unstable();
+        assert_eq!(listed, fields, "{} is out of sync with its struct", name);
+    }
+
+    #[test]
+    fn field_lists_match_the_structs() {
+        assert_fields::<Config>(CONFIG_FIELDS, "CONFIG_FIELDS");
+        assert_fields::<Character>(CHARACTER_FIELDS, "CHARACTER_FIELDS");
+        assert_fields::<ApiConfig>(API_FIELDS, "API_FIELDS");
+        assert_fields::<WebhookConfig>(WEBHOOK_FIELDS, "WEBHOOK_FIELDS");
+        assert_fields::<ScheduleConfig>(SCHEDULE_FIELDS, "SCHEDULE_FIELDS");
+        assert_fields::<WatchdogConfig>(WATCHDOG_FIELDS, "WATCHDOG_FIELDS");
+        assert_fields::<InputConfig>(INPUT_FIELDS, "INPUT_FIELDS");
+        assert_fields::<LayoutConfig>(LAYOUT_FIELDS, "LAYOUT_FIELDS");
+        assert_fields::<WindowRect>(WINDOW_RECT_FIELDS, "WINDOW_RECT_FIELDS");
+        assert_fields::<TitleConfig>(TITLE_FIELDS, "TITLE_FIELDS");
+        assert_fields::<LobbyConfig>(LOBBY_FIELDS, "LOBBY_FIELDS");
+    }
+
+    #[test]
+    fn unknown_fields_are_located_and_suggested() {
+        let value = json!({
+            "version": 2,
+            "regoin": "us",
+            "layout": { "windows": { "a/b": { "x": 0, "widthh": 5 } } },
+            "characters": [{ "name": "A" }, { "name": "B", "slott": 1 }]
+        });
+        let found: Vec<(String, String, String, Option<&str>)> = find_unknown_fields(&value)
+            .into_iter()
+            .map(|u| (u.location, u.pointer, u.field, u.suggestion))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("".into(), "".into(), "regoin".into(), Some("region")),
+                ("layout.windows.a/b".into(), "/layout/windows/a~1b".into(), "widthh".into(), Some("width")),
+                ("characters[1]".into(), "/characters/1".into(), "slott".into(), Some("slot")),
+            ]
+        );
+        for (_, pointer, _, _) in &found {
+            assert!(value.pointer(pointer).is_some_and(Value::is_object), "{}", pointer);
+        }
+    }
+
+    #[test]
+    fn unknown_field_error_has_the_line() {
+        let contents = "{\n  \"version\": 2,\n  \"characters\": [],\n  \"stager_delay_seconds\": 3\n}";
+        let err = Config::parse(contents, ConfigFormat::Json).err().unwrap().to_string();
+        assert_eq!(
+            err,
+            "unknown field 'stager_delay_seconds' (did you mean 'stagger_delay_seconds'?) at line 4"
+        );
+    }
+
+    fn input(strategy: TypingStrategy, layout: KeyboardLayout) -> InputConfig {
+        InputConfig {
+            strategy,
+            layout,
+            ..InputConfig::default()
+        }
+    }
+
+    #[test]
+    fn passwords_must_be_8_to_16_characters() {
+        let us = input(TypingStrategy::VirtualKey, KeyboardLayout::Us);
+        let length = "password must be 8 to 16 characters long";
+        assert_eq!(check_password("", &us).unwrap_err(), length);
+        assert_eq!(check_password("Passw0r", &us).unwrap_err(), length);
+        assert_eq!(check_password("Passw0rd", &us), Ok(()));
+        assert_eq!(check_password("Passw0rd!Passw0r", &us), Ok(()));
+        assert_eq!(check_password("Passw0rd!Passw0rd", &us).unwrap_err(), length);
+        // Counted in characters, not bytes
+        let unicode = input(TypingStrategy::Unicode, KeyboardLayout::Us);
+        assert!(check_password("ééééééé", &unicode).unwrap_err().contains("8 to 16"));
+    }
+
+    #[test]
+    fn passwords_reject_spaces_and_non_ascii_by_position() {
+        let unicode = input(TypingStrategy::Unicode, KeyboardLayout::Us);
+        assert_eq!(
+            check_password("Pass wörd1", &unicode).unwrap_err(),
+            "password has characters PlayOnline does not accept (positions 5, 7); \
+             use letters, digits and symbols"
+        );
+        assert!(check_password("Passw0rd\t", &unicode)
+            .unwrap_err()
+            .contains("(position 9)"));
+    }
+
+    #[test]
+    fn passwords_must_be_typeable_with_the_input_settings() {
+        let de = input(TypingStrategy::ScanCode, KeyboardLayout::De);
+        assert_eq!(
+            check_password("Pa^ssw0rd", &de).unwrap_err(),
+            "password character 3 can't be typed with the scan_code strategy on the de keyboard layout"
+        );
+        let clipboard = input(TypingStrategy::Clipboard, KeyboardLayout::De);
+        assert_eq!(check_password("Pa^ssw0rd", &clipboard), Ok(()));
+    }
+
+    #[test]
+    fn password_errors_never_contain_the_password() {
+        let de = input(TypingStrategy::ScanCode, KeyboardLayout::De);
+        for password in ["Secr3t", "Secr3t Secr3t", "Secr3t^Secr3t", "Secr3t€Secr3t"] {
+            let error = check_password(password, &de).unwrap_err();
+            assert!(!error.contains("Secr3t"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn configs_with_a_short_password_no_longer_load() {
+        let json = json!({
+            "version": 2,
+            "characters": [{ "name": "MyWarrior", "slot": 1, "password": "short" }]
+        })
+        .to_string();
+        let config = Config::parse(&json, ConfigFormat::Json).unwrap();
+        let error = config.validate_passwords().unwrap_err();
+        assert_eq!(
+            error,
+            "Character 'MyWarrior': password must be 8 to 16 characters long"
+        );
+        assert!(!error.contains("short"));
+    }
+}
@@ -0,0 +1,467 @@
+use crate::clock::Clock;
+use crate::config::{Character, Config};
+use crate::events::{Event, Output};
+use crate::launcher::Launcher;
+use crate::processes::ProcessQuery;
+use crate::watchdog::Watchdog;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, SystemTime};
+
+/// How often the config files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the files must stay unchanged before reloading, so a save that
+/// writes in several steps isn't picked up half-done
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// Reports changes to the config files
+pub trait Watcher {
+    /// Whether any watched file changed since the last call
+    fn changed(&mut self) -> bool;
+
+    /// Replace the set of watched files, e.g. after `include` changed
+    fn watch(&mut self, files: Vec<PathBuf>);
+}
+
+/// Compares modification times on every call. Files that don't exist yet are
+/// watched too, so creating a local override file triggers a reload.
+pub struct PollingWatcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl PollingWatcher {
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        let mut watcher = Self { files: Vec::new() };
+        watcher.watch(files);
+        watcher
+    }
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl Watcher for PollingWatcher {
+    fn changed(&mut self) -> bool {
+        let mut changed = false;
+        for (path, last) in &mut self.files {
+            let now = modified(path);
+            if now != *last {
+                *last = now;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    fn watch(&mut self, files: Vec<PathBuf>) {
+        self.files = files
+            .into_iter()
+            .map(|path| {
+                let stamp = modified(&path);
+                (path, stamp)
+            })
+            .collect();
+    }
+}
+
+/// A freshly loaded config and the files it was read from
+pub struct Loaded {
+    pub config: Config,
+    pub files: Vec<PathBuf>,
+}
+
+/// Keeps characters logged in while the config changes underneath it
+pub struct Daemon<'a> {
+    config: Config,
+    /// Names of characters this daemon has launched, with their Windower pid
+    running: Vec<(String, Option<u32>)>,
+    launcher: &'a mut dyn Launcher,
+    processes: &'a dyn ProcessQuery,
+    out: &'a Output,
+    /// Relaunches characters whose game closes, if enabled
+    watchdog: Option<Watchdog>,
+}
+
+impl<'a> Daemon<'a> {
+    pub fn new(
+        config: Config,
+        launcher: &'a mut dyn Launcher,
+        processes: &'a dyn ProcessQuery,
+        out: &'a Output,
+    ) -> Self {
+        Self {
+            config,
+            running: Vec::new(),
+            launcher,
+            processes,
+            out,
+            watchdog: None,
+        }
+    }
+
+    /// Relaunch launched characters whose game closes, checking them from `run`
+    pub fn supervise(&mut self, watchdog: Watchdog) {
+        self.watchdog = Some(watchdog);
+    }
+
+    /// Log in every character in the config that isn't already running.
+    /// Characters whose Windower instance launched count as running even if login failed,
+    /// since their window is still open.
+    pub fn launch_pending(&mut self) {
+        let running = &self.running;
+        let pending: Vec<&Character> = self
+            .config
+            .characters
+            .iter()
+            .filter(|ch| !running.iter().any(|(r, _)| r.eq_ignore_ascii_case(&ch.name)))
+            .collect();
+        if pending.is_empty() {
+            return;
+        }
+
+        let report = self.launcher.login(&self.config, &pending, &AtomicBool::new(false));
+        for ch in report.characters.iter().filter(|ch| ch.launched) {
+            self.running.push((ch.name.clone(), ch.instance.map(|i| i.pid)));
+        }
+        if let Some(watchdog) = &mut self.watchdog {
+            watchdog.track(&report);
+        }
+    }
+
+    /// Swap in a newly loaded config and launch any characters it added. If the config
+    /// failed to load, the error is reported and the current config stays in effect.
+    /// Returns whether the new config was applied.
+    pub fn reload(&mut self, loaded: Result<Config, String>) -> bool {
+        let new = match loaded {
+            Ok(config) => config,
+            Err(error) => {
+                self.out.emit(Event::ConfigReloadFailed { error: &error });
+                return false;
+            }
+        };
+
+        fn find<'c>(config: &'c Config, name: &str) -> Option<&'c Character> {
+            config
+                .characters
+                .iter()
+                .find(|ch| ch.name.eq_ignore_ascii_case(name))
+        }
+        let old = &self.config;
+        let added: Vec<&str> = new
+            .characters
+            .iter()
+            .filter(|ch| find(old, &ch.name).is_none())
+            .map(|ch| ch.name.as_str())
+            .collect();
+        let removed: Vec<&str> = old
+            .characters
+            .iter()
+            .filter(|ch| find(&new, &ch.name).is_none())
+            .map(|ch| ch.name.as_str())
+            .collect();
+        let changed: Vec<&str> = new
+            .characters
+            .iter()
+            .filter(|ch| find(old, &ch.name).is_some_and(|o| o != *ch))
+            .map(|ch| ch.name.as_str())
+            .collect();
+        let settings_changed = Config {
+            characters: Vec::new(),
+            ..new.clone()
+        } != Config {
+            characters: Vec::new(),
+            ..old.clone()
+        };
+
+        self.out.emit(Event::ConfigReloaded {
+            added,
+            removed,
+            changed,
+            settings_changed,
+        });
+
+        let old = std::mem::replace(&mut self.config, new);
+        // Characters removed by an earlier reload stay tracked while their game is open,
+        // so adding one back launches it again only once that game has closed
+        if self.running.iter().any(|(name, _)| find(&old, name).is_none()) {
+            let table = self.processes.snapshot();
+            self.running.retain(|(name, pid)| {
+                find(&old, name).is_some() || pid.is_some_and(|pid| table.tree_running(pid))
+            });
+        }
+        self.launch_pending();
+        true
+    }
+
+    /// Check the watched files once. If they changed, wait for them to settle,
+    /// then reload. Returns whether a reload was attempted.
+    pub fn poll(
+        &mut self,
+        watcher: &mut dyn Watcher,
+        clock: &dyn Clock,
+        load: &mut dyn FnMut() -> Result<Loaded, String>,
+    ) -> bool {
+        if !watcher.changed() {
+            return false;
+        }
+
+        let mut last_change = clock.now();
+        while clock.now().duration_since(last_change) < SETTLE_TIME {
+            clock.sleep(POLL_INTERVAL);
+            if watcher.changed() {
+                last_change = clock.now();
+            }
+        }
+
+        let loaded = load().map(|loaded| {
+            watcher.watch(loaded.files);
+            loaded.config
+        });
+        self.reload(loaded);
+        true
+    }
+
+    /// Watch for config changes, and check on running characters if supervising,
+    /// until the process is stopped
+    pub fn run(
+        &mut self,
+        watcher: &mut dyn Watcher,
+        clock: &dyn Clock,
+        load: &mut dyn FnMut() -> Result<Loaded, String>,
+    ) -> ! {
+        loop {
+            clock.sleep(POLL_INTERVAL);
+            self.poll(watcher, clock, load);
+            if let Some(watchdog) = &mut self.watchdog {
+                watchdog.tick(&self.config, &mut *self.launcher, self.processes, clock, self.out);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::config::ConfigFormat;
+    use crate::events::OutputFormat;
+    use crate::launcher::MockLauncher;
+    use crate::processes::{ProcessEntry, ProcessTable};
+    use serde_json::Value;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+
+    fn config(names: &[&str]) -> Config {
+        let characters: Vec<Value> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                serde_json::json!({"name": name, "slot": i + 1, "password": "Passw0rd!"})
+            })
+            .collect();
+        let json = serde_json::json!({"version": 2, "characters": characters}).to_string();
+        Config::parse(&json, ConfigFormat::Json).unwrap()
+    }
+
+    /// An output that keeps every event as JSON
+    fn recording_output() -> (Output, Arc<Mutex<Vec<Value>>>) {
+        let out = Output::new(OutputFormat::Json);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        out.subscribe(move |e| sink.lock().unwrap().push(serde_json::to_value(e).unwrap()));
+        (out, events)
+    }
+
+    /// Reports the scripted answers to `changed`, then no changes
+    #[derive(Default)]
+    struct FakeWatcher {
+        changes: VecDeque<bool>,
+        watched: Vec<PathBuf>,
+    }
+
+    impl Watcher for FakeWatcher {
+        fn changed(&mut self) -> bool {
+            self.changes.pop_front().unwrap_or(false)
+        }
+
+        fn watch(&mut self, files: Vec<PathBuf>) {
+            self.watched = files;
+        }
+    }
+
+    /// Reports the listed pids as running, with no windows
+    #[derive(Default)]
+    struct FakeProcesses(Mutex<Vec<u32>>);
+
+    impl ProcessQuery for FakeProcesses {
+        fn snapshot(&self) -> ProcessTable {
+            ProcessTable {
+                processes: self
+                    .0
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|&pid| ProcessEntry { pid, parent: 0 })
+                    .collect(),
+                windows: Vec::new(),
+            }
+        }
+    }
+
+    fn runs(names: &[&[&str]]) -> Vec<Vec<String>> {
+        names
+            .iter()
+            .map(|run| run.iter().map(|n| n.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn reload_launches_only_added_characters() {
+        let (out, events) = recording_output();
+        let mut launcher = MockLauncher::default();
+        let processes = FakeProcesses::default();
+        let mut daemon =
+            Daemon::new(config(&["MyWarrior", "MyMage"]), &mut launcher, &processes, &out);
+        daemon.launch_pending();
+
+        let mut changed = config(&["MyWarrior", "MyThief"]);
+        changed.characters[0].slot = 5;
+        changed.stagger_delay_seconds += 1;
+        assert!(daemon.reload(Ok(changed)));
+        drop(daemon);
+
+        assert_eq!(launcher.runs, runs(&[&["MyWarrior", "MyMage"], &["MyThief"]]));
+        let events = events.lock().unwrap();
+        let reloaded = events.iter().find(|e| e["event"] == "config_reloaded").unwrap();
+        assert_eq!(reloaded["added"], serde_json::json!(["MyThief"]));
+        assert_eq!(reloaded["removed"], serde_json::json!(["MyMage"]));
+        assert_eq!(reloaded["changed"], serde_json::json!(["MyWarrior"]));
+        assert_eq!(reloaded["settings_changed"], true);
+    }
+
+    #[test]
+    fn failed_reload_keeps_the_current_config() {
+        let (out, events) = recording_output();
+        let mut launcher = MockLauncher::default();
+        let processes = FakeProcesses::default();
+        let mut daemon = Daemon::new(config(&["MyWarrior"]), &mut launcher, &processes, &out);
+        daemon.launch_pending();
+
+        assert!(!daemon.reload(Err("bad config".into())));
+        daemon.launch_pending();
+        drop(daemon);
+
+        assert_eq!(launcher.runs, runs(&[&["MyWarrior"]]));
+        let events = events.lock().unwrap();
+        let failed = events.iter().find(|e| e["event"] == "config_reload_failed").unwrap();
+        assert_eq!(failed["error"], "bad config");
+    }
+
+    #[test]
+    fn removed_characters_launch_again_when_added_back_after_their_game_closed() {
+        let out = Output::new(OutputFormat::Json);
+        let mut launcher = MockLauncher::default();
+        let processes = FakeProcesses::default();
+        let mut daemon =
+            Daemon::new(config(&["MyWarrior", "MyMage"]), &mut launcher, &processes, &out);
+        daemon.launch_pending();
+        daemon.reload(Ok(config(&["MyWarrior"])));
+        daemon.reload(Ok(config(&["MyWarrior", "mymage"])));
+        drop(daemon);
+
+        assert_eq!(launcher.runs, runs(&[&["MyWarrior", "MyMage"], &["mymage"]]));
+    }
+
+    #[test]
+    fn removed_characters_added_back_while_running_are_not_launched_twice() {
+        let out = Output::new(OutputFormat::Json);
+        let mut launcher = MockLauncher::default();
+        let processes = FakeProcesses::default();
+        let mut daemon =
+            Daemon::new(config(&["MyWarrior", "MyMage"]), &mut launcher, &processes, &out);
+        daemon.launch_pending();
+        // MyMage's Windower is still running when it comes back
+        let mage = daemon.running[1].1.unwrap();
+        processes.0.lock().unwrap().push(mage);
+        daemon.reload(Ok(config(&["MyWarrior"])));
+        daemon.reload(Ok(config(&["MyWarrior", "MyMage"])));
+        assert!(daemon.running.iter().any(|(name, _)| name == "MyMage"));
+        drop(daemon);
+
+        assert_eq!(launcher.runs, runs(&[&["MyWarrior", "MyMage"]]));
+    }
+
+    #[test]
+    fn characters_that_failed_to_launch_are_retried_on_reload() {
+        let out = Output::new(OutputFormat::Json);
+        let mut launcher = MockLauncher {
+            fail_launch: vec!["MyMage".into()],
+            fail_login: vec!["MyWarrior".into()],
+            ..Default::default()
+        };
+        let processes = FakeProcesses::default();
+        let mut daemon =
+            Daemon::new(config(&["MyWarrior", "MyMage"]), &mut launcher, &processes, &out);
+        daemon.launch_pending();
+        daemon.reload(Ok(config(&["MyWarrior", "MyMage"])));
+        drop(daemon);
+
+        // A failed login leaves its window open, so only the failed launch is retried
+        assert_eq!(launcher.runs, runs(&[&["MyWarrior", "MyMage"], &["MyMage"]]));
+    }
+
+    #[test]
+    fn poll_waits_for_the_files_to_settle_before_reloading() {
+        let out = Output::new(OutputFormat::Json);
+        let clock = FakeClock::new(chrono::Utc::now());
+        let mut launcher = MockLauncher::default();
+        let processes = FakeProcesses::default();
+        let mut daemon = Daemon::new(config(&["MyWarrior"]), &mut launcher, &processes, &out);
+        daemon.launch_pending();
+
+        // Changed, then changed again a second into the wait
+        let mut watcher = FakeWatcher {
+            changes: VecDeque::from([true, true, false, false]),
+            ..Default::default()
+        };
+        let mut loads = 0;
+        let mut load = || {
+            loads += 1;
+            Ok(Loaded {
+                config: config(&["MyWarrior", "MyMage"]),
+                files: vec![PathBuf::from("config.json"), PathBuf::from("shared.json")],
+            })
+        };
+        assert!(daemon.poll(&mut watcher, &clock, &mut load));
+        assert!(!daemon.poll(&mut watcher, &clock, &mut load));
+        drop(daemon);
+
+        assert_eq!(loads, 1);
+        assert_eq!(clock.elapsed(), POLL_INTERVAL + SETTLE_TIME);
+        assert_eq!(watcher.watched.len(), 2);
+        assert_eq!(launcher.runs, runs(&[&["MyWarrior"], &["MyMage"]]));
+    }
+
+    #[test]
+    fn poll_keeps_watching_the_old_files_when_loading_fails() {
+        let out = Output::new(OutputFormat::Json);
+        let clock = FakeClock::new(chrono::Utc::now());
+        let mut launcher = MockLauncher::default();
+        let processes = FakeProcesses::default();
+        let mut daemon = Daemon::new(config(&["MyWarrior"]), &mut launcher, &processes, &out);
+
+        let mut watcher = FakeWatcher {
+            changes: VecDeque::from([true]),
+            watched: vec![PathBuf::from("config.json")],
+        };
+        let mut load = || Err("Invalid JSON".to_string());
+        assert!(daemon.poll(&mut watcher, &clock, &mut load));
+        drop(daemon);
+
+        assert_eq!(watcher.watched, [PathBuf::from("config.json")]);
+        assert!(launcher.runs.is_empty());
+    }
+}
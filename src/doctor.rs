@@ -0,0 +1,285 @@
+use crate::checks::{CheckResult, FileSystem, Network};
+use crate::config::Config;
+use crate::hosts;
+use crate::login_bin;
+use std::path::Path;
+
+/// Environment checks for a machine about to run login-rs.
+/// `config` is `None` when the config failed to load, in which case the
+/// config-dependent checks are reported as skipped.
+pub fn run_doctor(
+    config: Option<&Config>,
+    fs: &dyn FileSystem,
+    net: &dyn Network,
+    hosts_path: &Path,
+    elevated: bool,
+) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+
+    match config {
+        Some(config) => {
+            results.push(check_windower(config, fs));
+            results.push(check_playonline_dir(config, fs));
+            results.push(check_login_bin(config, fs));
+            results.push(check_proxy_port(config, net));
+        }
+        None => {
+            for name in ["Windower", "PlayOnline directory", "login_w.bin", "Proxy port"] {
+                results.push(CheckResult::warn(name, "Skipped: config did not load"));
+            }
+        }
+    }
+
+    results.push(check_hosts_writable(fs, hosts_path));
+    results.push(check_stale_hosts(fs, hosts_path));
+    results.push(check_elevation(elevated));
+
+    results
+}
+
+fn check_windower(config: &Config, fs: &dyn FileSystem) -> CheckResult {
+    let path = &config.windower_path;
+    if fs.is_file(path) {
+        CheckResult::pass("Windower", path.display().to_string())
+    } else {
+        CheckResult::fail("Windower", format!("Not found: {}", path.display()))
+    }
+}
+
+fn check_playonline_dir(config: &Config, fs: &dyn FileSystem) -> CheckResult {
+    let path = &config.playonline_dir;
+    if fs.is_dir(path) {
+        CheckResult::pass("PlayOnline directory", path.display().to_string())
+    } else {
+        CheckResult::fail("PlayOnline directory", format!("Not found: {}", path.display()))
+    }
+}
+
+fn check_login_bin(config: &Config, fs: &dyn FileSystem) -> CheckResult {
+    let path = config.playonline_dir.join("login_w.bin");
+    let bytes = match fs.read(&path) {
+        Ok(b) => b,
+        Err(e) => {
+            return CheckResult::fail(
+                "login_w.bin",
+                format!("Cannot read {}: {}", path.display(), e),
+            )
+        }
+    };
+    match login_bin::parse_login_bin(&bytes) {
+        Ok(info) if info.auto_login_enabled => CheckResult::warn(
+            "login_w.bin",
+            "Auto-login is enabled in PlayOnline; it can skip the member selection screen",
+        ),
+        Ok(info) => CheckResult::pass(
+            "login_w.bin",
+            format!("Readable (last selected slot {})", info.current_slot),
+        ),
+        Err(e) => CheckResult::fail("login_w.bin", e),
+    }
+}
+
+fn check_proxy_port(config: &Config, net: &dyn Network) -> CheckResult {
+    let port = config.region.proxy_port();
+    match net.probe_port(port) {
+        Ok(()) => CheckResult::pass("Proxy port", format!("{} is free", port)),
+        Err(e) => CheckResult::fail("Proxy port", format!("Cannot bind {}: {}", port, e)),
+    }
+}
+
+fn check_hosts_writable(fs: &dyn FileSystem, hosts_path: &Path) -> CheckResult {
+    match fs.probe_writable(hosts_path) {
+        Ok(()) => CheckResult::pass("Hosts file", "Writable"),
+        Err(e) => CheckResult::fail(
+            "Hosts file",
+            format!("Cannot write {}: {}", hosts_path.display(), e),
+        ),
+    }
+}
+
+fn check_stale_hosts(fs: &dyn FileSystem, hosts_path: &Path) -> CheckResult {
+    let contents = match fs.read(hosts_path) {
+        Ok(b) => String::from_utf8_lossy(&b).into_owned(),
+        Err(e) => {
+            return CheckResult::warn(
+                "Stale hosts entries",
+                format!("Cannot read {}: {}", hosts_path.display(), e),
+            )
+        }
+    };
+    match hosts::count_entries(&contents) {
+        0 => CheckResult::pass("Stale hosts entries", "None"),
+        n => CheckResult::warn(
+            "Stale hosts entries",
+            format!("{} '# login-rs' line(s) left from a previous run; they are removed at startup", n),
+        ),
+    }
+}
+
+fn check_elevation(elevated: bool) -> CheckResult {
+    if elevated {
+        CheckResult::pass("Elevation", "Running as administrator")
+    } else {
+        CheckResult::warn(
+            "Elevation",
+            "Not elevated; login will request administrator rights",
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checks::CheckStatus;
+    use std::collections::HashMap;
+    use std::io;
+    use std::path::PathBuf;
+
+    const HOSTS: &str = "/etc/hosts";
+
+    /// Files and directories that exist, with their contents
+    #[derive(Default)]
+    struct FakeFs {
+        files: HashMap<PathBuf, Vec<u8>>,
+        dirs: Vec<PathBuf>,
+        read_only: Vec<PathBuf>,
+    }
+
+    impl FakeFs {
+        fn with_file(mut self, path: impl Into<PathBuf>, contents: &[u8]) -> Self {
+            self.files.insert(path.into(), contents.to_vec());
+            self
+        }
+    }
+
+    impl FileSystem for FakeFs {
+        fn is_file(&self, path: &Path) -> bool {
+            self.files.contains_key(path)
+        }
+
+        fn is_dir(&self, path: &Path) -> bool {
+            self.dirs.iter().any(|d| d == path)
+        }
+
+        fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+            self.files
+                .get(path)
+                .cloned()
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        }
+
+        fn probe_writable(&self, path: &Path) -> io::Result<()> {
+            if self.read_only.iter().any(|p| p == path) {
+                Err(io::Error::from(io::ErrorKind::PermissionDenied))
+            } else if self.is_file(path) {
+                Ok(())
+            } else {
+                Err(io::Error::from(io::ErrorKind::NotFound))
+            }
+        }
+    }
+
+    struct FakeNet {
+        busy: Vec<u16>,
+    }
+
+    impl Network for FakeNet {
+        fn probe_port(&self, port: u16) -> io::Result<()> {
+            if self.busy.contains(&port) {
+                Err(io::Error::from(io::ErrorKind::AddrInUse))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn config() -> Config {
+        Config::parse(
+            r#"{
+                "version": 2,
+                "windower_path": "/games/Windower.exe",
+                "playonline_dir": "/games/pol",
+                "characters": [{ "name": "MyWarrior", "slot": 1, "password": "Passw0rd!" }]
+            }"#,
+            crate::config::ConfigFormat::Json,
+        )
+        .unwrap()
+    }
+
+    fn login_bin(slot: u8, auto_login: bool) -> Vec<u8> {
+        let mut bytes = vec![0; 0x70];
+        bytes[0x64] = slot;
+        bytes[0x6F] = u8::from(auto_login);
+        bytes
+    }
+
+    fn healthy() -> FakeFs {
+        FakeFs {
+            dirs: vec![PathBuf::from("/games/pol")],
+            ..FakeFs::default()
+        }
+        .with_file("/games/Windower.exe", b"")
+        .with_file("/games/pol/login_w.bin", &login_bin(3, false))
+        .with_file(HOSTS, b"127.0.0.1 localhost\n")
+    }
+
+    fn status_of<'a>(results: &'a [CheckResult], name: &str) -> &'a CheckResult {
+        results.iter().find(|r| r.name == name).unwrap()
+    }
+
+    #[test]
+    fn healthy_machine_passes() {
+        let results = run_doctor(Some(&config()), &healthy(), &FakeNet { busy: vec![] }, Path::new(HOSTS), true);
+        assert!(results.iter().all(|r| r.status == CheckStatus::Pass), "{:?}", results);
+        assert_eq!(status_of(&results, "login_w.bin").detail, "Readable (last selected slot 3)");
+    }
+
+    #[test]
+    fn missing_files_and_busy_port_fail() {
+        let fs = FakeFs::default().with_file(HOSTS, b"");
+        let net = FakeNet {
+            busy: vec![config().region.proxy_port()],
+        };
+        let results = run_doctor(Some(&config()), &fs, &net, Path::new(HOSTS), true);
+        for name in ["Windower", "PlayOnline directory", "login_w.bin", "Proxy port"] {
+            assert_eq!(status_of(&results, name).status, CheckStatus::Fail, "{}", name);
+        }
+    }
+
+    #[test]
+    fn login_bin_problems() {
+        let auto = healthy().with_file("/games/pol/login_w.bin", &login_bin(1, true));
+        let results = run_doctor(Some(&config()), &auto, &FakeNet { busy: vec![] }, Path::new(HOSTS), true);
+        assert_eq!(status_of(&results, "login_w.bin").status, CheckStatus::Warn);
+
+        let short = healthy().with_file("/games/pol/login_w.bin", &[0; 16]);
+        let results = run_doctor(Some(&config()), &short, &FakeNet { busy: vec![] }, Path::new(HOSTS), true);
+        let check = status_of(&results, "login_w.bin");
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(check.detail.contains("too short (16 bytes"), "{}", check.detail);
+    }
+
+    #[test]
+    fn hosts_file_checks() {
+        let mut fs = healthy().with_file(HOSTS, b"127.0.0.1 wh000.pol.com # login-rs\n");
+        fs.read_only.push(PathBuf::from(HOSTS));
+        let results = run_doctor(Some(&config()), &fs, &FakeNet { busy: vec![] }, Path::new(HOSTS), false);
+        assert_eq!(status_of(&results, "Hosts file").status, CheckStatus::Fail);
+        let stale = status_of(&results, "Stale hosts entries");
+        assert_eq!(stale.status, CheckStatus::Warn);
+        assert!(stale.detail.starts_with("1 '# login-rs' line(s)"));
+        assert_eq!(status_of(&results, "Elevation").status, CheckStatus::Warn);
+    }
+
+    #[test]
+    fn config_checks_are_skipped_without_a_config() {
+        let results = run_doctor(None, &healthy(), &FakeNet { busy: vec![] }, Path::new(HOSTS), true);
+        let skipped: Vec<&str> = results
+            .iter()
+            .filter(|r| r.detail == "Skipped: config did not load")
+            .map(|r| r.name.as_str())
+            .collect();
+        assert_eq!(skipped, ["Windower", "PlayOnline directory", "login_w.bin", "Proxy port"]);
+        assert_eq!(status_of(&results, "Hosts file").status, CheckStatus::Pass);
+    }
+}
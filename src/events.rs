@@ -0,0 +1,512 @@
+use crate::checks::{CheckResult, CheckStatus};
+use crate::report::RunReport;
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable status lines
+    Text,
+    /// One JSON event per line on stdout
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CharacterSummary {
+    pub name: String,
+    pub slot: u8,
+}
+
+/// A user-facing status update. In text mode each event renders as the
+/// familiar console line; in JSON mode it is written as a single JSON object.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    RunStarted {
+        version: &'a str,
+        characters: Vec<CharacterSummary>,
+        log_dir: String,
+    },
+    PhaseStarted {
+        phase: u8,
+        description: &'a str,
+    },
+    Launching {
+        character: &'a str,
+    },
+    WindowFound {
+        character: &'a str,
+    },
+    LaunchFailed {
+        character: &'a str,
+    },
+    NoInstancesLaunched,
+    WaitingForWindows,
+    LoggingIn {
+        character: &'a str,
+    },
+    TargetingSlot {
+        character: &'a str,
+        slot: u8,
+    },
+    ResettingSlot {
+        character: &'a str,
+    },
+    NavigatingToSlot {
+        character: &'a str,
+        presses: u8,
+        slot: u8,
+    },
+    SlotKeyPress {
+        character: &'a str,
+        press: u8,
+        total: u8,
+    },
+    SelectingSlot {
+        character: &'a str,
+    },
+    LoginAttemptFailed {
+        character: &'a str,
+        attempt: u32,
+        error: &'a str,
+    },
+    RetryPrompt {
+        character: &'a str,
+    },
+    LoginRetrying {
+        character: &'a str,
+        attempt: u32,
+        max_attempts: u32,
+    },
+    LoginComplete {
+        character: &'a str,
+    },
+    LoginFailed {
+        character: &'a str,
+    },
+    StaggerWait {
+        seconds: u64,
+    },
+    ParallelLogins {
+        limit: usize,
+    },
+    GameWindowFound {
+        character: &'a str,
+        seconds: u64,
+    },
+    GameWindowNotFound {
+        character: &'a str,
+        seconds: u64,
+    },
+    SelectingCharacter {
+        character: &'a str,
+        index: u8,
+    },
+    LobbyStep {
+        character: &'a str,
+        step: &'a str,
+    },
+    LobbyKeysSent {
+        character: &'a str,
+    },
+    LobbyFailed {
+        character: &'a str,
+        step: &'a str,
+        error: &'a str,
+    },
+    Done,
+    RunFinished {
+        report: &'a RunReport,
+    },
+    ReportWritten {
+        path: String,
+    },
+    Check {
+        #[serde(flatten)]
+        result: &'a CheckResult,
+    },
+    CharacterListed {
+        name: &'a str,
+        slot: u8,
+    },
+    HostsCleaned {
+        removed: usize,
+    },
+    ConfigValue {
+        key: &'a str,
+        value: &'a Value,
+        origin: &'a str,
+    },
+    Watching {
+        files: Vec<String>,
+    },
+    ConfigReloaded {
+        added: Vec<&'a str>,
+        removed: Vec<&'a str>,
+        changed: Vec<&'a str>,
+        settings_changed: bool,
+    },
+    ConfigReloadFailed {
+        error: &'a str,
+    },
+    RunCancelled,
+    ApiListening {
+        address: String,
+    },
+    ScheduledRunNext {
+        schedule: &'a str,
+        at: String,
+        characters: Vec<&'a str>,
+    },
+    ScheduledRunStarted {
+        schedules: Vec<&'a str>,
+    },
+    ScheduledRunSkipped {
+        schedule: &'a str,
+        at: String,
+    },
+    WatchdogStarted {
+        characters: Vec<&'a str>,
+    },
+    GameLost {
+        character: &'a str,
+        reason: &'a str,
+    },
+    Relaunching {
+        character: &'a str,
+        restart: u32,
+        max: u32,
+    },
+    WatchdogGaveUp {
+        character: &'a str,
+        restarts: u32,
+    },
+}
+
+impl Event<'_> {
+    /// Console rendering for text mode
+    fn to_text(&self) -> String {
+        match self {
+            Event::RunStarted {
+                version,
+                characters,
+                log_dir,
+            } => {
+                let mut lines = vec![
+                    format!("login-rs v{}", version),
+                    format!("Launching {} character(s):", characters.len()),
+                ];
+                for ch in characters {
+                    lines.push(format!("  - {} (slot {})", ch.name, ch.slot));
+                }
+                lines.push(format!("Logs: {}", log_dir));
+                lines.join("\n")
+            }
+            Event::PhaseStarted { phase, description } => {
+                format!("\n=== Phase {}: {} ===", phase, description)
+            }
+            Event::Launching { character } => format!("  Launching Windower for {}...", character),
+            Event::WindowFound { character } => format!("  ✓ {} - window found", character),
+            Event::LaunchFailed { character } => format!("  ✗ {} - failed after retry", character),
+            Event::NoInstancesLaunched => {
+                "No Windower instances launched successfully. Aborting.".to_string()
+            }
+            Event::WaitingForWindows => "Waiting for PlayOnline windows to initialize...".to_string(),
+            Event::LoggingIn { character } => format!("  Logging in {}...", character),
+            Event::TargetingSlot { character, slot } => {
+                format!("    {}: targeting slot {}", character, slot)
+            }
+            Event::ResettingSlot { .. } => {
+                "    Resetting slot position with mouse wheel up".to_string()
+            }
+            Event::NavigatingToSlot { presses, slot, .. } => {
+                format!("    Moving down {} time(s) to reach slot {}", presses, slot)
+            }
+            Event::SlotKeyPress { press, total, .. } => {
+                format!("    DOWN press {}/{}", press, total)
+            }
+            Event::SelectingSlot { .. } => "    ENTER to select slot".to_string(),
+            Event::LoginAttemptFailed {
+                character, error, ..
+            } => format!("  ✗ {} login failed: {}", character, error),
+            Event::RetryPrompt { .. } => {
+                "  Press Enter to retry, or type 'skip' to skip this character:".to_string()
+            }
+            Event::LoginRetrying {
+                character,
+                attempt,
+                max_attempts,
+            } => format!("  Retrying {} (attempt {}/{})", character, attempt, max_attempts),
+            Event::LoginComplete { character } => {
+                format!("  ✓ {} - login automation complete", character)
+            }
+            Event::LoginFailed { character } => {
+                format!("  ✗ {} - login failed after retry", character)
+            }
+            Event::StaggerWait { seconds } => {
+                format!("  Waiting {} seconds before next login...", seconds)
+            }
+            Event::ParallelLogins { limit } => {
+                format!("  Logging in up to {} characters at once", limit)
+            }
+            Event::GameWindowFound { character, seconds } => {
+                format!("  ✓ {} - game started after {}s", character, seconds)
+            }
+            Event::GameWindowNotFound { character, seconds } => {
+                format!("  ✗ {} - game window did not appear within {}s", character, seconds)
+            }
+            Event::SelectingCharacter { character, index } => {
+                format!("  {} - selecting FFXI character {}", character, index)
+            }
+            Event::LobbyStep { character, step } => {
+                format!("  {} - keys sent at {}", character, step)
+            }
+            Event::LobbyKeysSent { character } => {
+                format!("  ✓ {} - sent every lobby step (the game itself isn't checked)", character)
+            }
+            Event::LobbyFailed { character, step, error } => {
+                format!("  ✗ {} - stuck at {}: {}", character, step, error)
+            }
+            Event::Done => "\n=== Done ===".to_string(),
+            Event::RunFinished { report } => {
+                format!("\n=== Run Report ===\n{}", report.render_table().trim_end())
+            }
+            Event::ReportWritten { path } => format!("Report: {}", path),
+            Event::Check { result } => {
+                let mark = match result.status {
+                    CheckStatus::Pass => "✓",
+                    CheckStatus::Warn => "!",
+                    CheckStatus::Fail => "✗",
+                };
+                format!("  {} {} - {}", mark, result.name, result.detail)
+            }
+            Event::CharacterListed { name, slot } => format!("  - {} (slot {})", name, slot),
+            Event::HostsCleaned { removed } => {
+                format!("Removed {} login-rs hosts entr{}", removed, if *removed == 1 { "y" } else { "ies" })
+            }
+            Event::ConfigValue { key, value, origin } => format!("{} = {}  # {}", key, value, origin),
+            Event::Watching { files } => {
+                format!("Watching {} for changes (Ctrl+C to stop)", files.join(", "))
+            }
+            Event::ConfigReloaded {
+                added,
+                removed,
+                changed,
+                settings_changed,
+            } => {
+                let mut parts = Vec::new();
+                if !added.is_empty() {
+                    parts.push(format!("added {}", added.join(", ")));
+                }
+                if !removed.is_empty() {
+                    // Running instances are left alone; only future launches are affected
+                    parts.push(format!("removed {} (left running)", removed.join(", ")));
+                }
+                if !changed.is_empty() {
+                    parts.push(format!("updated {}", changed.join(", ")));
+                }
+                if *settings_changed {
+                    parts.push("settings updated".to_string());
+                }
+                if parts.is_empty() {
+                    "Config reloaded, nothing changed".to_string()
+                } else {
+                    format!("Config reloaded: {}", parts.join("; "))
+                }
+            }
+            Event::ConfigReloadFailed { error } => {
+                format!("Config reload failed, keeping the previous config: {}", error)
+            }
+            Event::RunCancelled => "\n=== Cancelled ===".to_string(),
+            Event::ApiListening { address } => {
+                format!("Control API listening on http://{} (Ctrl+C to stop)", address)
+            }
+            Event::ScheduledRunNext {
+                schedule,
+                at,
+                characters,
+            } => format!(
+                "Next scheduled login at {} ({}): {} (Ctrl+C to stop)",
+                at,
+                schedule,
+                characters.join(", ")
+            ),
+            Event::ScheduledRunStarted { schedules } => {
+                format!("\n=== Scheduled login ({}) ===", schedules.join(", "))
+            }
+            Event::ScheduledRunSkipped { schedule, at } => format!(
+                "Skipped scheduled login at {} ({}): the previous run was still going",
+                at, schedule
+            ),
+            Event::WatchdogStarted { characters } => format!(
+                "\nWatching {} for crashes (Ctrl+C to stop)",
+                characters.join(", ")
+            ),
+            Event::GameLost { character, reason } => {
+                format!("  ✗ {} - game is gone ({})", character, reason)
+            }
+            Event::Relaunching {
+                character,
+                restart,
+                max,
+            } => format!("  Relaunching {} (restart {}/{} this hour)", character, restart, max),
+            Event::WatchdogGaveUp { character, restarts } => format!(
+                "  ✗ {} - restarted {} times in the last hour, no longer relaunching",
+                character, restarts
+            ),
+        }
+    }
+
+    fn is_error(&self) -> bool {
+        matches!(
+            self,
+            Event::NoInstancesLaunched
+                | Event::ConfigReloadFailed { .. }
+                | Event::WatchdogGaveUp { .. }
+                | Event::GameWindowNotFound { .. }
+                | Event::LobbyFailed { .. }
+        )
+    }
+}
+
+type Listener = Box<dyn Fn(&Event) + Send + Sync>;
+
+/// Writes events to stdout in the selected format, and passes them to any subscribers
+pub struct Output {
+    format: OutputFormat,
+    listeners: Mutex<Vec<Listener>>,
+}
+
+impl Output {
+    pub fn new(format: OutputFormat) -> Self {
+        Self {
+            format,
+            listeners: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Call `listener` with every event emitted from now on
+    pub fn subscribe(&self, listener: impl Fn(&Event) + Send + Sync + 'static) {
+        self.listeners.lock().unwrap().push(Box::new(listener));
+    }
+
+    pub fn emit(&self, event: Event) {
+        self.print(&event);
+        for listener in self.listeners.lock().unwrap().iter() {
+            listener(&event);
+        }
+    }
+
+    fn print(&self, event: &Event) {
+        match self.format {
+            OutputFormat::Text => {
+                if event.is_error() {
+                    eprintln!("{}", event.to_text());
+                } else {
+                    println!("{}", event.to_text());
+                }
+            }
+            OutputFormat::Json => match serde_json::to_string(event) {
+                Ok(line) => println!("{}", line),
+                Err(e) => log::error!("Failed to serialize event: {}", e),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{CharacterOutcome, LoginResult};
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    fn json(event: &Event) -> String {
+        serde_json::to_string(event).unwrap()
+    }
+
+    #[test]
+    fn json_events_snapshot() {
+        let lines = [
+            json(&Event::RunStarted {
+                version: "1.2.3",
+                characters: vec![CharacterSummary {
+                    name: "MyWarrior".to_string(),
+                    slot: 1,
+                }],
+                log_dir: "logs/run".to_string(),
+            }),
+            json(&Event::PhaseStarted {
+                phase: 2,
+                description: "Logging in",
+            }),
+            json(&Event::NoInstancesLaunched),
+            json(&Event::LoginAttemptFailed {
+                character: "MyWarrior",
+                attempt: 1,
+                error: "timed out",
+            }),
+            json(&Event::LoginRetrying {
+                character: "MyWarrior",
+                attempt: 2,
+                max_attempts: 3,
+            }),
+            json(&Event::LoginComplete {
+                character: "MyWarrior",
+            }),
+            json(&Event::Check {
+                result: &CheckResult::warn("Delays", "launch_delay_seconds is 0"),
+            }),
+            json(&Event::Done),
+        ];
+        assert_eq!(
+            lines,
+            [
+                r#"{"event":"run_started","version":"1.2.3","characters":[{"name":"MyWarrior","slot":1}],"log_dir":"logs/run"}"#,
+                r#"{"event":"phase_started","phase":2,"description":"Logging in"}"#,
+                r#"{"event":"no_instances_launched"}"#,
+                r#"{"event":"login_attempt_failed","character":"MyWarrior","attempt":1,"error":"timed out"}"#,
+                r#"{"event":"login_retrying","character":"MyWarrior","attempt":2,"max_attempts":3}"#,
+                r#"{"event":"login_complete","character":"MyWarrior"}"#,
+                r#"{"event":"check","name":"Delays","status":"warn","detail":"launch_delay_seconds is 0"}"#,
+                r#"{"event":"done"}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn run_finished_carries_the_report() {
+        let outcome = CharacterOutcome {
+            name: "MyWarrior".to_string(),
+            slot: 3,
+            launched: true,
+            login: LoginResult::Success,
+            attempts: 1,
+            time_to_window: Some(Duration::from_millis(1500)),
+            time_to_proxy: None,
+            time_to_game: None,
+            instance: None,
+        };
+        let report = RunReport::build(&[outcome], &HashMap::new());
+        let value: Value = serde_json::from_str(&json(&Event::RunFinished { report: &report })).unwrap();
+        assert_eq!(value["event"], "run_finished");
+        assert_eq!(value["report"]["status"], "success");
+        assert_eq!(value["report"]["characters"][0]["name"], "MyWarrior");
+        assert_eq!(value["report"]["characters"][0]["time_to_window_ms"], 1500);
+    }
+
+    #[test]
+    fn failures_go_to_stderr() {
+        assert!(Event::NoInstancesLaunched.is_error());
+        assert!(Event::GameWindowNotFound {
+            character: "MyWarrior",
+            seconds: 120
+        }
+        .is_error());
+        assert!(!Event::LoginComplete {
+            character: "MyWarrior"
+        }
+        .is_error());
+    }
+}
@@ -0,0 +1,102 @@
+use crate::report::RunStatus;
+
+/// Process exit codes. These are part of the CLI contract; see the README.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    /// Every selected character logged in
+    Success = 0,
+    /// Config could not be loaded, no characters matched, or startup failed
+    ConfigError = 1,
+    /// Some characters logged in, others failed
+    PartialFailure = 2,
+    /// No character logged in
+    AllFailed = 3,
+    /// The UAC elevation prompt was declined or failed
+    ElevationRefused = 4,
+    /// Ctrl+C was pressed during the run
+    Interrupted = 130,
+}
+
+impl ExitCode {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    pub fn exit(self) -> ! {
+        std::process::exit(self.code())
+    }
+}
+
+impl From<RunStatus> for ExitCode {
+    fn from(status: RunStatus) -> Self {
+        match status {
+            RunStatus::Success => ExitCode::Success,
+            RunStatus::PartialFailure => ExitCode::PartialFailure,
+            RunStatus::TotalFailure => ExitCode::AllFailed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{CharacterOutcome, LoginResult, RunReport};
+    use std::collections::HashMap;
+
+    fn outcome(name: &str, launched: bool, login: LoginResult) -> CharacterOutcome {
+        CharacterOutcome {
+            name: name.to_string(),
+            slot: 1,
+            launched,
+            login,
+            attempts: u32::from(launched),
+            time_to_window: None,
+            time_to_proxy: None,
+            time_to_game: None,
+            instance: None,
+        }
+    }
+
+    fn exit_code_for(outcomes: &[CharacterOutcome]) -> ExitCode {
+        ExitCode::from(RunReport::build(outcomes, &HashMap::new()).status)
+    }
+
+    #[test]
+    fn every_login_succeeding_exits_0() {
+        let code = exit_code_for(&[
+            outcome("A", true, LoginResult::Success),
+            outcome("B", true, LoginResult::Success),
+        ]);
+        assert_eq!(code, ExitCode::Success);
+        assert_eq!(code.code(), 0);
+    }
+
+    #[test]
+    fn some_logins_failing_exits_2() {
+        let code = exit_code_for(&[
+            outcome("A", true, LoginResult::Success),
+            outcome("B", true, LoginResult::Failed),
+            outcome("C", false, LoginResult::NotAttempted),
+        ]);
+        assert_eq!(code, ExitCode::PartialFailure);
+        assert_eq!(code.code(), 2);
+    }
+
+    #[test]
+    fn no_login_succeeding_exits_3() {
+        let code = exit_code_for(&[
+            outcome("A", true, LoginResult::Failed),
+            outcome("B", false, LoginResult::NotAttempted),
+        ]);
+        assert_eq!(code, ExitCode::AllFailed);
+        assert_eq!(code.code(), 3);
+        assert_eq!(exit_code_for(&[]), ExitCode::AllFailed);
+    }
+
+    #[test]
+    fn fixed_codes_match_the_readme() {
+        assert_eq!(ExitCode::ConfigError.code(), 1);
+        assert_eq!(ExitCode::ElevationRefused.code(), 4);
+        assert_eq!(ExitCode::Interrupted.code(), 130);
+    }
+}
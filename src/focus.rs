@@ -0,0 +1,199 @@
+use crate::clock::Clock;
+use crate::win32;
+use std::time::Duration;
+
+/// How many times to try bringing a window to the front before giving up
+const FOCUS_ATTEMPTS: u32 = 5;
+
+/// Time Windows gets to switch windows after a focus request
+const FOCUS_SETTLE: Duration = Duration::from_millis(200);
+
+/// Reads and changes the foreground window. `SystemWindows` asks Windows; code that
+/// sends keys takes the trait so it can run against a fake window manager.
+pub trait WindowBackend {
+    /// `win32::window_id` of the foreground window, if there is one
+    fn foreground(&self) -> Option<isize>;
+    fn focus(&self, window: isize);
+}
+
+pub struct SystemWindows;
+
+impl WindowBackend for SystemWindows {
+    fn foreground(&self) -> Option<isize> {
+        win32::foreground_window().map(win32::window_id)
+    }
+
+    fn focus(&self, window: isize) {
+        win32::focus_window(win32::window_handle(window));
+    }
+}
+
+/// Make sure `window` is in front before keys are sent to it, re-focusing it if
+/// something else (e.g. a popup) took over. Fails if it can't be brought back,
+/// so the keys aren't typed into another application.
+pub fn ensure_focused(
+    windows: &dyn WindowBackend,
+    window: isize,
+    clock: &dyn Clock,
+) -> Result<(), String> {
+    for attempt in 1..=FOCUS_ATTEMPTS {
+        if windows.foreground() == Some(window) {
+            if attempt > 1 {
+                log::debug!("Window regained focus after {} attempt(s)", attempt - 1);
+            }
+            return Ok(());
+        }
+        windows.focus(window);
+        clock.sleep(FOCUS_SETTLE);
+    }
+    if windows.foreground() == Some(window) {
+        return Ok(());
+    }
+    Err(format!(
+        "another window stayed in front after {} attempts to focus the character's window",
+        FOCUS_ATTEMPTS
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::input::KeySender;
+    use crate::sequence::{press, Progress, Sequence, Step};
+    use crate::typing::InputEvent;
+    use std::sync::Mutex;
+
+    const GAME: isize = 7;
+    const POPUP: isize = 99;
+
+    /// A window manager where another window is in front until `refused` focus
+    /// requests have been ignored
+    struct FakeWindows {
+        foreground: Mutex<Option<isize>>,
+        refused: Mutex<u32>,
+        requests: Mutex<u32>,
+    }
+
+    impl FakeWindows {
+        fn new(foreground: isize, refused: u32) -> Self {
+            Self {
+                foreground: Mutex::new(Some(foreground)),
+                refused: Mutex::new(refused),
+                requests: Mutex::new(0),
+            }
+        }
+
+        fn requests(&self) -> u32 {
+            *self.requests.lock().unwrap()
+        }
+    }
+
+    impl WindowBackend for FakeWindows {
+        fn foreground(&self) -> Option<isize> {
+            *self.foreground.lock().unwrap()
+        }
+
+        fn focus(&self, window: isize) {
+            *self.requests.lock().unwrap() += 1;
+            let mut refused = self.refused.lock().unwrap();
+            if *refused > 0 {
+                *refused -= 1;
+            } else {
+                *self.foreground.lock().unwrap() = Some(window);
+            }
+        }
+    }
+
+    fn clock() -> FakeClock {
+        FakeClock::new(chrono::Utc::now())
+    }
+
+    #[test]
+    fn does_nothing_when_the_window_is_already_in_front() {
+        let (windows, clock) = (FakeWindows::new(GAME, 0), clock());
+        assert_eq!(ensure_focused(&windows, GAME, &clock), Ok(()));
+        assert_eq!(windows.requests(), 0);
+        assert_eq!(clock.elapsed(), Duration::ZERO);
+    }
+
+    #[test]
+    fn refocuses_until_the_window_is_back() {
+        let (windows, clock) = (FakeWindows::new(POPUP, 2), clock());
+        assert_eq!(ensure_focused(&windows, GAME, &clock), Ok(()));
+        assert_eq!(windows.requests(), 3);
+        assert_eq!(clock.elapsed(), FOCUS_SETTLE * 3);
+    }
+
+    #[test]
+    fn the_last_attempt_still_counts() {
+        let (windows, clock) = (FakeWindows::new(POPUP, FOCUS_ATTEMPTS - 1), clock());
+        assert_eq!(ensure_focused(&windows, GAME, &clock), Ok(()));
+        assert_eq!(windows.requests(), FOCUS_ATTEMPTS);
+    }
+
+    #[test]
+    fn gives_up_after_the_attempt_limit() {
+        let (windows, clock) = (FakeWindows::new(POPUP, u32::MAX), clock());
+        assert_eq!(
+            ensure_focused(&windows, GAME, &clock).unwrap_err(),
+            "another window stayed in front after 5 attempts to focus the character's window"
+        );
+        assert_eq!(windows.requests(), FOCUS_ATTEMPTS);
+        assert_eq!(clock.elapsed(), FOCUS_SETTLE * FOCUS_ATTEMPTS);
+    }
+
+    /// Checks focus like `SendInputKeys`, but against fake windows, and records keys
+    struct RecordingKeys<'a> {
+        windows: &'a FakeWindows,
+        clock: &'a FakeClock,
+        sent: Mutex<Vec<InputEvent>>,
+    }
+
+    impl KeySender for RecordingKeys<'_> {
+        fn ready(&self, step: &str) -> Result<(), String> {
+            ensure_focused(self.windows, GAME, self.clock)
+                .map_err(|e| format!("Before {}: {}", step, e))
+        }
+
+        fn press_key(&self, _vk: u16, _hold_ms: u64) {}
+
+        fn scroll_up(&self) {}
+
+        fn send(&self, events: &[InputEvent]) {
+            self.sent.lock().unwrap().extend_from_slice(events);
+        }
+    }
+
+    #[test]
+    fn no_keys_are_sent_while_another_window_keeps_focus() {
+        let (windows, clock) = (FakeWindows::new(POPUP, u32::MAX), clock());
+        let keys = RecordingKeys {
+            windows: &windows,
+            clock: &clock,
+            sent: Mutex::new(Vec::new()),
+        };
+        let step = Step {
+            name: "the title screen",
+            delay: Duration::ZERO,
+            events: press(0x1C, 100),
+        };
+        let mut sequence = Sequence::new(vec![step], Duration::from_secs(3), clock.now());
+
+        let mut progress = Vec::new();
+        while !sequence.finished() {
+            progress.push(sequence.poll(&keys, &clock));
+        }
+        assert_eq!(
+            progress.last(),
+            Some(&Progress::Failed {
+                step: "the title screen",
+                error: "Before the title screen: another window stayed in front after 5 \
+                        attempts to focus the character's window (tried for 3s)"
+                    .to_string(),
+            })
+        );
+        assert!(progress[..progress.len() - 1].iter().all(|p| *p == Progress::Waiting));
+        assert!(keys.sent.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,293 @@
+use crate::processes::ProcessTable;
+use crate::titles::GAME_TITLE;
+use std::time::{Duration, Instant};
+
+/// Where a logged-in character's wait for its game window stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameState {
+    Waiting,
+    /// The game window appeared this long after the login finished
+    Started { window: isize, after: Duration },
+    /// No game window within the timeout
+    TimedOut,
+}
+
+/// A change reported by `GameWatch::poll`, for the character added with `key`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameChange {
+    Started {
+        key: usize,
+        window: isize,
+        after: Duration,
+    },
+    TimedOut {
+        key: usize,
+    },
+}
+
+struct Awaited {
+    key: usize,
+    pid: u32,
+    logged_in_at: Instant,
+    state: GameState,
+}
+
+/// Tracks which logged-in characters have their FFXI game window yet. Each character
+/// is matched only to windows in its own Windower process tree, and is given
+/// `timeout` from the end of its login. Time and windows are passed in, so it can be
+/// driven by a fake clock and a prepared process table.
+pub struct GameWatch {
+    timeout: Duration,
+    awaited: Vec<Awaited>,
+    /// No more characters will be added
+    closed: bool,
+}
+
+impl GameWatch {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            awaited: Vec::new(),
+            closed: false,
+        }
+    }
+
+    /// Start waiting for the game window of the character launched as `pid`, whose
+    /// login finished at `logged_in_at`. `key` identifies it in changes and `state`.
+    /// Adding a key again, after a retried login, starts its wait over.
+    pub fn add(&mut self, key: usize, pid: u32, logged_in_at: Instant) {
+        let awaited = Awaited {
+            key,
+            pid,
+            logged_in_at,
+            state: GameState::Waiting,
+        };
+        match self.awaited.iter_mut().find(|a| a.key == key) {
+            Some(a) => *a = awaited,
+            None => self.awaited.push(awaited),
+        }
+    }
+
+    /// Look for game windows in `table` and time out characters that have waited too
+    /// long as of `now`. Returns what changed since the last poll.
+    pub fn poll(&mut self, table: &ProcessTable, now: Instant) -> Vec<GameChange> {
+        let mut changes = Vec::new();
+        for a in self.awaited.iter_mut().filter(|a| a.state == GameState::Waiting) {
+            let window = table
+                .windows_of(a.pid)
+                .into_iter()
+                .find(|w| w.title.starts_with(GAME_TITLE));
+            if let Some(w) = window {
+                let after = now.saturating_duration_since(a.logged_in_at);
+                a.state = GameState::Started {
+                    window: w.id,
+                    after,
+                };
+                changes.push(GameChange::Started {
+                    key: a.key,
+                    window: w.id,
+                    after,
+                });
+            } else if now.saturating_duration_since(a.logged_in_at) >= self.timeout {
+                a.state = GameState::TimedOut;
+                changes.push(GameChange::TimedOut { key: a.key });
+            }
+        }
+        changes
+    }
+
+    /// Whether any character is still waiting
+    pub fn waiting(&self) -> bool {
+        self.awaited.iter().any(|a| a.state == GameState::Waiting)
+    }
+
+    /// Call once every character that will be has been added
+    pub fn close(&mut self) {
+        self.closed = true;
+    }
+
+    /// Closed, and every character's game has started or timed out
+    pub fn finished(&self) -> bool {
+        self.closed && !self.waiting()
+    }
+
+    /// Keys of characters whose game has started, with their process ids
+    pub fn started(&self) -> Vec<(usize, u32)> {
+        self.awaited
+            .iter()
+            .filter(|a| matches!(a.state, GameState::Started { .. }))
+            .map(|a| (a.key, a.pid))
+            .collect()
+    }
+
+    /// Keys of characters that gave up waiting for their game window
+    pub fn timed_out(&self) -> Vec<usize> {
+        self.awaited
+            .iter()
+            .filter(|a| a.state == GameState::TimedOut)
+            .map(|a| a.key)
+            .collect()
+    }
+
+    /// `None` if the character with `key` was never added
+    pub fn state(&self, key: usize) -> Option<GameState> {
+        self.awaited.iter().find(|a| a.key == key).map(|a| a.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processes::{ProcessEntry, WindowEntry};
+
+    const TIMEOUT: Duration = Duration::from_secs(120);
+
+    fn table(processes: &[(u32, u32)], windows: &[(isize, u32, &str)]) -> ProcessTable {
+        ProcessTable {
+            processes: processes
+                .iter()
+                .map(|&(pid, parent)| ProcessEntry { pid, parent })
+                .collect(),
+            windows: windows
+                .iter()
+                .map(|&(id, pid, title)| WindowEntry {
+                    id,
+                    pid,
+                    title: title.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    fn secs(n: u64) -> Duration {
+        Duration::from_secs(n)
+    }
+
+    #[test]
+    fn reports_a_game_window_in_the_process_tree_once() {
+        let start = Instant::now();
+        let mut watch = GameWatch::new(TIMEOUT);
+        watch.add(0, 100, start);
+        // Windower (100) started pol.exe (200), which now shows FFXI's window
+        let t = table(
+            &[(100, 1), (200, 100)],
+            &[(7, 200, "PlayOnline Viewer - MyWarrior"), (8, 200, "FINAL FANTASY XI")],
+        );
+
+        assert_eq!(
+            watch.poll(&t, start + secs(40)),
+            [GameChange::Started {
+                key: 0,
+                window: 8,
+                after: secs(40)
+            }]
+        );
+        assert_eq!(watch.poll(&t, start + secs(41)), []);
+        assert_eq!(
+            watch.state(0),
+            Some(GameState::Started {
+                window: 8,
+                after: secs(40)
+            })
+        );
+        assert_eq!(watch.started(), [(0, 100)]);
+        assert_eq!(watch.state(1), None);
+    }
+
+    #[test]
+    fn ignores_game_windows_outside_the_tree() {
+        let start = Instant::now();
+        let mut watch = GameWatch::new(TIMEOUT);
+        watch.add(0, 100, start);
+        watch.add(1, 101, start);
+        // Another character's game, and one started by hand
+        let t = table(
+            &[(100, 1), (101, 1), (900, 1)],
+            &[(9, 900, "FINAL FANTASY XI"), (8, 101, "FINAL FANTASY XI")],
+        );
+
+        assert_eq!(
+            watch.poll(&t, start + secs(5)),
+            [GameChange::Started {
+                key: 1,
+                window: 8,
+                after: secs(5)
+            }]
+        );
+        assert_eq!(watch.state(0), Some(GameState::Waiting));
+    }
+
+    #[test]
+    fn times_out_from_the_end_of_each_login() {
+        let start = Instant::now();
+        let mut watch = GameWatch::new(TIMEOUT);
+        watch.add(0, 100, start);
+        watch.add(1, 101, start + secs(30));
+        let t = table(&[(100, 1), (101, 1)], &[]);
+
+        assert_eq!(watch.poll(&t, start + TIMEOUT - secs(1)), []);
+        assert_eq!(
+            watch.poll(&t, start + TIMEOUT),
+            [GameChange::TimedOut { key: 0 }]
+        );
+        assert_eq!(watch.timed_out(), [0]);
+        assert!(watch.waiting());
+        assert_eq!(
+            watch.poll(&t, start + TIMEOUT + secs(30)),
+            [GameChange::TimedOut { key: 1 }]
+        );
+        assert!(!watch.waiting());
+
+        // A late window no longer counts
+        let late = table(&[(100, 1)], &[(8, 100, "FINAL FANTASY XI")]);
+        assert_eq!(watch.poll(&late, start + TIMEOUT * 2), []);
+        assert_eq!(watch.state(0), Some(GameState::TimedOut));
+    }
+
+    #[test]
+    fn adding_a_key_again_restarts_its_wait() {
+        let start = Instant::now();
+        let mut watch = GameWatch::new(TIMEOUT);
+        watch.add(0, 100, start);
+        let t = table(&[(100, 1)], &[]);
+        watch.poll(&t, start + TIMEOUT);
+        assert_eq!(watch.timed_out(), [0]);
+
+        let relogged = start + TIMEOUT + secs(10);
+        watch.add(0, 100, relogged);
+        assert_eq!(watch.state(0), Some(GameState::Waiting));
+        assert!(watch.timed_out().is_empty());
+        let t = table(&[(100, 1)], &[(8, 100, "FINAL FANTASY XI")]);
+        assert_eq!(
+            watch.poll(&t, relogged + secs(3)),
+            [GameChange::Started {
+                key: 0,
+                window: 8,
+                after: secs(3)
+            }]
+        );
+    }
+
+    #[test]
+    fn finished_only_once_closed() {
+        let start = Instant::now();
+        let mut watch = GameWatch::new(TIMEOUT);
+        assert!(!watch.finished());
+        watch.add(0, 100, start);
+        watch.close();
+        assert!(!watch.finished());
+
+        let t = table(&[(100, 1)], &[(8, 100, "FINAL FANTASY XI")]);
+        watch.poll(&t, start + secs(1));
+        assert!(watch.finished());
+
+        let mut open = GameWatch::new(TIMEOUT);
+        open.add(0, 100, start);
+        open.poll(&t, start + secs(1));
+        assert!(!open.waiting());
+        // More logins may still be added
+        assert!(!open.finished());
+        open.close();
+        assert!(open.finished());
+    }
+}
@@ -1,7 +1,7 @@
 use std::fs;
 use std::io::Write;
 
-const HOSTS_PATH: &str = r"C:\Windows\System32\drivers\etc\hosts";
+pub const HOSTS_PATH: &str = r"C:\Windows\System32\drivers\etc\hosts";
 const MARKER: &str = "# login-rs";
 
 /// Add the hosts file entry for POL redirect
@@ -30,6 +30,11 @@ pub fn remove_entries() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Count login-rs entries in the given hosts file contents
+pub fn count_entries(contents: &str) -> usize {
+    contents.lines().filter(|line| line.contains(MARKER)).count()
+}
+
 /// Ensure cleanup happens even on unexpected exit.
 /// Call this at startup to remove any stale entries from a previous crash.
 pub fn cleanup_stale() {
@@ -0,0 +1,281 @@
+use crate::clock::SystemClock;
+use crate::focus::{self, SystemWindows};
+use crate::typing::InputEvent;
+use crate::win32;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Blocks the user's keyboard and mouse while login-rs sends keys. `SystemInput` asks
+/// Windows; `InputBlockGuard` takes the trait so it can run against a mock.
+pub trait InputBackend: Send + Sync {
+    fn block_input(&self, block: bool);
+}
+
+pub struct SystemInput;
+
+impl InputBackend for SystemInput {
+    fn block_input(&self, block: bool) {
+        win32::block_input(block);
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// How a job went, so a panic can be raised again on the thread that asked for it
+type JobResult = Result<(), Box<dyn Any + Send>>;
+
+/// Keeps user input blocked until dropped, so an early return or a panic can't leave
+/// the keyboard and mouse locked. If the guard is still alive after the maximum
+/// duration, input is unblocked anyway.
+///
+/// Windows only lets the thread that blocked input unblock it, and only lets that
+/// thread's simulated input through, so one thread blocks, sends every key (see `run`)
+/// and unblocks.
+pub struct InputBlockGuard {
+    /// Dropping this tells the blocking thread to unblock
+    jobs: Option<Sender<(Job, Sender<JobResult>)>>,
+    blocker: Option<JoinHandle<()>>,
+}
+
+impl InputBlockGuard {
+    /// Block input for at most `max`. Returns once input is blocked.
+    pub fn new(backend: Arc<dyn InputBackend>, max: Duration) -> Self {
+        let (jobs, queued) = mpsc::channel::<(Job, Sender<JobResult>)>();
+        let (blocked, is_blocked) = mpsc::channel::<()>();
+
+        let blocker = thread::spawn(move || {
+            backend.block_input(true);
+            let deadline = Instant::now() + max;
+            let mut blocking = true;
+            let _ = blocked.send(());
+            loop {
+                // The time limit is checked between jobs, which each send one batch of keys
+                let next = if blocking {
+                    queued.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                } else {
+                    queued.recv().map_err(|_| RecvTimeoutError::Disconnected)
+                };
+                match next {
+                    Ok((job, done)) => {
+                        let _ = done.send(panic::catch_unwind(AssertUnwindSafe(job)));
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        log::warn!(
+                            "Input was blocked for longer than {}s; unblocking it",
+                            max.as_secs()
+                        );
+                        backend.block_input(false);
+                        blocking = false;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            if blocking {
+                backend.block_input(false);
+            }
+        });
+        let _ = is_blocked.recv();
+
+        Self {
+            jobs: Some(jobs),
+            blocker: Some(blocker),
+        }
+    }
+
+    /// Run `job` on the thread that blocked input and wait for it to finish
+    pub fn run(&self, job: impl FnOnce() + Send + 'static) {
+        let (done, finished) = mpsc::channel();
+        let Some(jobs) = &self.jobs else {
+            return;
+        };
+        if jobs.send((Box::new(job), done)).is_err() {
+            return;
+        }
+        if let Ok(Err(payload)) = finished.recv() {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+impl Drop for InputBlockGuard {
+    fn drop(&mut self) {
+        drop(self.jobs.take());
+        if let Some(blocker) = self.blocker.take() {
+            let _ = blocker.join();
+        }
+    }
+}
+
+/// Delivers keys to one PlayOnline window, for the `input.backend` setting
+pub trait KeySender: Sync {
+    /// Check the window can receive the next batch of keys (`step` names it for errors)
+    fn ready(&self, step: &str) -> Result<(), String>;
+    fn press_key(&self, vk: u16, hold_ms: u64);
+    /// One mouse wheel notch up over the window
+    fn scroll_up(&self);
+    fn send(&self, events: &[InputEvent]);
+}
+
+/// `send_input`: simulated input to the foreground window, which must be PlayOnline.
+/// Keys are sent from the thread that blocked the user's input.
+pub struct SendInputKeys {
+    pub window: isize,
+    pub block: InputBlockGuard,
+}
+
+impl KeySender for SendInputKeys {
+    fn ready(&self, step: &str) -> Result<(), String> {
+        // Keys go to whatever is in front, so make sure that's PlayOnline
+        focus::ensure_focused(&SystemWindows, self.window, &SystemClock)
+            .map_err(|e| format!("Before {}: {}", step, e))
+    }
+
+    fn press_key(&self, vk: u16, hold_ms: u64) {
+        self.block.run(move || win32::press_key(vk, hold_ms));
+    }
+
+    fn scroll_up(&self) {
+        // Window handles can't cross threads; ids can
+        let window = self.window;
+        self.block.run(move || {
+            win32::move_cursor_to_window(win32::window_handle(window));
+            win32::mouse_scroll_up();
+        });
+    }
+
+    fn send(&self, events: &[InputEvent]) {
+        let events = events.to_vec();
+        self.block.run(move || win32::send_input(&events));
+    }
+}
+
+/// `post_message`: messages posted straight to the window, whether or not it has focus
+pub struct PostMessageKeys {
+    pub window: isize,
+}
+
+impl KeySender for PostMessageKeys {
+    fn ready(&self, _step: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn press_key(&self, vk: u16, hold_ms: u64) {
+        win32::post_key_press(win32::window_handle(self.window), vk, hold_ms);
+    }
+
+    fn scroll_up(&self) {
+        win32::post_scroll_up(win32::window_handle(self.window));
+    }
+
+    fn send(&self, events: &[InputEvent]) {
+        win32::post_input(win32::window_handle(self.window), events);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::thread::ThreadId;
+    use std::time::Instant;
+
+    /// Records every block and unblock, and the thread it came from
+    #[derive(Default)]
+    struct RecordingInput(Mutex<Vec<(bool, ThreadId)>>);
+
+    impl RecordingInput {
+        fn calls(&self) -> Vec<bool> {
+            self.0.lock().unwrap().iter().map(|&(block, _)| block).collect()
+        }
+    }
+
+    impl InputBackend for RecordingInput {
+        fn block_input(&self, block: bool) {
+            self.0.lock().unwrap().push((block, thread::current().id()));
+        }
+    }
+
+    const LONG: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn blocks_before_returning_and_unblocks_on_drop() {
+        let input = Arc::new(RecordingInput::default());
+        let guard = InputBlockGuard::new(input.clone(), LONG);
+        assert_eq!(input.calls(), [true]);
+        drop(guard);
+        assert_eq!(input.calls(), [true, false]);
+
+        // Windows only honours an unblock from the thread that blocked
+        let calls = input.0.lock().unwrap();
+        assert_eq!(calls[0].1, calls[1].1);
+    }
+
+    #[test]
+    fn jobs_run_on_the_thread_that_blocked_input() {
+        let input = Arc::new(RecordingInput::default());
+        let guard = InputBlockGuard::new(input.clone(), LONG);
+        let sent_from = Arc::new(Mutex::new(Vec::new()));
+        for _ in 0..2 {
+            let sent_from = Arc::clone(&sent_from);
+            guard.run(move || sent_from.lock().unwrap().push(thread::current().id()));
+        }
+        drop(guard);
+
+        let blocked_from = input.0.lock().unwrap()[0].1;
+        assert_ne!(blocked_from, thread::current().id());
+        assert_eq!(*sent_from.lock().unwrap(), [blocked_from, blocked_from]);
+        assert_eq!(input.calls(), [true, false]);
+    }
+
+    #[test]
+    fn a_panicking_job_unblocks_and_panics_the_caller() {
+        let input = Arc::new(RecordingInput::default());
+        let backend: Arc<dyn InputBackend> = input.clone();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+            let guard = InputBlockGuard::new(backend, LONG);
+            guard.run(|| panic!("SendInput failed"));
+        }));
+        assert!(result.is_err());
+        assert_eq!(input.calls(), [true, false]);
+    }
+
+    #[test]
+    fn unblocks_when_the_holder_panics() {
+        let input = Arc::new(RecordingInput::default());
+        let backend: Arc<dyn InputBackend> = input.clone();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+            let _guard = InputBlockGuard::new(backend, LONG);
+            panic!("login failed mid-sequence");
+        }));
+        assert!(result.is_err());
+        assert_eq!(input.calls(), [true, false]);
+    }
+
+    #[test]
+    fn unblocks_once_when_the_time_limit_passes() {
+        let input = Arc::new(RecordingInput::default());
+        let guard = InputBlockGuard::new(input.clone(), Duration::from_millis(20));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while input.calls().len() < 2 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(input.calls(), [true, false]);
+
+        // The guard outlived the limit; dropping it doesn't unblock again
+        drop(guard);
+        assert_eq!(input.calls(), [true, false]);
+    }
+
+    #[test]
+    fn each_guard_blocks_and_unblocks_once() {
+        let input = Arc::new(RecordingInput::default());
+        for _ in 0..3 {
+            drop(InputBlockGuard::new(input.clone(), LONG));
+        }
+        assert_eq!(input.calls(), [true, false, true, false, true, false]);
+    }
+}
@@ -1,65 +1,305 @@
-use crate::config::{Character, Config};
+use crate::clock::{Clock, SystemClock};
+use crate::config::{Character, Config, InputBackendKind};
+use crate::events::{CharacterSummary, Event, Output};
+use crate::game::{GameChange, GameState, GameWatch};
 use crate::hosts;
+use crate::input::{InputBlockGuard, KeySender, PostMessageKeys, SendInputKeys, SystemInput};
+use crate::layout::{self, Rect};
+use crate::lobby;
 use crate::logging::FileLogger;
+use crate::processes::{self, ProcessQuery, ProcessTable, SystemProcesses, WindowEntry, WindowMatch};
 use crate::proxy;
-use crate::win32;
+use crate::report::{CharacterOutcome, Instance, LoginResult, RunReport};
+use crate::sequence::{Progress, Sequence};
+use crate::titles::{self, POL_TITLE};
+use crate::typing::{self, InputEvent};
+use crate::win32::{self, RecordedKey};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-#[cfg(windows)]
-type WindowHandle = windows_sys::Win32::Foundation::HWND;
-#[cfg(not(windows))]
-type WindowHandle = isize;
+/// How long to keep re-applying titles after the last game window appears, since
+/// the game may set its own title again while it starts up
+const TITLE_SETTLE: Duration = Duration::from_secs(15);
+
+/// How often Phase 3 looks for game windows
+const GAME_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often the lobby worker checks for due steps
+const LOBBY_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Time PlayOnline gets to reach the proxy after Connect is pressed. Bounds how long
+/// a login can hold the proxy port and hosts entry the others are waiting for.
+const PROXY_TIMEOUT: Duration = Duration::from_secs(30);
 
 struct LaunchedCharacter<'a> {
     character: &'a Character,
-    hwnd: WindowHandle,
+    pid: u32,
+    /// The PlayOnline window, as a `win32::window_id`
+    window: isize,
+    time_to_window: Duration,
+}
+
+struct LoginOutcome {
+    result: LoginResult,
+    attempts: u32,
+    time_to_proxy: Option<Duration>,
+    time_to_game: Option<Duration>,
+}
+
+/// Launches and logs in a batch of characters. `WindowerLauncher` does it for real;
+/// long-running modes take the trait so they can be driven without Windows.
+pub trait Launcher {
+    /// Setting `cancel` stops the run before the next character is launched or logged in
+    fn login(&mut self, config: &Config, characters: &[&Character], cancel: &AtomicBool) -> RunReport;
+}
+
+/// What happens after a failed login attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryMode {
+    /// Ask on stdin whether to retry or skip the character
+    Ask,
+    /// Retry up to `login_retries` times without asking, for modes nobody is watching
+    Unattended,
+}
+
+/// Wait between unattended attempts, for PlayOnline to settle after a failed one
+const UNATTENDED_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Runs both phases, then reports the run and writes the report to the log directory
+pub struct WindowerLauncher<'a> {
+    pub logger: &'a FileLogger,
+    pub out: &'a Output,
+    pub retry: RetryMode,
+}
+
+impl Launcher for WindowerLauncher<'_> {
+    fn login(&mut self, config: &Config, characters: &[&Character], cancel: &AtomicBool) -> RunReport {
+        self.logger.clear_errors();
+        self.out.emit(Event::RunStarted {
+            version: env!("CARGO_PKG_VERSION"),
+            characters: characters
+                .iter()
+                .map(|ch| CharacterSummary {
+                    name: ch.name.clone(),
+                    slot: ch.slot,
+                })
+                .collect(),
+            log_dir: self.logger.log_dir().display().to_string(),
+        });
+
+        let outcomes = run(config, characters, self.logger, self.out, self.retry, cancel);
+        let report = RunReport::build(&outcomes, &self.logger.last_errors());
+
+        self.out.emit(Event::RunFinished { report: &report });
+        match report.write_to(self.logger.log_dir()) {
+            Ok(()) => self.out.emit(Event::ReportWritten {
+                path: self.logger.log_dir().join("report.md").display().to_string(),
+            }),
+            Err(e) => eprintln!("Failed to write run report: {}", e),
+        }
+        report
+    }
 }
 
-pub fn run(config: &Config, characters: &[&Character], logger: &FileLogger) {
+/// Records what it was asked to log in and reports every character as logged in,
+/// except those listed in `fail_launch` or `fail_login`. Each launched character gets
+/// a new pid, with the window id equal to it.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MockLauncher {
+    /// Names of the characters passed to each `login` call
+    pub runs: Vec<Vec<String>>,
+    pub fail_launch: Vec<String>,
+    pub fail_login: Vec<String>,
+    /// Pid given to the most recently launched character
+    pub last_pid: u32,
+}
+
+#[cfg(test)]
+impl Launcher for MockLauncher {
+    fn login(&mut self, _: &Config, characters: &[&Character], cancel: &AtomicBool) -> RunReport {
+        self.runs.push(characters.iter().map(|ch| ch.name.clone()).collect());
+        let mut outcomes = Vec::new();
+        for ch in characters {
+            let launched = !cancel.load(Ordering::SeqCst) && !self.fail_launch.contains(&ch.name);
+            let login = match launched {
+                false => LoginResult::NotAttempted,
+                true if self.fail_login.contains(&ch.name) => LoginResult::Failed,
+                true => LoginResult::Success,
+            };
+            let instance = launched.then(|| {
+                self.last_pid = self.last_pid.max(1000) + 1;
+                let pid = self.last_pid;
+                Instance { pid, window: pid as isize }
+            });
+            outcomes.push(CharacterOutcome {
+                name: ch.name.clone(),
+                slot: ch.slot,
+                launched,
+                login,
+                attempts: u32::from(launched),
+                time_to_window: None,
+                time_to_proxy: None,
+                time_to_game: None,
+                instance,
+            });
+        }
+        RunReport::build(&outcomes, &std::collections::HashMap::new())
+    }
+}
+
+/// Run both phases for the given characters and return one outcome per character,
+/// in the same order as `characters`. Characters not reached before `cancel` was set
+/// are reported as not launched or not attempted.
+pub fn run(
+    config: &Config,
+    characters: &[&Character],
+    logger: &FileLogger,
+    out: &Output,
+    retry: RetryMode,
+    cancel: &AtomicBool,
+) -> Vec<CharacterOutcome> {
     // Clean any stale hosts entries from a previous crash
     hosts::cleanup_stale();
 
     // Phase 1: Launch all Windower instances
-    println!("\n=== Phase 1: Launching Windower instances ===");
-    let launched = phase1_launch(config, characters, logger);
+    out.emit(Event::PhaseStarted {
+        phase: 1,
+        description: "Launching Windower instances",
+    });
+    let launched = phase1_launch(config, characters, logger, out, cancel);
 
+    if cancel.load(Ordering::SeqCst) {
+        out.emit(Event::RunCancelled);
+        return build_outcomes(characters, &launched, &[]);
+    }
     if launched.is_empty() {
-        eprintln!("No Windower instances launched successfully. Aborting.");
-        return;
+        out.emit(Event::NoInstancesLaunched);
+        return build_outcomes(characters, &launched, &[]);
     }
 
     // Brief pause to let all POL windows initialize
-    println!("Waiting for PlayOnline windows to initialize...");
+    out.emit(Event::WaitingForWindows);
     thread::sleep(Duration::from_secs(5));
 
-    // Phase 2: Automate POL login for each
-    println!("\n=== Phase 2: Automating PlayOnline login ===");
-    phase2_login(config, &launched, logger);
+    // Phase 2: Automate POL login for each. Phase 3 starts watching for each
+    // character's game window as soon as its login is done.
+    out.emit(Event::PhaseStarted {
+        phase: 2,
+        description: "Automating PlayOnline login",
+    });
+    let watch = Mutex::new(GameWatch::new(Duration::from_secs(
+        config.game_window_timeout_seconds,
+    )));
+    let (logins, lobby_failed) = thread::scope(|scope| {
+        let (lobby_jobs, lobby_queue) = mpsc::channel();
+        scope.spawn(|| phase3_watch(config, &launched, &watch, lobby_jobs, logger, out, cancel));
+        let lobby = scope.spawn(|| run_lobbies(config, &launched, lobby_queue, logger, out, cancel));
+        let mut logins = phase2_login(config, &launched, &watch, logger, out, retry, cancel);
 
-    println!("\n=== Done ===");
+        // Phase 3: Wait for the game windows still to come
+        if lock(&watch).waiting() && !cancel.load(Ordering::SeqCst) {
+            out.emit(Event::PhaseStarted {
+                phase: 3,
+                description: "Waiting for game windows",
+            });
+        }
+        // A game window that never appears is a failed attempt like any other
+        retry_missing_games(&mut logins, &watch, &SystemClock, cancel, |i, attempts| {
+            let lc = &launched[i];
+            if let Err(e) = playonline_waiting(config, lc, &SystemProcesses.snapshot()) {
+                let message = format!("Not logging in again: {}", e);
+                logger.log_error(&lc.character.name, "game", &message);
+                return None;
+            }
+            if !should_retry(config, &lc.character.name, attempts, retry, cancel, out) {
+                return None;
+            }
+            let outcome = login_character(config, lc, logger, out, retry, cancel, attempts);
+            if outcome.result == LoginResult::Success {
+                lock(&watch).add(i, lc.pid, Instant::now());
+            }
+            Some(outcome)
+        });
+        lock(&watch).close();
+        (logins, lobby.join().unwrap_or_default())
+    });
+    let logins = with_game_results(logins, &lock(&watch), &lobby_failed);
+
+    if cancel.load(Ordering::SeqCst) {
+        out.emit(Event::RunCancelled);
+    } else {
+        out.emit(Event::Done);
+    }
+    build_outcomes(characters, &launched, &logins)
+}
+
+/// Pair each requested character with its launch and login results.
+/// `logins` is parallel to `launched`, with `None` for logins never started.
+fn build_outcomes(
+    characters: &[&Character],
+    launched: &[LaunchedCharacter],
+    logins: &[Option<LoginOutcome>],
+) -> Vec<CharacterOutcome> {
+    characters
+        .iter()
+        .map(|character| {
+            let launch = launched
+                .iter()
+                .position(|lc| std::ptr::eq(lc.character, *character));
+            let login = launch.and_then(|i| logins.get(i)).and_then(Option::as_ref);
+            CharacterOutcome {
+                name: character.name.clone(),
+                slot: character.slot,
+                launched: launch.is_some(),
+                login: login.map_or(LoginResult::NotAttempted, |l| l.result),
+                attempts: login.map_or(0, |l| l.attempts),
+                time_to_window: launch.map(|i| launched[i].time_to_window),
+                time_to_proxy: login.and_then(|l| l.time_to_proxy),
+                time_to_game: login.and_then(|l| l.time_to_game),
+                instance: launch.map(|i| Instance {
+                    pid: launched[i].pid,
+                    window: launched[i].window,
+                }),
+            }
+        })
+        .collect()
 }
 
 fn phase1_launch<'a>(
     config: &Config,
     characters: &[&'a Character],
     logger: &FileLogger,
+    out: &Output,
+    cancel: &AtomicBool,
 ) -> Vec<LaunchedCharacter<'a>> {
     let mut launched = Vec::new();
 
     // Track existing POL windows so we can identify new ones
-    let existing_windows = win32::find_windows_by_title_prefix("PlayOnline Viewer");
+    let existing_windows = pol_windows();
 
     for (i, character) in characters.iter().enumerate() {
-        println!("  Launching Windower for {}...", character.name);
+        if cancel.load(Ordering::SeqCst) {
+            break;
+        }
+        out.emit(Event::Launching {
+            character: &character.name,
+        });
 
         match launch_with_retry(config, character, &existing_windows, &launched, logger) {
             Some(lc) => {
-                println!("  ✓ {} - window found", character.name);
+                out.emit(Event::WindowFound {
+                    character: &character.name,
+                });
                 launched.push(lc);
             }
             None => {
-                println!("  ✗ {} - failed after retry", character.name);
+                out.emit(Event::LaunchFailed {
+                    character: &character.name,
+                });
             }
         }
 
@@ -75,7 +315,7 @@ fn phase1_launch<'a>(
 fn launch_with_retry<'a>(
     config: &Config,
     character: &'a Character,
-    existing_windows: &[WindowHandle],
+    existing_windows: &[isize],
     already_launched: &[LaunchedCharacter],
     logger: &FileLogger,
 ) -> Option<LaunchedCharacter<'a>> {
@@ -96,203 +336,830 @@ fn launch_with_retry<'a>(
     None
 }
 
+/// PlayOnline windows open right now, as `win32::window_id`s
+fn pol_windows() -> Vec<isize> {
+    SystemProcesses
+        .snapshot()
+        .windows
+        .iter()
+        .filter(|w| w.title.starts_with(POL_TITLE))
+        .map(|w| w.id)
+        .collect()
+}
+
 fn launch_single<'a>(
     config: &Config,
     character: &'a Character,
-    existing_windows: &[WindowHandle],
+    existing_windows: &[isize],
     already_launched: &[LaunchedCharacter],
 ) -> Result<LaunchedCharacter<'a>, String> {
     // Launch Windower with -p flag to skip the profile picker
     let profile_arg = config.windower_profile.as_ref()
         .map(|p| format!("-p=\"{}\"", p));
-    let _pid = win32::launch_process(
+    let started = Instant::now();
+    let pid = win32::launch_process(
         &config.windower_path,
         profile_arg.as_deref(),
     )?;
 
-    // Poll for new PlayOnline window (up to 30 seconds)
-    let mut new_hwnd = None;
+    let claimed: Vec<isize> = existing_windows
+        .iter()
+        .copied()
+        .chain(already_launched.iter().map(|lc| lc.window))
+        .collect();
+
+    // Poll for the PlayOnline window Windower opens (up to 30 seconds)
+    let mut found = None;
     for _ in 0..60 {
         thread::sleep(Duration::from_millis(500));
 
-        let all_windows = win32::find_windows_by_title_prefix("PlayOnline Viewer");
-        for hwnd in &all_windows {
-            let is_existing = existing_windows.contains(hwnd);
-            let is_already_launched = already_launched.iter().any(|lc| lc.hwnd == *hwnd);
-            if !is_existing && !is_already_launched {
-                new_hwnd = Some(*hwnd);
-                break;
-            }
-        }
-        if new_hwnd.is_some() {
+        found = processes::find_launched_window(&SystemProcesses.snapshot(), pid, POL_TITLE, &claimed);
+        if found.is_some() {
             break;
         }
     }
 
-    let hwnd = new_hwnd.ok_or_else(|| {
-        format!("Timed out waiting for PlayOnline window for {}", character.name)
-    })?;
+    let window = match found {
+        Some(WindowMatch::ProcessTree(id)) => id,
+        Some(WindowMatch::TitleOnly(id)) => {
+            log::warn!(
+                "Lost track of Windower's process for {}; using the new PlayOnline window by title",
+                character.name
+            );
+            id
+        }
+        None => {
+            return Err(format!("Timed out waiting for PlayOnline window for {}", character.name));
+        }
+    };
 
     // Rename window for identification
-    let title = format!("PlayOnline Viewer - {}", character.name);
-    win32::set_window_title(hwnd, &title);
+    if !config.titles.playonline.is_empty() {
+        let title = titles::render(&config.titles.playonline, character);
+        win32::set_window_title(win32::window_handle(window), &title);
+    }
 
-    Ok(LaunchedCharacter { character, hwnd })
+    Ok(LaunchedCharacter {
+        character,
+        pid,
+        window,
+        time_to_window: started.elapsed(),
+    })
 }
 
-fn phase2_login(config: &Config, launched: &[LaunchedCharacter], logger: &FileLogger) {
-    let port = config.region.proxy_port();
+/// Log in each launched character, handing those that succeed to `watch`
+fn phase2_login(
+    config: &Config,
+    launched: &[LaunchedCharacter],
+    watch: &Mutex<GameWatch>,
+    logger: &FileLogger,
+    out: &Output,
+    retry: RetryMode,
+    cancel: &AtomicBool,
+) -> Vec<Option<LoginOutcome>> {
+    let login = |i: usize| {
+        let outcome = login_character(config, &launched[i], logger, out, retry, cancel, 0);
+        if outcome.result == LoginResult::Success {
+            lock(watch).add(i, launched[i].pid, Instant::now());
+        }
+        outcome
+    };
 
-    for (i, lc) in launched.iter().enumerate() {
-        println!("  Logging in {}...", lc.character.name);
+    let limit = config.input.parallel_logins();
+    if limit > 1 {
+        // Window messages don't need focus, so logins can overlap
+        out.emit(Event::ParallelLogins { limit });
+        return run_limited(launched.len(), limit, cancel, login);
+    }
 
-        match login_with_retry(config, lc, port, logger) {
-            Ok(()) => println!("  ✓ {} - login automation complete", lc.character.name),
-            Err(()) => println!("  ✗ {} - login failed after retry", lc.character.name),
+    let mut outcomes = Vec::new();
+    for i in 0..launched.len() {
+        if cancel.load(Ordering::SeqCst) {
+            break;
         }
+        outcomes.push(Some(login(i)));
 
         // Stagger delay before next character
         if i < launched.len() - 1 {
-            println!(
-                "  Waiting {} seconds before next login...",
-                config.stagger_delay_seconds
-            );
+            out.emit(Event::StaggerWait {
+                seconds: config.stagger_delay_seconds,
+            });
             thread::sleep(Duration::from_secs(config.stagger_delay_seconds));
         }
     }
+    outcomes
+}
+
+/// Log in again characters whose game window timed out, for as long as `relogin` (given
+/// the key and attempts so far) agrees, returning `None` once the retry policy says
+/// stop. `relogin` hands successful logins back to `watch`. Returns once no game is
+/// still awaited and every timed out one has given up.
+fn retry_missing_games(
+    logins: &mut [Option<LoginOutcome>],
+    watch: &Mutex<GameWatch>,
+    clock: &dyn Clock,
+    cancel: &AtomicBool,
+    mut relogin: impl FnMut(usize, u32) -> Option<LoginOutcome>,
+) {
+    let mut given_up = Vec::new();
+    while !cancel.load(Ordering::SeqCst) {
+        let (timed_out, waiting) = {
+            let watch = lock(watch);
+            (watch.timed_out(), watch.waiting())
+        };
+        let retry: Vec<usize> = timed_out
+            .into_iter()
+            .filter(|key| !given_up.contains(key))
+            .collect();
+        if retry.is_empty() {
+            if !waiting {
+                break;
+            }
+            clock.sleep(GAME_POLL_INTERVAL);
+            continue;
+        }
+        for key in retry {
+            let Some(login) = logins.get_mut(key).and_then(Option::as_mut) else {
+                given_up.push(key);
+                continue;
+            };
+            match relogin(key, login.attempts) {
+                Some(again) => {
+                    if again.result != LoginResult::Success {
+                        given_up.push(key);
+                    }
+                    *login = again;
+                }
+                None => given_up.push(key),
+            }
+        }
+    }
+}
+
+/// Check `lc`'s PlayOnline window is still open under a PlayOnline title before its login
+/// is sent again. Its game never appeared, so PlayOnline may have closed, leaving nothing
+/// for the keys to go to, or the window may be showing something other than PlayOnline.
+fn playonline_waiting(
+    config: &Config,
+    lc: &LaunchedCharacter,
+    table: &ProcessTable,
+) -> Result<(), String> {
+    let window = table
+        .windows_of(lc.pid)
+        .into_iter()
+        .find(|w| w.id == lc.window)
+        .ok_or("the PlayOnline window has closed")?;
+    let renamed = &config.titles.playonline;
+    if window.title.starts_with(POL_TITLE)
+        || (!renamed.is_empty() && window.title == titles::render(renamed, lc.character))
+    {
+        Ok(())
+    } else {
+        Err(format!("the PlayOnline window is now titled '{}'", window.title))
+    }
+}
+
+/// Run `job` for `0..count` on up to `limit` threads, starting jobs in order.
+/// Jobs not started before `cancel` is set are left as `None`.
+fn run_limited<R: Send>(
+    count: usize,
+    limit: usize,
+    cancel: &AtomicBool,
+    job: impl Fn(usize) -> R + Sync,
+) -> Vec<Option<R>> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..count).map(|_| None).collect::<Vec<_>>());
+    thread::scope(|scope| {
+        for _ in 0..limit.min(count) {
+            scope.spawn(|| loop {
+                if cancel.load(Ordering::SeqCst) {
+                    break;
+                }
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                if i >= count {
+                    break;
+                }
+                let result = job(i);
+                lock(&results)[i] = Some(result);
+            });
+        }
+    });
+    results.into_inner().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Lock a mutex, carrying on if another thread panicked while holding it
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Log `lc` in, counting on from `attempts` already made (e.g. one whose game window
+/// never appeared)
+fn login_character(
+    config: &Config,
+    lc: &LaunchedCharacter,
+    logger: &FileLogger,
+    out: &Output,
+    retry: RetryMode,
+    cancel: &AtomicBool,
+    attempts: u32,
+) -> LoginOutcome {
+    let character = lc.character.name.as_str();
+    out.emit(Event::LoggingIn { character });
+
+    let outcome = login_with_retry(config, lc, logger, out, retry, cancel, attempts);
+    match outcome.result {
+        LoginResult::Success => out.emit(Event::LoginComplete { character }),
+        _ => out.emit(Event::LoginFailed { character }),
+    }
+    outcome
 }
 
+/// Held while a login uses the proxy port and hosts entry, which all logins share
+static CONNECTING: Mutex<()> = Mutex::new(());
+
+/// Held while simulated input is sent, since it goes to whichever window is in front
+static SENDING_KEYS: Mutex<()> = Mutex::new(());
+
+/// Held while asking the user whether to retry, so parallel logins ask one at a time
+static PROMPTING: Mutex<()> = Mutex::new(());
+
 fn login_with_retry(
     config: &Config,
     lc: &LaunchedCharacter,
-    port: u16,
     logger: &FileLogger,
-) -> Result<(), ()> {
+    out: &Output,
+    retry: RetryMode,
+    cancel: &AtomicBool,
+    mut attempts: u32,
+) -> LoginOutcome {
+    let failed = |attempts| LoginOutcome {
+        result: LoginResult::Failed,
+        attempts,
+        time_to_proxy: None,
+        time_to_game: None,
+    };
+
     loop {
-        match login_single(config, lc, port) {
-            Ok(()) => return Ok(()),
+        attempts += 1;
+        match login_single(config, lc, out) {
+            Ok(time_to_proxy) => {
+                return LoginOutcome {
+                    result: LoginResult::Success,
+                    attempts,
+                    time_to_proxy: Some(time_to_proxy),
+                    time_to_game: None,
+                }
+            }
             Err(e) => {
                 logger.log_error(&lc.character.name, "login", &e);
-                // Always cleanup on failure
-                win32::block_input(false);
-                let _ = hosts::remove_entries();
-
-                println!("  ✗ {} login failed: {}", lc.character.name, e);
-                println!("  Press Enter to retry, or type 'skip' to skip this character:");
-
-                let mut input = String::new();
-                if std::io::stdin().read_line(&mut input).is_err() {
-                    return Err(());
+                // Always cleanup on failure, once no other login is connecting
+                {
+                    let _connecting = lock(&CONNECTING);
+                    let _ = hosts::remove_entries();
                 }
-                if input.trim().eq_ignore_ascii_case("skip") {
-                    return Err(());
+
+                let character = lc.character.name.as_str();
+                out.emit(Event::LoginAttemptFailed {
+                    character,
+                    attempt: attempts,
+                    error: &e,
+                });
+                if !should_retry(config, character, attempts, retry, cancel, out) {
+                    return failed(attempts);
                 }
             }
         }
     }
 }
 
+/// Whether to make another login attempt after `attempts` failed ones
+fn should_retry(
+    config: &Config,
+    character: &str,
+    attempts: u32,
+    retry: RetryMode,
+    cancel: &AtomicBool,
+    out: &Output,
+) -> bool {
+    if cancel.load(Ordering::SeqCst) {
+        return false;
+    }
+    match retry {
+        RetryMode::Ask => {
+            let _prompting = lock(&PROMPTING);
+            out.emit(Event::RetryPrompt { character });
+
+            // Treat a closed stdin (e.g. when scripted) as "skip"
+            let mut input = String::new();
+            matches!(std::io::stdin().read_line(&mut input), Ok(n) if n > 0)
+                && !input.trim().eq_ignore_ascii_case("skip")
+        }
+        RetryMode::Unattended => {
+            if attempts > config.login_retries {
+                return false;
+            }
+            out.emit(Event::LoginRetrying {
+                character,
+                attempt: attempts + 1,
+                max_attempts: config.login_retries + 1,
+            });
+            thread::sleep(UNATTENDED_RETRY_DELAY);
+            !cancel.load(Ordering::SeqCst)
+        }
+    }
+}
+
+/// Run the POL login key sequence once. On success, returns how long it took
+/// for POL to hit the proxy.
 fn login_single(
     config: &Config,
     lc: &LaunchedCharacter,
-    port: u16,
-) -> Result<(), String> {
-    // Start proxy server
-    let proxy_handle = proxy::start_proxy(port)
-        .map_err(|e| format!("Failed to start proxy: {}", e))?;
+    out: &Output,
+) -> Result<Duration, String> {
+    let started = Instant::now();
+
+    // Work out the password keystrokes first, so an untypeable password fails
+    // before anything is sent to PlayOnline
+    let password_input = typing::translate(&lc.character.password, &config.input)
+        .map_err(|e| format!("Password for {}: {}", lc.character.name, e))?;
+
+    // Simulated input goes to the foreground window, so it needs the user's own
+    // input blocked until the key sequence is done; window messages don't
+    let (keys, sending): (Box<dyn KeySender>, _) = match config.input.backend {
+        InputBackendKind::SendInput => {
+            // Wait for any game lobby being sent keys (see `send_lobby_step`)
+            let sending = lock(&SENDING_KEYS);
+            let keys = SendInputKeys {
+                window: lc.window,
+                block: block_input(config),
+            };
+            (Box::new(keys), Some(sending))
+        }
+        InputBackendKind::PostMessage => (Box::new(PostMessageKeys { window: lc.window }), None),
+    };
+
+    send_login_keys(config, &*keys, lc.character, &password_input, &SystemClock, out)?;
 
-    // Add hosts entry
-    hosts::add_entry(config.region.hosts_entry())
-        .map_err(|e| format!("Failed to add hosts entry: {}", e))?;
+    // One login at a time from here: they all use the same proxy port and hosts entry.
+    // That is why the proxy and hosts entry are set up only now rather than before the
+    // first key: held for the whole sequence, they would let just one login run at a
+    // time, even with `post_message`. PlayOnline only contacts the server once Connect
+    // is pressed, which `connect` does after both are in place.
+    let _connecting = lock(&CONNECTING);
+    let proxy = connect(&*keys, &SystemRedirect { config }, &SystemClock)?;
 
-    // Block user input
-    win32::block_input(true);
+    // Dropping the keys unblocks input
+    drop(keys);
+    drop(sending);
 
-    // Focus the POL window, position cursor in neutral zone, then scroll
-    // up to guarantee slot 1 is selected before navigating down.
-    println!("    {}: targeting slot {}", lc.character.name, lc.character.slot);
-    win32::focus_window(lc.hwnd);
-    win32::move_cursor_to_window(lc.hwnd);
-    thread::sleep(Duration::from_millis(500));
+    // Wait for proxy to serve its response, at most `PROXY_TIMEOUT`
+    let served_at = proxy.wait();
+
+    // Remove hosts entry
+    hosts::remove_entries()
+        .map_err(|e| format!("Failed to remove hosts entry: {}", e))?;
+
+    let served_at = served_at.ok_or_else(|| {
+        format!(
+            "PlayOnline never reached the proxy within {}s",
+            PROXY_TIMEOUT.as_secs()
+        )
+    })?;
+    Ok(served_at.duration_since(started))
+}
+
+/// Points PlayOnline at a local proxy for the connect step. `SystemRedirect` starts the
+/// real proxy and edits the hosts file; `connect` takes the trait so it can run against
+/// a mock.
+trait Redirect {
+    /// Stops the proxy when dropped
+    type Proxy;
+    fn start_proxy(&self) -> Result<Self::Proxy, String>;
+    fn add_hosts_entry(&self) -> Result<(), String>;
+}
+
+struct SystemRedirect<'a> {
+    config: &'a Config,
+}
+
+impl Redirect for SystemRedirect<'_> {
+    type Proxy = proxy::Proxy;
+
+    fn start_proxy(&self) -> Result<proxy::Proxy, String> {
+        proxy::start_proxy(self.config.region.proxy_port(), PROXY_TIMEOUT)
+            .map_err(|e| format!("Failed to start proxy: {}", e))
+    }
+
+    fn add_hosts_entry(&self) -> Result<(), String> {
+        // A failed login removes it again (see `login_with_retry`)
+        hosts::add_entry(self.config.region.hosts_entry())
+            .map_err(|e| format!("Failed to add hosts entry: {}", e))
+    }
+}
+
+/// Send the PlayOnline key sequence from the character list up to submitting the
+/// password. Each batch of keys is sent right after `keys.ready` checks the window can
+/// take it, so keys (above all the password) never go to a window that popped up over
+/// PlayOnline in the meantime.
+fn send_login_keys(
+    config: &Config,
+    keys: &dyn KeySender,
+    character: &Character,
+    password: &[InputEvent],
+    clock: &dyn Clock,
+    out: &Output,
+) -> Result<(), String> {
+    // Position the cursor in the neutral zone, then scroll up to guarantee
+    // slot 1 is selected before navigating down.
+    let name = character.name.as_str();
+    out.emit(Event::TargetingSlot {
+        character: name,
+        slot: character.slot,
+    });
+    clock.sleep(Duration::from_millis(500));
 
     // Scroll mouse wheel up to reset to slot 1
-    println!("    Resetting slot position with mouse wheel up");
+    keys.ready("resetting the slot")?;
+    out.emit(Event::ResettingSlot { character: name });
     for _ in 0..20 {
-        win32::mouse_scroll_up();
+        keys.scroll_up();
     }
-    thread::sleep(Duration::from_millis(300));
+    clock.sleep(Duration::from_millis(300));
 
     // Navigate to target slot: press DOWN `slot` times (first DOWN activates slot 1)
-    let down_presses = lc.character.slot;
-    println!("    Moving down {} time(s) to reach slot {}", down_presses, lc.character.slot);
+    let down_presses = character.slot;
+    out.emit(Event::NavigatingToSlot {
+        character: name,
+        presses: down_presses,
+        slot: character.slot,
+    });
+    keys.ready("navigating to the slot")?;
     for i in 0..down_presses {
-        println!("    DOWN press {}/{}", i + 1, down_presses);
-        win32::press_key(0x28, 200);
+        out.emit(Event::SlotKeyPress {
+            character: name,
+            press: i + 1,
+            total: down_presses,
+        });
+        keys.press_key(0x28, 200);
     }
 
     // Select the slot
-    println!("    ENTER to select slot");
-    win32::press_key(0x0D, 300);
-    thread::sleep(Duration::from_millis(1500));
+    out.emit(Event::SelectingSlot { character: name });
+    keys.press_key(0x0D, 300);
+    clock.sleep(Duration::from_millis(1500));
 
     // Step 2: First confirmation screen
+    keys.ready("confirmation screen 1")?;
     log::debug!("ENTER (confirmation screen 1)");
-    win32::press_key(0x0D, 300);
-    thread::sleep(Duration::from_millis(1500));
+    keys.press_key(0x0D, 300);
+    clock.sleep(Duration::from_millis(1500));
 
     // Step 3: Second confirmation screen
+    keys.ready("confirmation screen 2")?;
     log::debug!("ENTER (confirmation screen 2)");
-    win32::press_key(0x0D, 300);
-    thread::sleep(Duration::from_millis(1500));
+    keys.press_key(0x0D, 300);
+    clock.sleep(Duration::from_millis(1500));
 
     // Step 4: Third confirmation screen
+    keys.ready("confirmation screen 3")?;
     log::debug!("ENTER (confirmation screen 3)");
-    win32::press_key(0x0D, 300);
-    thread::sleep(Duration::from_millis(1500));
+    keys.press_key(0x0D, 300);
+    clock.sleep(Duration::from_millis(1500));
 
     // Step 5: Navigate to password input field (UP, RIGHT, RIGHT, ENTER)
+    keys.ready("navigating to the password field")?;
     log::debug!("UP, RIGHT, RIGHT, ENTER (navigate to password field)");
-    win32::press_key(0x26, 150);
-    win32::press_key(0x27, 150);
-    win32::press_key(0x27, 150);
-    win32::press_key(0x0D, 150);
-    thread::sleep(Duration::from_millis(500));
+    keys.press_key(0x26, 150);
+    keys.press_key(0x27, 150);
+    keys.press_key(0x27, 150);
+    keys.press_key(0x0D, 150);
+    clock.sleep(Duration::from_millis(500));
 
     // Step 6: Type password
-    log::debug!("typing password ({} chars)", lc.character.password.len());
-    win32::type_text(&lc.character.password);
-    thread::sleep(Duration::from_millis(300));
+    keys.ready("typing the password")?;
+    log::debug!(
+        "typing password ({} chars, {} strategy)",
+        character.password.chars().count(),
+        typing::strategy_name(config.input.typing_strategy())
+    );
+    keys.send(password);
+    clock.sleep(Duration::from_millis(300));
 
     // Step 7: Submit password
+    keys.ready("submitting the password")?;
     log::debug!("ENTER (submit password)");
-    win32::press_key(0x0D, 150);
-    thread::sleep(Duration::from_millis(500));
+    keys.press_key(0x0D, 150);
+    clock.sleep(Duration::from_millis(500));
+    Ok(())
+}
+
+/// Redirect PlayOnline to the proxy, then navigate to Connect and press it (step 8).
+/// Returns the proxy, which stops if dropped.
+fn connect<R: Redirect>(
+    keys: &dyn KeySender,
+    redirect: &R,
+    clock: &dyn Clock,
+) -> Result<R::Proxy, String> {
+    let proxy = redirect.start_proxy()?;
+    redirect.add_hosts_entry()?;
 
-    // Step 8: Navigate to Connect and press it
+    keys.ready("connecting")?;
     log::debug!("DOWN, ENTER (connect)");
-    win32::press_key(0x28, 150);
-    win32::press_key(0x0D, 150);
-    thread::sleep(Duration::from_millis(500));
+    keys.press_key(0x28, 150);
+    keys.press_key(0x0D, 150);
+    clock.sleep(Duration::from_millis(500));
+    Ok(proxy)
+}
 
-    // Unblock user input
-    win32::block_input(false);
+/// Phase 3: watch for each logged-in character's game window until all have appeared
+/// or timed out, and Phase 2 is over. Windows are renamed and moved into place as they
+/// appear, and titles are re-applied until they have had time to settle. Characters
+/// with an `ffxi_character_index` are passed on to `run_lobbies`.
+fn phase3_watch(
+    config: &Config,
+    launched: &[LaunchedCharacter],
+    watch: &Mutex<GameWatch>,
+    lobby_jobs: Sender<LobbyJob>,
+    logger: &FileLogger,
+    out: &Output,
+    cancel: &AtomicBool,
+) {
+    let rects = plan_layout(config);
+    let rect_for = |lc: &LaunchedCharacter| {
+        config
+            .characters
+            .iter()
+            .position(|c| c.name == lc.character.name)
+            .and_then(|i| rects[i])
+    };
 
-    // Wait for proxy to serve its response
-    let _ = proxy_handle.join();
+    let mut last_found = None;
+    while !cancel.load(Ordering::SeqCst) {
+        let table = SystemProcesses.snapshot();
+        let now = SystemClock.now();
+        let changes = lock(watch).poll(&table, now);
+        for change in changes {
+            match change {
+                GameChange::Started { key, window, after } => {
+                    let lc = &launched[key];
+                    if let Some(rect) = rect_for(lc) {
+                        log::debug!("Moving {}'s game window to {:?}", lc.character.name, rect);
+                        win32::move_window(win32::window_handle(window), rect);
+                    }
+                    out.emit(Event::GameWindowFound {
+                        character: &lc.character.name,
+                        seconds: after.as_secs(),
+                    });
+                    last_found = Some(now);
+                    if lc.character.ffxi_character_index.is_some() {
+                        let _ = lobby_jobs.send(LobbyJob {
+                            key,
+                            window,
+                            appeared_at: now,
+                        });
+                    }
+                }
+                GameChange::TimedOut { key } => {
+                    let character = &launched[key].character.name;
+                    logger.log_error(
+                        character,
+                        "game",
+                        &format!(
+                            "Game window did not appear within {}s of logging in",
+                            config.game_window_timeout_seconds
+                        ),
+                    );
+                    out.emit(Event::GameWindowNotFound {
+                        character,
+                        seconds: config.game_window_timeout_seconds,
+                    });
+                }
+            }
+        }
 
-    // Remove hosts entry
-    hosts::remove_entries()
-        .map_err(|e| format!("Failed to remove hosts entry: {}", e))?;
+        let started = lock(watch).started();
+        for (key, pid) in started {
+            retitle(config, launched[key].character, &table.windows_of(pid));
+        }
 
-    Ok(())
+        let settling = last_found.is_some_and(|t| now.duration_since(t) < TITLE_SETTLE);
+        if lock(watch).finished() && !settling {
+            break;
+        }
+        SystemClock.sleep(GAME_POLL_INTERVAL);
+    }
+}
+
+/// A game window that appeared for a character with an `ffxi_character_index`
+struct LobbyJob {
+    /// Index into `launched`
+    key: usize,
+    window: isize,
+    appeared_at: Instant,
+}
+
+/// A character being sent the lobby steps that select its FFXI character
+struct LobbyRun {
+    key: usize,
+    window: isize,
+    sequence: Sequence,
+}
+
+/// Send each queued character the lobby steps from its game's opening to confirming
+/// its FFXI character. Characters progress side by side, but only one is sent keys at
+/// a time. Returns the keys of characters that got stuck on the way.
+fn run_lobbies(
+    config: &Config,
+    launched: &[LaunchedCharacter],
+    queue: Receiver<LobbyJob>,
+    logger: &FileLogger,
+    out: &Output,
+    cancel: &AtomicBool,
+) -> Vec<usize> {
+    let timeout = Duration::from_secs(config.lobby.step_timeout_seconds);
+    let mut runs: Vec<LobbyRun> = Vec::new();
+    let mut failed = Vec::new();
+    let mut queue_open = true;
+    while (queue_open || !runs.is_empty()) && !cancel.load(Ordering::SeqCst) {
+        loop {
+            match queue.try_recv() {
+                Ok(job) => {
+                    let character = launched[job.key].character;
+                    let Some(index) = character.ffxi_character_index else {
+                        continue;
+                    };
+                    out.emit(Event::SelectingCharacter {
+                        character: &character.name,
+                        index,
+                    });
+                    runs.push(LobbyRun {
+                        key: job.key,
+                        window: job.window,
+                        sequence: Sequence::new(
+                            lobby::select_character(&config.lobby, index),
+                            timeout,
+                            job.appeared_at,
+                        ),
+                    });
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    queue_open = false;
+                    break;
+                }
+            }
+        }
+
+        for run in &mut runs {
+            if run.sequence.due().is_none_or(|due| Instant::now() < due) {
+                continue;
+            }
+            let character = &launched[run.key].character.name;
+            match send_lobby_step(config, run) {
+                Progress::Waiting => {}
+                Progress::Sent(step) => {
+                    out.emit(Event::LobbyStep { character, step });
+                    if run.sequence.finished() {
+                        out.emit(Event::LobbyKeysSent { character });
+                    }
+                }
+                Progress::Failed { step, error } => {
+                    logger.log_error(character, "lobby", &error);
+                    out.emit(Event::LobbyFailed {
+                        character,
+                        step,
+                        error: &error,
+                    });
+                    failed.push(run.key);
+                }
+            }
+        }
+        runs.retain(|run| !run.sequence.finished());
+        SystemClock.sleep(LOBBY_POLL_INTERVAL);
+    }
+    failed
+}
+
+/// Send `run`'s next step to its game window, if it is due
+fn send_lobby_step(config: &Config, run: &mut LobbyRun) -> Progress {
+    // FFXI reads the keyboard through DirectInput rather than window messages, so this
+    // always uses simulated input, and blocks the user's, whatever `input.backend` is
+    let _sending = lock(&SENDING_KEYS);
+    let keys = SendInputKeys {
+        window: run.window,
+        block: block_input(config),
+    };
+    run.sequence.poll(&keys, &SystemClock)
+}
+
+/// Add Phase 3's results to the logins: time to game for characters whose game
+/// started, and failure for those whose game window never appeared or whose FFXI
+/// character couldn't be selected (`lobby_failed`)
+fn with_game_results(
+    logins: Vec<Option<LoginOutcome>>,
+    watch: &GameWatch,
+    lobby_failed: &[usize],
+) -> Vec<Option<LoginOutcome>> {
+    logins
+        .into_iter()
+        .enumerate()
+        .map(|(i, login)| {
+            login.map(|mut l| {
+                match watch.state(i) {
+                    Some(GameState::Started { after, .. }) => l.time_to_game = Some(after),
+                    Some(GameState::TimedOut) => l.result = LoginResult::Failed,
+                    _ => {}
+                }
+                if lobby_failed.contains(&i) {
+                    l.result = LoginResult::Failed;
+                }
+                l
+            })
+        })
+        .collect()
+}
+
+/// Where `layout` puts each character's game window, parallel to `config.characters`.
+/// Planned against every character, so each keeps its place whoever is launched.
+fn plan_layout(config: &Config) -> Vec<Option<Rect>> {
+    let Some(layout) = &config.layout else {
+        return vec![None; config.characters.len()];
+    };
+    win32::enable_dpi_awareness();
+    let mut monitors = win32::list_monitors();
+    layout::order_monitors(&mut monitors);
+    let names: Vec<&str> = config.characters.iter().map(|c| c.name.as_str()).collect();
+    layout::plan(layout, &names, &monitors)
+}
+
+/// Give `character`'s windows their `titles`, if the game named (or renamed) them
+pub fn retitle(config: &Config, character: &Character, windows: &[&WindowEntry]) {
+    for window in windows {
+        if let Some(title) = titles::wanted_title(&config.titles, character, window) {
+            log::debug!("Renaming '{}' to '{}'", window.title, title);
+            win32::set_window_title(win32::window_handle(window.id), &title);
+        }
+    }
+}
+
+/// Block the user's keyboard and mouse for at most `input.max_block_seconds`
+fn block_input(config: &Config) -> InputBlockGuard {
+    InputBlockGuard::new(
+        Arc::new(SystemInput),
+        Duration::from_secs(config.input.max_block_seconds),
+    )
 }
 
-pub fn run_record_mode(config: &Config, characters: &[&Character], logger: &FileLogger) {
-    let character = characters[0];
+pub fn run_record_mode(config: &Config, character: &Character, logger: &FileLogger) {
     println!("\n=== Record Mode ===");
+
+    if let Some(lc) = launch_for_debugging(config, character, logger, "record_launch") {
+        println!();
+        println!("=== Recording keypresses (Ctrl+C to stop) ===");
+        println!("Manually perform the login in PlayOnline.");
+        println!();
+        println!("{:<6} {:<20} {:<6} Delay", "#", "Key", "Dir");
+        println!("{}", "-".repeat(80));
+
+        // Streams events to stdout until Ctrl+C
+        // Mouse positions are logged relative to the POL window
+        win32::record_keys_stream(win32::window_handle(lc.window));
+    }
+}
+
+/// Launch one character and replay a recording made with `run_record_mode`
+pub fn run_replay_mode(
+    config: &Config,
+    character: &Character,
+    keys: &[RecordedKey],
+    logger: &FileLogger,
+) {
+    println!("\n=== Replay Mode ===");
+
+    if let Some(_lc) = launch_for_debugging(config, character, logger, "replay_launch") {
+        println!("Replaying {} key event(s)...", keys.len());
+        let block = block_input(config);
+        for key in keys {
+            // Sent from the thread that blocked input, one key at a time so the time
+            // limit still applies
+            let key = key.clone();
+            block.run(move || win32::replay_keys(&[key]));
+        }
+        drop(block);
+        println!("Replay complete");
+    }
+}
+
+/// Launch a single character and focus its POL window once it has initialized
+fn launch_for_debugging<'a>(
+    config: &Config,
+    character: &'a Character,
+    logger: &FileLogger,
+    step: &str,
+) -> Option<LaunchedCharacter<'a>> {
     println!("Launching Windower for {}...", character.name);
 
-    let existing_windows = win32::find_windows_by_title_prefix("PlayOnline Viewer");
+    let existing_windows = pol_windows();
 
     match launch_single(config, character, &existing_windows, &[]) {
         Ok(lc) => {
@@ -300,23 +1167,433 @@ pub fn run_record_mode(config: &Config, characters: &[&Character], logger: &File
             println!("Waiting for PlayOnline to initialize...");
             thread::sleep(Duration::from_secs(5));
 
-            win32::focus_window(lc.hwnd);
+            win32::focus_window(win32::window_handle(lc.window));
             thread::sleep(Duration::from_millis(500));
-
-            println!();
-            println!("=== Recording keypresses (Ctrl+C to stop) ===");
-            println!("Manually perform the login in PlayOnline.");
-            println!();
-            println!("{:<6} {:<20} {:<6} Delay", "#", "Key", "Dir");
-            println!("{}", "-".repeat(80));
-
-            // Streams events to stdout until Ctrl+C
-            // Mouse positions are logged relative to the POL window
-            win32::record_keys_stream(lc.hwnd);
+            Some(lc)
         }
         Err(e) => {
-            logger.log_error(&character.name, "record_launch", &e);
+            logger.log_error(&character.name, step, &e);
             eprintln!("Failed to launch: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::config::ConfigFormat;
+    use crate::events::OutputFormat;
+    use crate::processes::{ProcessEntry, ProcessTable};
+
+    fn config(json: &str) -> Config {
+        Config::parse(json, ConfigFormat::Json).unwrap()
+    }
+
+    const CONFIG: &str = r#"{
+        "version": 2,
+        "login_retries": 1,
+        "characters": [{"name": "MyWarrior", "slot": 1, "password": "Passw0rd!"}]
+    }"#;
+
+    /// Records what the login steps send, and when, failing `ready` at `fail_at`
+    struct RecordingKeys<'a> {
+        clock: &'a FakeClock,
+        fail_at: Option<&'static str>,
+        sent: Mutex<Vec<(String, Duration)>>,
+    }
+
+    impl<'a> RecordingKeys<'a> {
+        fn new(clock: &'a FakeClock, fail_at: Option<&'static str>) -> Self {
+            Self {
+                clock,
+                fail_at,
+                sent: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn record(&self, what: String) {
+            self.sent.lock().unwrap().push((what, self.clock.elapsed()));
+        }
+
+        fn sent(&self) -> Vec<String> {
+            self.sent.lock().unwrap().iter().map(|(what, _)| what.clone()).collect()
+        }
+    }
+
+    impl KeySender for RecordingKeys<'_> {
+        fn ready(&self, step: &str) -> Result<(), String> {
+            self.record(format!("ready {}", step));
+            match self.fail_at {
+                Some(failing) if failing == step => Err(format!("Before {}: covered", step)),
+                _ => Ok(()),
+            }
+        }
+
+        fn press_key(&self, vk: u16, _hold_ms: u64) {
+            self.record(format!("key {:#04X}", vk));
+        }
+
+        fn scroll_up(&self) {
+            self.record("scroll".to_string());
+        }
+
+        fn send(&self, events: &[InputEvent]) {
+            self.record(format!("send {}", events.len()));
+        }
+    }
+
+    /// Records the redirect alongside the keys, failing to start the proxy if `busy`
+    struct RecordingRedirect<'a> {
+        keys: &'a RecordingKeys<'a>,
+        busy: bool,
+    }
+
+    impl Redirect for RecordingRedirect<'_> {
+        type Proxy = ();
+
+        fn start_proxy(&self) -> Result<(), String> {
+            if self.busy {
+                return Err("Failed to start proxy: port in use".to_string());
+            }
+            self.keys.record("proxy started".to_string());
+            Ok(())
+        }
+
+        fn add_hosts_entry(&self) -> Result<(), String> {
+            self.keys.record("hosts entry added".to_string());
+            Ok(())
+        }
+    }
+
+    fn password() -> Vec<InputEvent> {
+        vec![
+            InputEvent::Unicode { unit: 0x50, up: false },
+            InputEvent::Unicode { unit: 0x50, up: true },
+        ]
+    }
+
+    #[test]
+    fn login_keys_follow_a_ready_check_at_once() {
+        let config = config(CONFIG);
+        let clock = FakeClock::new(chrono::Utc::now());
+        let keys = RecordingKeys::new(&clock, None);
+        let out = Output::new(OutputFormat::Json);
+        send_login_keys(&config, &keys, &config.characters[0], &password(), &clock, &out).unwrap();
+
+        let sent = keys.sent.lock().unwrap();
+        let readies: Vec<&str> = sent
+            .iter()
+            .filter_map(|(what, _)| what.strip_prefix("ready "))
+            .collect();
+        assert_eq!(
+            readies,
+            [
+                "resetting the slot",
+                "navigating to the slot",
+                "confirmation screen 1",
+                "confirmation screen 2",
+                "confirmation screen 3",
+                "navigating to the password field",
+                "typing the password",
+                "submitting the password",
+            ]
+        );
+        // No time passes between a check and the keys it clears
+        for pair in sent.windows(2) {
+            if pair[0].0.starts_with("ready ") {
+                assert!(!pair[1].0.starts_with("ready "), "{:?}", pair);
+                assert_eq!(pair[0].1, pair[1].1, "{:?}", pair);
+            }
+        }
+        // Nothing is sent before the first check
+        assert!(sent[0].0.starts_with("ready "));
+    }
+
+    #[test]
+    fn playonline_is_redirected_only_after_the_password_and_before_connect() {
+        let config = config(CONFIG);
+        let clock = FakeClock::new(chrono::Utc::now());
+        let keys = RecordingKeys::new(&clock, None);
+        let out = Output::new(OutputFormat::Json);
+        send_login_keys(&config, &keys, &config.characters[0], &password(), &clock, &out).unwrap();
+        let redirect = RecordingRedirect { keys: &keys, busy: false };
+        connect(&keys, &redirect, &clock).unwrap();
+
+        let sent = keys.sent();
+        let tail: Vec<&str> = sent[sent.len() - 7..].iter().map(String::as_str).collect();
+        assert_eq!(
+            tail,
+            [
+                "ready submitting the password",
+                "key 0x0D",
+                "proxy started",
+                "hosts entry added",
+                "ready connecting",
+                "key 0x28",
+                "key 0x0D",
+            ]
+        );
+    }
+
+    #[test]
+    fn connect_is_not_pressed_without_the_proxy() {
+        let clock = FakeClock::new(chrono::Utc::now());
+        let keys = RecordingKeys::new(&clock, None);
+        let redirect = RecordingRedirect { keys: &keys, busy: true };
+        assert_eq!(
+            connect(&keys, &redirect, &clock),
+            Err("Failed to start proxy: port in use".to_string())
+        );
+        assert!(keys.sent().is_empty());
+    }
+
+    #[test]
+    fn the_password_is_never_sent_to_a_covered_window() {
+        let config = config(CONFIG);
+        let clock = FakeClock::new(chrono::Utc::now());
+        let keys = RecordingKeys::new(&clock, Some("typing the password"));
+        let out = Output::new(OutputFormat::Json);
+        let character = &config.characters[0];
+        let result = send_login_keys(&config, &keys, character, &password(), &clock, &out);
+
+        assert_eq!(result, Err("Before typing the password: covered".to_string()));
+        let sent = keys.sent();
+        assert_eq!(sent.last().map(String::as_str), Some("ready typing the password"));
+        assert!(sent.iter().all(|what| !what.starts_with("send ")));
+    }
+
+    #[test]
+    fn cancelled_runs_never_retry_or_prompt() {
+        let config = config(CONFIG);
+        let out = Output::new(OutputFormat::Json);
+        let cancel = AtomicBool::new(true);
+        // `Ask` would block on stdin if it got as far as prompting
+        for mode in [RetryMode::Ask, RetryMode::Unattended] {
+            assert!(!should_retry(&config, "MyWarrior", 1, mode, &cancel, &out));
         }
     }
+
+    #[test]
+    fn unattended_retries_stop_at_login_retries() {
+        let retry_once = config(CONFIG);
+        let out = Output::new(OutputFormat::Json);
+        let cancel = AtomicBool::new(false);
+        assert!(!should_retry(&retry_once, "MyWarrior", 2, RetryMode::Unattended, &cancel, &out));
+
+        let defaults = config(r#"{"version": 2, "characters": []}"#);
+        assert_eq!(defaults.login_retries, 2);
+    }
+
+    #[test]
+    fn run_limited_returns_results_in_job_order() {
+        let cancel = AtomicBool::new(false);
+        let results = run_limited(10, 3, &cancel, |i| {
+            // Later jobs finish first
+            thread::sleep(Duration::from_millis(10 - i as u64));
+            i * 10
+        });
+        assert_eq!(results, (0..10).map(|i| Some(i * 10)).collect::<Vec<_>>());
+        assert!(run_limited(0, 3, &cancel, |i| i).is_empty());
+        assert_eq!(run_limited(2, 8, &cancel, |i| i), [Some(0), Some(1)]);
+    }
+
+    #[test]
+    fn run_limited_never_runs_more_than_the_limit_at_once() {
+        let cancel = AtomicBool::new(false);
+        let running = AtomicUsize::new(0);
+        let most = AtomicUsize::new(0);
+        run_limited(12, 3, &cancel, |_| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            most.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(5));
+            running.fetch_sub(1, Ordering::SeqCst);
+        });
+        assert_eq!(most.load(Ordering::SeqCst), 3);
+
+        run_limited(5, 1, &cancel, |_| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            assert_eq!(now, 1);
+            running.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+
+    #[test]
+    fn run_limited_starts_nothing_after_cancel() {
+        let cancel = AtomicBool::new(false);
+        let results = run_limited(5, 1, &cancel, |i| {
+            if i == 2 {
+                cancel.store(true, Ordering::SeqCst);
+            }
+            i
+        });
+        // The job that cancelled still finishes
+        assert_eq!(results, [Some(0), Some(1), Some(2), None, None]);
+
+        let results = run_limited(3, 2, &cancel, |i| i);
+        assert_eq!(results, [None, None, None]);
+    }
+
+    fn outcome(result: LoginResult, attempts: u32) -> LoginOutcome {
+        LoginOutcome {
+            result,
+            attempts,
+            time_to_proxy: None,
+            time_to_game: None,
+        }
+    }
+
+    /// A watch where the character with key 0 (pid 100) timed out waiting for its game
+    fn timed_out_watch(clock: &FakeClock) -> Mutex<GameWatch> {
+        let mut watch = GameWatch::new(Duration::from_secs(120));
+        watch.add(0, 100, clock.now());
+        clock.advance(Duration::from_secs(120));
+        watch.poll(&ProcessTable::default(), clock.now());
+        assert_eq!(watch.timed_out(), [0]);
+        Mutex::new(watch)
+    }
+
+    fn game_started(watch: &Mutex<GameWatch>, clock: &FakeClock) {
+        let table = ProcessTable {
+            processes: vec![ProcessEntry { pid: 100, parent: 1 }],
+            windows: vec![WindowEntry {
+                id: 8,
+                pid: 100,
+                title: "FINAL FANTASY XI".to_string(),
+            }],
+        };
+        lock(watch).poll(&table, clock.now());
+    }
+
+    #[test]
+    fn a_missing_game_window_logs_in_again() {
+        let clock = FakeClock::new(chrono::Utc::now());
+        let watch = timed_out_watch(&clock);
+        let mut logins = vec![Some(outcome(LoginResult::Success, 1))];
+        let mut calls = Vec::new();
+        retry_missing_games(&mut logins, &watch, &clock, &AtomicBool::new(false), |key, attempts| {
+            calls.push((key, attempts));
+            lock(&watch).add(key, 100, clock.now());
+            game_started(&watch, &clock);
+            Some(outcome(LoginResult::Success, attempts + 1))
+        });
+
+        assert_eq!(calls, [(0, 1)]);
+        let login = logins[0].as_ref().unwrap();
+        assert_eq!((login.result, login.attempts), (LoginResult::Success, 2));
+        let logins = with_game_results(logins, &lock(&watch), &[]);
+        assert_eq!(logins[0].as_ref().unwrap().result, LoginResult::Success);
+    }
+
+    #[test]
+    fn a_missing_game_window_fails_once_the_policy_stops_retrying() {
+        let clock = FakeClock::new(chrono::Utc::now());
+        let watch = timed_out_watch(&clock);
+        let mut logins = vec![Some(outcome(LoginResult::Success, 1))];
+        let mut calls = 0;
+        retry_missing_games(&mut logins, &watch, &clock, &AtomicBool::new(false), |_, _| {
+            calls += 1;
+            None
+        });
+
+        assert_eq!(calls, 1);
+        let logins = with_game_results(logins, &lock(&watch), &[]);
+        let login = logins[0].as_ref().unwrap();
+        assert_eq!((login.result, login.attempts), (LoginResult::Failed, 1));
+    }
+
+    #[test]
+    fn a_failed_relogin_is_not_retried_again() {
+        let clock = FakeClock::new(chrono::Utc::now());
+        let watch = timed_out_watch(&clock);
+        let mut logins = vec![Some(outcome(LoginResult::Success, 1))];
+        let mut calls = 0;
+        retry_missing_games(&mut logins, &watch, &clock, &AtomicBool::new(false), |_, attempts| {
+            calls += 1;
+            Some(outcome(LoginResult::Failed, attempts + 3))
+        });
+
+        assert_eq!(calls, 1);
+        let login = logins[0].as_ref().unwrap();
+        assert_eq!((login.result, login.attempts), (LoginResult::Failed, 4));
+    }
+
+    #[test]
+    fn a_cancelled_run_does_not_log_in_again() {
+        let clock = FakeClock::new(chrono::Utc::now());
+        let watch = timed_out_watch(&clock);
+        let mut logins = vec![Some(outcome(LoginResult::Success, 1))];
+        retry_missing_games(&mut logins, &watch, &clock, &AtomicBool::new(true), |_, _| {
+            panic!("cancelled runs don't retry")
+        });
+        let logins = with_game_results(logins, &lock(&watch), &[]);
+        assert_eq!(logins[0].as_ref().unwrap().result, LoginResult::Failed);
+    }
+
+    #[test]
+    fn only_an_open_playonline_window_is_logged_in_again() {
+        let config = config(
+            r#"{
+                "version": 2,
+                "titles": { "playonline": "POL - {name}" },
+                "characters": [{"name": "MyWarrior", "slot": 1, "password": "Passw0rd!"}]
+            }"#,
+        );
+        let lc = LaunchedCharacter {
+            character: &config.characters[0],
+            pid: 100,
+            window: 7,
+            time_to_window: Duration::ZERO,
+        };
+        let table = |title: &str| ProcessTable {
+            processes: vec![ProcessEntry { pid: 100, parent: 1 }],
+            windows: vec![WindowEntry {
+                id: 7,
+                pid: 100,
+                title: title.to_string(),
+            }],
+        };
+
+        assert_eq!(playonline_waiting(&config, &lc, &table("PlayOnline Viewer")), Ok(()));
+        assert_eq!(playonline_waiting(&config, &lc, &table("POL - MyWarrior")), Ok(()));
+        assert_eq!(
+            playonline_waiting(&config, &lc, &table("Error")),
+            Err("the PlayOnline window is now titled 'Error'".to_string())
+        );
+        assert_eq!(
+            playonline_waiting(&config, &lc, &ProcessTable::default()),
+            Err("the PlayOnline window has closed".to_string())
+        );
+    }
+
+    #[test]
+    fn game_results_add_time_to_game_and_failures() {
+        let clock = FakeClock::new(chrono::Utc::now());
+        let watch = timed_out_watch(&clock);
+        lock(&watch).add(1, 100, clock.now());
+        clock.advance(Duration::from_secs(9));
+        game_started(&watch, &clock);
+        lock(&watch).add(2, 100, clock.now());
+
+        let logins = (0..4).map(|_| Some(outcome(LoginResult::Success, 1))).collect();
+        let logins = with_game_results(logins, &lock(&watch), &[1]);
+        let results: Vec<_> = logins
+            .iter()
+            .map(|l| {
+                let l = l.as_ref().unwrap();
+                (l.result, l.time_to_game)
+            })
+            .collect();
+        assert_eq!(
+            results,
+            [
+                (LoginResult::Failed, None),
+                // Its game started, but the lobby got stuck
+                (LoginResult::Failed, Some(Duration::from_secs(9))),
+                (LoginResult::Success, None),
+                (LoginResult::Success, None),
+            ]
+        );
+    }
 }
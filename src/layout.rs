@@ -0,0 +1,369 @@
+use crate::config::{LayoutConfig, LayoutMode, WindowRect};
+
+/// A rectangle in physical screen pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// A display, as `win32::list_monitors` reports it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Monitor {
+    pub bounds: Rect,
+    /// `bounds` without the taskbar and docked toolbars
+    pub work_area: Rect,
+    /// Display scaling, e.g. 1.5 at 150% (144 DPI)
+    pub scale: f64,
+    pub primary: bool,
+}
+
+/// Put monitors in the order `layout.monitor` counts them: the primary first,
+/// then the rest left to right, top to bottom
+pub fn order_monitors(monitors: &mut [Monitor]) {
+    monitors.sort_by_key(|m| (!m.primary, m.bounds.x, m.bounds.y));
+}
+
+/// Where each character's game window goes. `names` are every character in config
+/// order, so a character keeps its place whichever of them are launched; `monitors`
+/// are in `order_monitors` order. Returns one entry per name, `None` for windows
+/// the layout leaves where they are.
+pub fn plan(layout: &LayoutConfig, names: &[&str], monitors: &[Monitor]) -> Vec<Option<Rect>> {
+    let Some(target) = pick_monitor(monitors, layout.monitor) else {
+        return vec![None; names.len()];
+    };
+
+    let mut rects = vec![None; names.len()];
+    let mut arranged = Vec::new();
+    for (i, name) in names.iter().enumerate() {
+        match explicit_rect(layout, name) {
+            Some(rect) => {
+                let monitor = pick_monitor(monitors, rect.monitor.unwrap_or(layout.monitor))
+                    .unwrap_or(target);
+                rects[i] = Some(place(rect, monitor));
+            }
+            None => arranged.push(i),
+        }
+    }
+
+    let cells = match layout.mode {
+        LayoutMode::Grid => grid(layout, arranged.len(), target),
+        LayoutMode::Cascade => cascade(layout, arranged.len(), target),
+        LayoutMode::Manual => Vec::new(),
+    };
+    for (i, cell) in arranged.into_iter().zip(cells) {
+        rects[i] = Some(cell);
+    }
+    rects
+}
+
+/// Monitor `index`, falling back to the primary if there is no such monitor
+fn pick_monitor(monitors: &[Monitor], index: usize) -> Option<&Monitor> {
+    if index >= monitors.len() && !monitors.is_empty() {
+        log::warn!(
+            "Layout monitor {} does not exist ({} connected); using the primary monitor",
+            index,
+            monitors.len()
+        );
+    }
+    monitors.get(index).or_else(|| monitors.first())
+}
+
+fn explicit_rect<'a>(layout: &'a LayoutConfig, name: &str) -> Option<&'a WindowRect> {
+    layout
+        .windows
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, rect)| rect)
+}
+
+/// Unscaled pixels to the monitor's physical pixels
+fn scaled(value: i64, monitor: &Monitor) -> i32 {
+    (value as f64 * monitor.scale).round() as i32
+}
+
+/// A `windows` entry, relative to its monitor's work area
+fn place(rect: &WindowRect, monitor: &Monitor) -> Rect {
+    Rect {
+        x: monitor.work_area.x + scaled(rect.x.into(), monitor),
+        y: monitor.work_area.y + scaled(rect.y.into(), monitor),
+        width: scaled(rect.width.into(), monitor),
+        height: scaled(rect.height.into(), monitor),
+    }
+}
+
+/// `count` equal cells filling the work area, row by row
+fn grid(layout: &LayoutConfig, count: usize, monitor: &Monitor) -> Vec<Rect> {
+    if count == 0 {
+        return Vec::new();
+    }
+    // As square as possible unless the config says otherwise
+    let columns = layout
+        .columns
+        .map(|c| c as usize)
+        .unwrap_or_else(|| (count as f64).sqrt().ceil() as usize)
+        .clamp(1, count);
+    let rows = count.div_ceil(columns);
+
+    let area = monitor.work_area;
+    let gap = scaled(layout.gap.into(), monitor);
+    let width = (area.width - gap * (columns as i32 - 1)) / columns as i32;
+    let height = (area.height - gap * (rows as i32 - 1)) / rows as i32;
+    (0..count)
+        .map(|i| Rect {
+            x: area.x + (i % columns) as i32 * (width + gap),
+            y: area.y + (i / columns) as i32 * (height + gap),
+            width,
+            height,
+        })
+        .collect()
+}
+
+/// `count` overlapping windows, each shifted down and right from the last. Starts
+/// again from the top left once the next window would run off the work area.
+fn cascade(layout: &LayoutConfig, count: usize, monitor: &Monitor) -> Vec<Rect> {
+    if count == 0 {
+        return Vec::new();
+    }
+    let area = monitor.work_area;
+    let offset = scaled(layout.cascade_offset.into(), monitor);
+    let steps = offset * (count as i32 - 1);
+    // By default, as large as lets every window fit, but never under half the area
+    let width = layout
+        .width
+        .map(|w| scaled(w.into(), monitor))
+        .unwrap_or((area.width - steps).max(area.width / 2))
+        .min(area.width);
+    let height = layout
+        .height
+        .map(|h| scaled(h.into(), monitor))
+        .unwrap_or((area.height - steps).max(area.height / 2))
+        .min(area.height);
+
+    let fits = if offset == 0 {
+        count
+    } else {
+        (((area.width - width).min(area.height - height) / offset) as usize + 1).max(1)
+    };
+    (0..count)
+        .map(|i| {
+            let shift = (i % fits) as i32 * offset;
+            Rect {
+                x: area.x + shift,
+                y: area.y + shift,
+                width,
+                height,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rect(x: i32, y: i32, width: i32, height: i32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// 1080p primary with a 40px taskbar
+    const PRIMARY: Monitor = Monitor {
+        bounds: Rect {
+            x: 0,
+            y: 0,
+            width: 1920,
+            height: 1080,
+        },
+        work_area: Rect {
+            x: 0,
+            y: 0,
+            width: 1920,
+            height: 1040,
+        },
+        scale: 1.0,
+        primary: true,
+    };
+
+    /// 1440p at 150%, to the right of the primary
+    const RIGHT: Monitor = Monitor {
+        bounds: Rect {
+            x: 1920,
+            y: 0,
+            width: 2560,
+            height: 1440,
+        },
+        work_area: Rect {
+            x: 1920,
+            y: 0,
+            width: 2560,
+            height: 1440,
+        },
+        scale: 1.5,
+        primary: false,
+    };
+
+    /// To the left of the primary, so at negative coordinates
+    const LEFT: Monitor = Monitor {
+        bounds: Rect {
+            x: -1280,
+            y: 0,
+            width: 1280,
+            height: 1024,
+        },
+        work_area: Rect {
+            x: -1280,
+            y: 0,
+            width: 1280,
+            height: 1024,
+        },
+        scale: 1.0,
+        primary: false,
+    };
+
+    /// Monitors in `order_monitors` order: primary, left, right
+    const MONITORS: [Monitor; 3] = [PRIMARY, LEFT, RIGHT];
+
+    fn layout(value: serde_json::Value) -> LayoutConfig {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn plan_all(layout: &LayoutConfig, names: &[&str]) -> Vec<Rect> {
+        plan(layout, names, &MONITORS)
+            .into_iter()
+            .map(Option::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn orders_the_primary_first_then_left_to_right() {
+        let mut monitors = [RIGHT, LEFT, PRIMARY];
+        order_monitors(&mut monitors);
+        assert_eq!(monitors, MONITORS);
+    }
+
+    #[test]
+    fn grid_is_as_square_as_possible_and_avoids_the_taskbar() {
+        let grid = layout(json!({}));
+        assert_eq!(
+            plan_all(&grid, &["A", "B", "C", "D"]),
+            [
+                rect(0, 0, 960, 520),
+                rect(960, 0, 960, 520),
+                rect(0, 520, 960, 520),
+                rect(960, 520, 960, 520),
+            ]
+        );
+        // Five need three columns; the last row isn't full
+        let five = plan_all(&grid, &["A", "B", "C", "D", "E"]);
+        assert_eq!(five[2], rect(1280, 0, 640, 520));
+        assert_eq!(five[4], rect(640, 520, 640, 520));
+        assert_eq!(plan_all(&grid, &["A"]), [rect(0, 0, 1920, 1040)]);
+    }
+
+    #[test]
+    fn grid_columns_and_gap() {
+        let grid = layout(json!({ "columns": 3, "gap": 10 }));
+        assert_eq!(
+            plan_all(&grid, &["A", "B", "C", "D"]),
+            [
+                rect(0, 0, 633, 515),
+                rect(643, 0, 633, 515),
+                rect(1286, 0, 633, 515),
+                rect(0, 525, 633, 515),
+            ]
+        );
+        // More columns than windows
+        let wide = layout(json!({ "columns": 8 }));
+        assert_eq!(plan_all(&wide, &["A", "B"])[1], rect(960, 0, 960, 1040));
+    }
+
+    #[test]
+    fn grid_gap_is_scaled_with_the_monitor() {
+        let grid = layout(json!({ "monitor": 2, "gap": 10 }));
+        assert_eq!(
+            plan_all(&grid, &["A", "B"]),
+            [rect(1920, 0, 1272, 1440), rect(3207, 0, 1272, 1440)]
+        );
+    }
+
+    #[test]
+    fn cascade_fills_the_area_by_default() {
+        let cascade = layout(json!({ "mode": "cascade", "cascade_offset": 30 }));
+        assert_eq!(
+            plan_all(&cascade, &["A", "B", "C"]),
+            [
+                rect(0, 0, 1860, 980),
+                rect(30, 30, 1860, 980),
+                rect(60, 60, 1860, 980),
+            ]
+        );
+    }
+
+    #[test]
+    fn cascade_wraps_before_running_off_the_work_area() {
+        let cascade = layout(json!({
+            "mode": "cascade", "cascade_offset": 100, "width": 1600, "height": 900
+        }));
+        let shifts: Vec<_> = plan_all(&cascade, &["A", "B", "C", "D", "E"])
+            .iter()
+            .map(|r| (r.x, r.y))
+            .collect();
+        assert_eq!(shifts, [(0, 0), (100, 100), (0, 0), (100, 100), (0, 0)]);
+
+        // Never larger than the work area, never under half of it
+        let big = layout(json!({ "mode": "cascade", "width": 5000, "height": 5000 }));
+        assert_eq!(plan_all(&big, &["A", "B"]), [rect(0, 0, 1920, 1040); 2]);
+        let many: Vec<String> = (0..40).map(|i| i.to_string()).collect();
+        let names: Vec<&str> = many.iter().map(String::as_str).collect();
+        let crowded = plan_all(&layout(json!({ "mode": "cascade" })), &names);
+        assert_eq!((crowded[0].width, crowded[0].height), (960, 520));
+    }
+
+    #[test]
+    fn explicit_rects_are_placed_on_their_own_monitor_and_scaled() {
+        let grid = layout(json!({
+            "windows": { "mymage": { "x": 100, "y": 50, "width": 800, "height": 600, "monitor": 2 } }
+        }));
+        assert_eq!(
+            plan_all(&grid, &["MyWarrior", "MyMage", "MyThief"]),
+            [
+                rect(0, 0, 960, 1040),
+                rect(2070, 75, 1200, 900),
+                rect(960, 0, 960, 1040),
+            ]
+        );
+    }
+
+    #[test]
+    fn manual_moves_only_explicit_rects() {
+        let manual = layout(json!({
+            "mode": "manual",
+            "windows": { "MyMage": { "x": -10, "y": 0, "width": 640, "height": 480, "monitor": 1 } }
+        }));
+        assert_eq!(
+            plan(&manual, &["MyWarrior", "MyMage"], &MONITORS),
+            [None, Some(rect(-1290, 0, 640, 480))]
+        );
+    }
+
+    #[test]
+    fn missing_monitors_fall_back_to_the_primary() {
+        let grid = layout(json!({
+            "monitor": 5,
+            "windows": { "MyMage": { "x": 0, "y": 0, "width": 640, "height": 480, "monitor": 9 } }
+        }));
+        assert_eq!(
+            plan_all(&grid, &["MyWarrior", "MyMage"]),
+            [rect(0, 0, 1920, 1040), rect(0, 0, 640, 480)]
+        );
+        assert_eq!(plan(&grid, &["MyWarrior", "MyMage"], &[]), [None, None]);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::config::LobbyConfig;
+use crate::sequence::{press, Step};
+use crate::typing::InputEvent;
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+/// Positions in an FFXI account's character list
+pub const FFXI_CHARACTERS: RangeInclusive<u8> = 1..=16;
+
+/// FFXI reads the keyboard through DirectInput, which goes by scan codes
+const SCAN_ENTER: u16 = 0x1C;
+const SCAN_ESCAPE: u16 = 0x01;
+/// Extended key: `0xE0` prefix, then the Down arrow's code
+const SCAN_DOWN: u16 = 0xE050;
+
+/// Long enough for FFXI's input polling to see each press
+const KEY_HOLD_MS: u64 = 100;
+
+/// Gap between presses that move the cursor in a list
+const KEY_GAP_MS: u64 = 250;
+
+/// Time from the game window appearing to the opening movie taking keys
+const OPENING_DELAY: Duration = Duration::from_secs(5);
+
+/// Time for the title screen to come up once the opening movie is skipped
+const TITLE_AFTER_OPENING: Duration = Duration::from_secs(3);
+
+/// Time for a lobby menu to open after a key
+const MENU_DELAY: Duration = Duration::from_millis(2000);
+
+/// Steps from the game window appearing to confirming the FFXI character at `index`
+/// (1-based) in the account's character list: optionally skip the opening
+/// movie, choose "Select Character" on the title screen (highlighted when it opens),
+/// move down the list, which opens on the first character, and confirm.
+pub fn select_character(lobby: &LobbyConfig, index: u8) -> Vec<Step> {
+    let mut steps = Vec::new();
+    let title_delay = if lobby.skip_opening {
+        steps.push(Step {
+            name: "the opening movie",
+            delay: OPENING_DELAY,
+            events: press(SCAN_ESCAPE, KEY_HOLD_MS),
+        });
+        TITLE_AFTER_OPENING
+    } else {
+        Duration::from_secs(lobby.title_screen_delay_seconds)
+    };
+
+    let mut to_character = Vec::new();
+    for _ in 1..index {
+        to_character.extend(press(SCAN_DOWN, KEY_HOLD_MS));
+        to_character.push(InputEvent::Pause(KEY_GAP_MS));
+    }
+    to_character.extend(press(SCAN_ENTER, KEY_HOLD_MS));
+
+    steps.extend([
+        Step {
+            name: "the title screen",
+            delay: title_delay,
+            events: press(SCAN_ENTER, KEY_HOLD_MS),
+        },
+        Step {
+            name: "the character list",
+            delay: MENU_DELAY,
+            events: to_character,
+        },
+        // "Play with this character?" opens on Yes
+        Step {
+            name: "the confirmation prompt",
+            delay: MENU_DELAY,
+            events: press(SCAN_ENTER, KEY_HOLD_MS),
+        },
+    ]);
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lobby(skip_opening: bool) -> LobbyConfig {
+        LobbyConfig {
+            skip_opening,
+            title_screen_delay_seconds: 30,
+            ..LobbyConfig::default()
+        }
+    }
+
+    fn outline(steps: &[Step]) -> Vec<(&'static str, Duration)> {
+        steps.iter().map(|s| (s.name, s.delay)).collect()
+    }
+
+    /// Scan codes pressed, in order
+    fn presses(events: &[InputEvent]) -> Vec<u16> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                InputEvent::Scan { scan, up: false } => Some(*scan),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn skips_the_opening_then_picks_the_first_character() {
+        let steps = select_character(&lobby(true), 1);
+        assert_eq!(
+            outline(&steps),
+            [
+                ("the opening movie", OPENING_DELAY),
+                ("the title screen", TITLE_AFTER_OPENING),
+                ("the character list", MENU_DELAY),
+                ("the confirmation prompt", MENU_DELAY),
+            ]
+        );
+        let keys: Vec<_> = steps.iter().map(|s| presses(&s.events)).collect();
+        assert_eq!(
+            keys,
+            [vec![SCAN_ESCAPE], vec![SCAN_ENTER], vec![SCAN_ENTER], vec![SCAN_ENTER]]
+        );
+        // Every key is released after being held
+        assert_eq!(
+            steps[0].events,
+            [
+                InputEvent::Scan {
+                    scan: SCAN_ESCAPE,
+                    up: false
+                },
+                InputEvent::Pause(KEY_HOLD_MS),
+                InputEvent::Scan {
+                    scan: SCAN_ESCAPE,
+                    up: true
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn moves_down_to_the_last_character() {
+        let steps = select_character(&lobby(true), *FFXI_CHARACTERS.end());
+        let list = &steps[2].events;
+        let mut expected = vec![SCAN_DOWN; 15];
+        expected.push(SCAN_ENTER);
+        assert_eq!(presses(list), expected);
+        // A gap after each Down, so the cursor keeps up
+        let gaps = list
+            .iter()
+            .filter(|e| **e == InputEvent::Pause(KEY_GAP_MS))
+            .count();
+        assert_eq!(gaps, 15);
+        assert_eq!(list.len(), 15 * 4 + 3);
+    }
+
+    #[test]
+    fn waits_out_the_opening_when_not_skipping_it() {
+        let steps = select_character(&lobby(false), 3);
+        assert_eq!(
+            outline(&steps),
+            [
+                ("the title screen", Duration::from_secs(30)),
+                ("the character list", MENU_DELAY),
+                ("the confirmation prompt", MENU_DELAY),
+            ]
+        );
+        assert_eq!(presses(&steps[1].events), [SCAN_DOWN, SCAN_DOWN, SCAN_ENTER]);
+    }
+}
@@ -1,9 +1,12 @@
+use std::collections::HashMap;
 use std::fs;
 use std::io::Write;
 use std::path::PathBuf;
+use std::sync::Mutex;
 
 pub struct FileLogger {
     log_dir: PathBuf,
+    last_errors: Mutex<HashMap<String, String>>,
 }
 
 impl FileLogger {
@@ -17,7 +20,10 @@ impl FileLogger {
         fs::create_dir_all(&log_dir)?;
 
         log::info!("Log directory: {}", log_dir.display());
-        Ok(Self { log_dir })
+        Ok(Self {
+            log_dir,
+            last_errors: Mutex::new(HashMap::new()),
+        })
     }
 
     pub fn log_error(&self, character_name: &str, step: &str, error: &str) {
@@ -35,9 +41,28 @@ impl FileLogger {
             let _ = file.write_all(entry.as_bytes());
         }
 
+        if let Ok(mut errors) = self.last_errors.lock() {
+            errors.insert(character_name.to_string(), format!("{}: {}", step, error));
+        }
+
         log::error!("[{}] {}: {}", character_name, step, error);
     }
 
+    /// Forget errors from earlier runs, so long-running modes report each run's own
+    pub fn clear_errors(&self) {
+        if let Ok(mut errors) = self.last_errors.lock() {
+            errors.clear();
+        }
+    }
+
+    /// The most recent error logged for each character, keyed by character name
+    pub fn last_errors(&self) -> HashMap<String, String> {
+        self.last_errors
+            .lock()
+            .map(|errors| errors.clone())
+            .unwrap_or_default()
+    }
+
     pub fn log_dir(&self) -> &PathBuf {
         &self.log_dir
     }
@@ -1,11 +1,7 @@
-// Unused for now — slot navigation uses cursor-reset approach instead.
-// Kept for potential future use (auto-login detection, etc.)
+// Only `doctor` reads login_w.bin for now — slot navigation uses the cursor-reset
+// approach instead of `navigation_steps`. Kept for potential future use.
 #![allow(dead_code)]
 
-use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
-use std::path::Path;
-
 const SLOT_OFFSET: u64 = 0x64;
 const AUTO_LOGIN_OFFSET: u64 = 0x6F;
 
@@ -14,32 +10,22 @@ pub struct LoginBinInfo {
     pub auto_login_enabled: bool,
 }
 
-/// Read login_w.bin to get the currently selected slot and auto-login status
-pub fn read_login_bin(playonline_dir: &Path) -> Result<LoginBinInfo, Box<dyn std::error::Error>> {
-    let path = playonline_dir.join("login_w.bin");
-    let mut file = File::open(&path)
-        .map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
-
-    let mut slot_byte = [0u8; 1];
-    file.seek(SeekFrom::Start(SLOT_OFFSET))?;
-    file.read_exact(&mut slot_byte)?;
-
-    let mut auto_login_byte = [0u8; 1];
-    file.seek(SeekFrom::Start(AUTO_LOGIN_OFFSET))?;
-    file.read_exact(&mut auto_login_byte)?;
-
-    let info = LoginBinInfo {
-        current_slot: slot_byte[0],
-        auto_login_enabled: auto_login_byte[0] != 0,
+/// Parse the contents of login_w.bin that have already been read into memory
+pub fn parse_login_bin(bytes: &[u8]) -> Result<LoginBinInfo, String> {
+    let byte_at = |offset: u64| {
+        bytes.get(offset as usize).copied().ok_or_else(|| {
+            format!(
+                "login_w.bin is too short ({} bytes, expected at least {})",
+                bytes.len(),
+                offset + 1
+            )
+        })
     };
 
-    log::info!(
-        "login_w.bin: current_slot={}, auto_login={}",
-        info.current_slot,
-        info.auto_login_enabled
-    );
-
-    Ok(info)
+    Ok(LoginBinInfo {
+        current_slot: byte_at(SLOT_OFFSET)?,
+        auto_login_enabled: byte_at(AUTO_LOGIN_OFFSET)? != 0,
+    })
 }
 
 /// Calculate navigation steps from current slot to target slot
@@ -1,81 +1,561 @@
+mod api;
+mod checks;
+mod cli;
+mod clock;
 mod config;
+mod daemon;
+mod doctor;
+mod events;
+mod exit_code;
+mod focus;
+mod game;
 mod hosts;
+mod input;
 mod launcher;
+mod layout;
+mod lobby;
 mod logging;
 mod login_bin;
+mod processes;
 mod proxy;
+mod recording;
+mod report;
+mod schedule;
+mod sequence;
+mod titles;
+mod typing;
+mod validate;
+mod watchdog;
 mod win32;
+mod webhooks;
+mod wizard;
 
 use clap::Parser;
-use std::path::PathBuf;
+use cli::{Cli, Command, ConfigCommand, GlobalArgs, HostsCommand, LoginArgs, ReplayArgs};
+use clock::Clock;
+use events::{Event, Output, OutputFormat};
+use launcher::Launcher;
+use exit_code::ExitCode;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
 
-#[derive(Parser)]
-#[command(name = "login-rs", version, about = "Automated FFXI multi-character login")]
-struct Args {
-    /// Path to config file
-    #[arg(short, long, default_value = "config.json")]
-    config: PathBuf,
+const SAMPLE_CONFIG: &str = include_str!("../config.json");
 
-    /// Specific characters to log in (by name). If omitted, logs in all.
-    #[arg(long, num_args = 1..)]
-    characters: Vec<String>,
+fn main() {
+    let mut cli = Cli::parse();
 
-    /// Record mode: launch one character and record keypresses for debugging login automation
-    #[arg(long)]
-    record: bool,
-}
+    let mut logger = env_logger::Builder::from_default_env();
+    if let Some(level) = cli.global.log_level {
+        logger.filter_level(level.into());
+    }
+    logger.init();
 
-fn main() {
-    env_logger::init();
+    let out = Output::new(cli.global.output);
+    let command = cli.take_command().unwrap_or_else(|e| e.exit());
+    let global = &cli.global;
 
+    match command {
+        Command::Login(args) => run_login(global, &args, &out),
+        Command::Record(args) => run_record(global, args.character.as_deref()),
+        Command::Replay(args) => run_replay(global, &args),
+        Command::Daemon => run_daemon(global, &out),
+        Command::Serve => run_serve(global, &out),
+        Command::Scheduler => run_scheduler(global, &out),
+        Command::Validate => run_validate(global, &out),
+        Command::Doctor => run_doctor(global, &out),
+        Command::List => run_list(global, &out),
+        Command::Hosts {
+            command: HostsCommand::Clean,
+        } => run_hosts_clean(global, &out),
+        Command::Config {
+            command: ConfigCommand::Init { force, sample },
+        } => run_config_init(global, force, sample),
+        Command::Config {
+            command: ConfigCommand::Show { origin },
+        } => run_config_show(global, origin, &out),
+        Command::Config {
+            command: ConfigCommand::Migrate,
+        } => run_config_migrate(global),
+        Command::Config {
+            command:
+                ConfigCommand::Convert {
+                    to,
+                    destination,
+                    force,
+                },
+        } => run_config_convert(global, to, destination, force),
+    }
+}
+
+/// Re-launch elevated if needed. Commands that touch the hosts file or block input call this first.
+/// The elevated instance runs in its own console, so its events don't reach this one's stdout.
+fn require_elevation(global: &GlobalArgs) {
     if !win32::is_elevated() {
-        println!("Not running as administrator, requesting elevation...");
+        let message = "Not running as administrator, requesting elevation...";
+        // Keep stdout to JSON events only
+        if global.output == OutputFormat::Json {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message);
+        }
         win32::elevate_self();
     }
+}
 
-    let args = Args::parse();
-
-    let config = match config::Config::load(&args.config) {
+fn load_config(global: &GlobalArgs) -> config::Config {
+    match config::Config::load(&global.config, global.config_format(), &global.set) {
         Ok(c) => c,
         Err(e) => {
-            eprintln!("Failed to load config from {:?}: {}", args.config, e);
-            std::process::exit(1);
+            eprintln!("Failed to load config from {:?}: {}", global.config, e);
+            ExitCode::ConfigError.exit();
         }
-    };
+    }
+}
 
-    let file_logger = match logging::FileLogger::new() {
+fn init_file_logger() -> logging::FileLogger {
+    match logging::FileLogger::new() {
         Ok(l) => l,
         Err(e) => {
             eprintln!("Failed to initialize logging: {}", e);
-            std::process::exit(1);
+            ExitCode::ConfigError.exit();
         }
-    };
+    }
+}
+
+fn install_ctrlc_handler() {
+    ctrlc::set_handler(move || {
+        eprintln!("\nInterrupted! Cleaning up...");
+        // Best effort: Windows only honours this from the thread that blocked input,
+        // and otherwise releases it when the process exits
+        win32::block_input(false);
+        hosts::cleanup_stale();
+        ExitCode::Interrupted.exit();
+    })
+    .expect("Failed to set Ctrl+C handler");
+}
 
+fn run_login(global: &GlobalArgs, args: &LoginArgs, out: &Output) -> ! {
+    require_elevation(global);
+
+    let config = load_config(global);
+    let file_logger = init_file_logger();
     let characters = config.filter_characters(&args.characters);
 
     if characters.is_empty() {
         eprintln!("No matching characters found for: {:?}", args.characters);
-        std::process::exit(1);
+        ExitCode::ConfigError.exit();
     }
 
-    println!("login-rs v{}", env!("CARGO_PKG_VERSION"));
-    println!("Launching {} character(s):", characters.len());
-    for ch in &characters {
-        println!("  - {} (slot {})", ch.name, ch.slot);
+    install_ctrlc_handler();
+
+    let webhooks = webhooks::Webhooks::start(&config, out);
+    let mut launcher = launcher::WindowerLauncher {
+        logger: &file_logger,
+        out,
+        retry: launcher::RetryMode::Ask,
+    };
+    let report = launcher.login(&config, &characters, &AtomicBool::new(false));
+
+    let mut exit_code = ExitCode::from(report.status);
+    if let Some(settings) = config.watchdog.clone() {
+        let mut watchdog = watchdog::Watchdog::new(settings);
+        watchdog.track(&report);
+        if !watchdog.watching().is_empty() {
+            out.emit(Event::WatchdogStarted {
+                characters: watchdog.watching(),
+            });
+            // Relaunches happen while nobody may be watching
+            launcher.retry = launcher::RetryMode::Unattended;
+            watchdog.run(&config, &mut launcher, &processes::SystemProcesses, &clock::SystemClock, out);
+            // Only returns once it has given up on every character
+            exit_code = ExitCode::AllFailed;
+        }
+    }
+    if let Some(webhooks) = webhooks {
+        webhooks.finish();
     }
-    println!("Logs: {}", file_logger.log_dir().display());
 
-    ctrlc::set_handler(move || {
-        eprintln!("\nInterrupted! Cleaning up...");
-        win32::block_input(false);
-        hosts::cleanup_stale();
-        std::process::exit(1);
+    exit_code.exit();
+}
+
+/// Read the config and the files it was built from, for the daemon to watch
+fn load_for_daemon(global: &GlobalArgs) -> Result<daemon::Loaded, String> {
+    let layers = config::layers::read_layers(&global.config, global.config_format(), &global.set)?;
+    let config = config::Config::build(&layers, config::config_dir(&global.config))
+        .map_err(|e| e.to_string())?;
+    Ok(daemon::Loaded {
+        config,
+        files: config::layers::source_files(&global.config, &layers),
     })
-    .expect("Failed to set Ctrl+C handler");
+}
+
+fn run_daemon(global: &GlobalArgs, out: &Output) -> ! {
+    require_elevation(global);
+
+    let loaded = match load_for_daemon(global) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("Failed to load config from {:?}: {}", global.config, e);
+            ExitCode::ConfigError.exit();
+        }
+    };
+    let file_logger = init_file_logger();
+
+    install_ctrlc_handler();
+
+    let mut launcher = launcher::WindowerLauncher {
+        logger: &file_logger,
+        out,
+        retry: launcher::RetryMode::Unattended,
+    };
+    // Webhooks are set up from the config at startup; restart the daemon to change them
+    let _webhooks = webhooks::Webhooks::start(&loaded.config, out);
+    let mut watcher = daemon::PollingWatcher::new(loaded.files.clone());
+    out.emit(Event::Watching {
+        files: loaded.files.iter().map(|f| f.display().to_string()).collect(),
+    });
+
+    // Like webhooks, the watchdog settings are read at startup
+    let watchdog = loaded.config.watchdog.clone().map(watchdog::Watchdog::new);
+    let processes = processes::SystemProcesses;
+    let mut daemon = daemon::Daemon::new(loaded.config, &mut launcher, &processes, out);
+    if let Some(watchdog) = watchdog {
+        daemon.supervise(watchdog);
+    }
+    daemon.launch_pending();
+    daemon.run(&mut watcher, &clock::SystemClock, &mut || load_for_daemon(global))
+}
+
+fn run_serve(global: &GlobalArgs, out: &Output) -> ! {
+    require_elevation(global);
+
+    let config = load_config(global);
+    let Some(api) = config.api.clone() else {
+        eprintln!(
+            "No `api` section in {:?}; add one with a token to use `serve`",
+            global.config
+        );
+        ExitCode::ConfigError.exit();
+    };
+    let listener = match proxy::bind_localhost(api.port) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("Failed to start the control API on port {}: {}", api.port, e);
+            ExitCode::ConfigError.exit();
+        }
+    };
+    let file_logger = init_file_logger();
+
+    install_ctrlc_handler();
+
+    let _webhooks = webhooks::Webhooks::start(&config, out);
+    let state = Arc::new(api::ApiState::default());
+    let observer = Arc::clone(&state);
+    out.subscribe(move |event| observer.observe(event));
+
+    let launcher = Mutex::new(launcher::WindowerLauncher {
+        logger: &file_logger,
+        out,
+        retry: launcher::RetryMode::Unattended,
+    });
+    out.emit(Event::ApiListening {
+        address: format!("127.0.0.1:{}", api.port),
+    });
+    api::serve(listener, &config, &api.token, &state, &launcher)
+}
+
+fn run_scheduler(global: &GlobalArgs, out: &Output) -> ! {
+    require_elevation(global);
+
+    let config = load_config(global);
+    if config.schedule.is_empty() {
+        eprintln!(
+            "No `schedule` entries in {:?}; add at least one to use `scheduler`",
+            global.config
+        );
+        ExitCode::ConfigError.exit();
+    }
+    let file_logger = init_file_logger();
+
+    install_ctrlc_handler();
+
+    let _webhooks = webhooks::Webhooks::start(&config, out);
+    let mut launcher = launcher::WindowerLauncher {
+        logger: &file_logger,
+        out,
+        retry: launcher::RetryMode::Unattended,
+    };
+    let clock = clock::SystemClock;
+    let mut scheduler = match schedule::Scheduler::new(&config, &mut launcher, out, clock.utc_now()) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Failed to load config from {:?}: {}", global.config, e);
+            ExitCode::ConfigError.exit();
+        }
+    };
+    scheduler.run(&clock)
+}
+
+/// Resolve the character for `record`/`replay`, defaulting to the first in the config
+fn single_character<'a>(config: &'a config::Config, name: Option<&str>) -> &'a config::Character {
+    let names: Vec<String> = name.map(str::to_string).into_iter().collect();
+    match config.filter_characters(&names).first() {
+        Some(ch) => ch,
+        None => {
+            eprintln!("No matching character found for: {:?}", names);
+            ExitCode::ConfigError.exit();
+        }
+    }
+}
+
+fn run_record(global: &GlobalArgs, name: Option<&str>) -> ! {
+    require_elevation(global);
+
+    let config = load_config(global);
+    let file_logger = init_file_logger();
+    let character = single_character(&config, name);
+
+    install_ctrlc_handler();
+    launcher::run_record_mode(&config, character, &file_logger);
+    ExitCode::Success.exit();
+}
+
+fn run_replay(global: &GlobalArgs, args: &ReplayArgs) -> ! {
+    let keys = match std::fs::read_to_string(&args.recording)
+        .map_err(|e| e.to_string())
+        .and_then(|text| recording::parse_recording(&text))
+    {
+        Ok(keys) => keys,
+        Err(e) => {
+            eprintln!("Failed to read recording {:?}: {}", args.recording, e);
+            ExitCode::ConfigError.exit();
+        }
+    };
+
+    require_elevation(global);
+
+    let config = load_config(global);
+    let file_logger = init_file_logger();
+    let character = single_character(&config, args.character.as_deref());
+
+    install_ctrlc_handler();
+    launcher::run_replay_mode(&config, character, &keys, &file_logger);
+    ExitCode::Success.exit();
+}
+
+fn run_list(global: &GlobalArgs, out: &Output) -> ! {
+    let config = load_config(global);
+    for ch in &config.characters {
+        out.emit(Event::CharacterListed {
+            name: &ch.name,
+            slot: ch.slot,
+        });
+    }
+    ExitCode::Success.exit();
+}
+
+fn run_hosts_clean(global: &GlobalArgs, out: &Output) -> ! {
+    require_elevation(global);
+
+    let removed = std::fs::read_to_string(hosts::HOSTS_PATH)
+        .map(|contents| hosts::count_entries(&contents))
+        .unwrap_or(0);
+    if let Err(e) = hosts::remove_entries() {
+        eprintln!("Failed to clean hosts file: {}", e);
+        ExitCode::ConfigError.exit();
+    }
+    out.emit(Event::HostsCleaned { removed });
+    ExitCode::Success.exit();
+}
+
+fn run_config_init(global: &GlobalArgs, force: bool, sample: bool) -> ! {
+    let path = global.config.as_path();
+
+    if sample {
+        if path.exists() && !force {
+            eprintln!("{:?} already exists (use --force to overwrite)", path);
+            ExitCode::ConfigError.exit();
+        }
+        if let Err(e) = write_sample_config(path, global.config_format()) {
+            eprintln!("Failed to write {:?}: {}", path, e);
+            ExitCode::ConfigError.exit();
+        }
+        println!("Wrote {}", path.display());
+        ExitCode::Success.exit();
+    }
+
+    let exe_dir = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf));
+    match wizard::run_wizard(
+        &mut wizard::StdioPrompter,
+        &checks::RealFileSystem,
+        exe_dir.as_deref(),
+        path,
+        global.config_format(),
+        force,
+    ) {
+        Ok(wizard::WizardOutcome::Written(path)) => {
+            println!("\nWrote {}", path.display());
+            println!("Run `login-rs validate` and `login-rs doctor` to check it.");
+            ExitCode::Success.exit();
+        }
+        Ok(wizard::WizardOutcome::Cancelled) => {
+            println!("Cancelled; {} was not changed", path.display());
+            ExitCode::ConfigError.exit();
+        }
+        Err(e) => {
+            eprintln!("Failed to create config: {}", e);
+            ExitCode::ConfigError.exit();
+        }
+    }
+}
 
-    if args.record {
-        launcher::run_record_mode(&config, &characters, &file_logger);
+fn run_config_show(global: &GlobalArgs, origin: bool, out: &Output) -> ! {
+    let format = global.config_format();
+    let effective = config::layers::read_layers(&global.config, format, &global.set)
+        .map_err(|e| e.into())
+        .and_then(|layers| config::effective(&layers));
+    let effective = match effective {
+        Ok(e) => e,
+        Err(e) => {
+            eprintln!("Failed to load config from {:?}: {}", global.config, e);
+            ExitCode::ConfigError.exit();
+        }
+    };
+
+    if origin {
+        for (key, value) in config::layers::flatten(&effective.value, "") {
+            let origin = effective
+                .origins
+                .get(&key)
+                .map(String::as_str)
+                .unwrap_or(config::DEFAULT_ORIGIN);
+            out.emit(Event::ConfigValue {
+                key: &key,
+                value: &value,
+                origin,
+            });
+        }
     } else {
-        launcher::run(&config, &characters, &file_logger);
+        match format.serialize(&effective.value) {
+            Ok(text) => print!("{}", text),
+            Err(e) => {
+                eprintln!("Failed to format config: {}", e);
+                ExitCode::ConfigError.exit();
+            }
+        }
+    }
+    ExitCode::Success.exit();
+}
+
+fn run_config_migrate(global: &GlobalArgs) -> ! {
+    let path = global.config.as_path();
+    match config::migrate::migrate_file(path, global.config_format()) {
+        Ok(None) => println!(
+            "{} is already at version {}",
+            path.display(),
+            config::migrate::CURRENT_VERSION
+        ),
+        Ok(Some((from, backup))) => {
+            println!(
+                "Upgraded {} from version {} to {}",
+                path.display(),
+                from,
+                config::migrate::CURRENT_VERSION
+            );
+            println!("Backup: {}", backup.display());
+            let format = global.config_format();
+            if format != config::ConfigFormat::Json {
+                eprintln!(
+                    "Warning: comments in the {} file were not kept; copy any you need from the backup",
+                    format.name()
+                );
+            }
+        }
+        Err(e) => {
+            eprintln!("Failed to migrate {:?}: {}", path, e);
+            ExitCode::ConfigError.exit();
+        }
+    }
+    ExitCode::Success.exit();
+}
+
+fn run_config_convert(
+    global: &GlobalArgs,
+    to: config::ConfigFormat,
+    destination: Option<PathBuf>,
+    force: bool,
+) -> ! {
+    let source = global.config.as_path();
+    let destination = destination.unwrap_or_else(|| source.with_extension(to.extension()));
+    if destination.exists() && !force {
+        eprintln!("{:?} already exists (use --force to overwrite)", destination);
+        ExitCode::ConfigError.exit();
+    }
+
+    let converted = std::fs::read_to_string(source)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| config::convert(&contents, global.config_format(), to));
+    let result = converted.and_then(|text| std::fs::write(&destination, text).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        eprintln!("Failed to convert {:?}: {}", source, e);
+        ExitCode::ConfigError.exit();
+    }
+
+    println!(
+        "Wrote {} ({})",
+        destination.display(),
+        to.name()
+    );
+    ExitCode::Success.exit();
+}
+
+fn write_sample_config(path: &Path, format: config::ConfigFormat) -> Result<(), String> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let contents = config::convert(SAMPLE_CONFIG, config::ConfigFormat::Json, format)?;
+    std::fs::write(path, contents).map_err(|e| e.to_string())
+}
+
+fn run_validate(global: &GlobalArgs, out: &Output) -> ! {
+    let results = match config::layers::read_layers(&global.config, global.config_format(), &global.set) {
+        Ok(layers) => validate::validate_config(&layers, config::config_dir(&global.config)),
+        Err(e) => vec![checks::CheckResult::fail(
+            "Config file",
+            format!("{}: {}", global.config.display(), e),
+        )],
+    };
+    finish_checklist(&results, out)
+}
+
+fn run_doctor(global: &GlobalArgs, out: &Output) -> ! {
+    let config = config::Config::load(&global.config, global.config_format(), &global.set);
+    let mut results = vec![match &config {
+        Ok(_) => checks::CheckResult::pass("Config", global.config.display().to_string()),
+        Err(e) => checks::CheckResult::fail(
+            "Config",
+            format!("Failed to load {:?}: {}", global.config, e),
+        ),
+    }];
+    results.extend(doctor::run_doctor(
+        config.as_ref().ok(),
+        &checks::RealFileSystem,
+        &checks::RealNetwork,
+        Path::new(hosts::HOSTS_PATH),
+        win32::is_elevated(),
+    ));
+    finish_checklist(&results, out)
+}
+
+fn finish_checklist(results: &[checks::CheckResult], out: &Output) -> ! {
+    for result in results {
+        out.emit(Event::Check { result });
+    }
+    if checks::has_failures(results) {
+        ExitCode::ConfigError.exit();
     }
+    ExitCode::Success.exit();
 }
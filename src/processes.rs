@@ -0,0 +1,231 @@
+use crate::win32;
+
+/// A running process and the process that started it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessEntry {
+    pub pid: u32,
+    pub parent: u32,
+}
+
+/// A visible top-level window and the process that owns it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowEntry {
+    /// See `win32::window_id`
+    pub id: isize,
+    pub pid: u32,
+    pub title: String,
+}
+
+/// Processes and windows at one moment
+#[derive(Debug, Clone, Default)]
+pub struct ProcessTable {
+    pub processes: Vec<ProcessEntry>,
+    pub windows: Vec<WindowEntry>,
+}
+
+impl ProcessTable {
+    /// `root` and every process it started, directly or indirectly. Children are found
+    /// even after `root` itself has exited, since Windows keeps their parent pid.
+    pub fn tree(&self, root: u32) -> Vec<u32> {
+        let mut tree = vec![root];
+        let mut i = 0;
+        while i < tree.len() {
+            let parent = tree[i];
+            for p in &self.processes {
+                // A pid can be its own listed parent (e.g. the idle process); skip repeats
+                if p.parent == parent && !tree.contains(&p.pid) {
+                    tree.push(p.pid);
+                }
+            }
+            i += 1;
+        }
+        tree
+    }
+
+    /// Whether any process in `root`'s tree is still running
+    pub fn tree_running(&self, root: u32) -> bool {
+        let tree = self.tree(root);
+        self.processes.iter().any(|p| tree.contains(&p.pid))
+    }
+
+    /// Windows owned by `root` or any process it started
+    pub fn windows_of(&self, root: u32) -> Vec<&WindowEntry> {
+        let tree = self.tree(root);
+        self.windows
+            .iter()
+            .filter(|w| tree.contains(&w.pid))
+            .collect()
+    }
+}
+
+/// How a just-launched character's window was identified
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowMatch {
+    /// Owned by the launched process or one of the processes it started
+    ProcessTree(isize),
+    /// The launched process could no longer be followed, so this is simply a new
+    /// window with the expected title
+    TitleOnly(isize),
+}
+
+/// Find the window titled `prefix` that process `pid` opened, skipping `claimed` windows
+/// (ones open before the run, or already matched to another character).
+///
+/// Windows from `pid`'s own process tree win, so two instances started close together,
+/// or one the user opens by hand, can't be mixed up. Only once nothing in the tree is
+/// running any more (e.g. the launcher handed off to an unrelated process) does any new
+/// window with the right title count.
+pub fn find_launched_window(
+    table: &ProcessTable,
+    pid: u32,
+    prefix: &str,
+    claimed: &[isize],
+) -> Option<WindowMatch> {
+    let candidate = |w: &&WindowEntry| w.title.starts_with(prefix) && !claimed.contains(&w.id);
+    if let Some(w) = table.windows_of(pid).into_iter().find(candidate) {
+        return Some(WindowMatch::ProcessTree(w.id));
+    }
+    if table.tree_running(pid) {
+        // Still starting up; wait for its own window
+        return None;
+    }
+    table
+        .windows
+        .iter()
+        .find(candidate)
+        .map(|w| WindowMatch::TitleOnly(w.id))
+}
+
+/// Looks up running processes and their windows. `SystemProcesses` asks Windows;
+/// supervision logic takes the trait so it can run against a prepared table.
+pub trait ProcessQuery {
+    fn snapshot(&self) -> ProcessTable;
+}
+
+pub struct SystemProcesses;
+
+impl ProcessQuery for SystemProcesses {
+    fn snapshot(&self) -> ProcessTable {
+        ProcessTable {
+            processes: win32::list_processes()
+                .into_iter()
+                .map(|(pid, parent)| ProcessEntry { pid, parent })
+                .collect(),
+            windows: win32::list_windows()
+                .into_iter()
+                .map(|(hwnd, pid, title)| WindowEntry {
+                    id: win32::window_id(hwnd),
+                    pid,
+                    title,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POL: &str = "PlayOnline Viewer";
+
+    fn table(processes: &[(u32, u32)], windows: &[(isize, u32, &str)]) -> ProcessTable {
+        ProcessTable {
+            processes: processes
+                .iter()
+                .map(|&(pid, parent)| ProcessEntry { pid, parent })
+                .collect(),
+            windows: windows
+                .iter()
+                .map(|&(id, pid, title)| WindowEntry {
+                    id,
+                    pid,
+                    title: title.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn tree_follows_children_after_the_root_exits() {
+        // Windower (100) started pol.exe (200), which started ffxi (300), then exited
+        let t = table(&[(200, 100), (300, 200), (400, 1)], &[]);
+        assert_eq!(t.tree(100), [100, 200, 300]);
+        assert!(t.tree_running(100));
+        assert!(!t.tree_running(500));
+    }
+
+    #[test]
+    fn tree_stops_at_self_parented_pids() {
+        let t = table(&[(0, 0), (4, 0), (100, 4), (4, 100)], &[]);
+        assert_eq!(t.tree(0), [0, 4, 100]);
+        assert_eq!(t.tree(100), [100, 4]);
+    }
+
+    #[test]
+    fn finds_the_pol_window_of_a_windower_child() {
+        let t = table(&[(100, 1), (200, 100)], &[(11, 200, POL)]);
+        assert_eq!(
+            find_launched_window(&t, 100, "PlayOnline", &[]),
+            Some(WindowMatch::ProcessTree(11))
+        );
+        assert_eq!(t.windows_of(100).len(), 1);
+    }
+
+    #[test]
+    fn tells_apart_two_launches_started_close_together() {
+        // The second launch's window opened first
+        let t = table(
+            &[(100, 1), (101, 1), (200, 100), (201, 101)],
+            &[(12, 201, POL), (11, 200, POL)],
+        );
+        assert_eq!(
+            find_launched_window(&t, 100, "PlayOnline", &[]),
+            Some(WindowMatch::ProcessTree(11))
+        );
+        assert_eq!(
+            find_launched_window(&t, 101, "PlayOnline", &[11]),
+            Some(WindowMatch::ProcessTree(12))
+        );
+    }
+
+    #[test]
+    fn ignores_a_pol_window_opened_by_hand_while_the_launch_runs() {
+        let t = table(&[(100, 1), (200, 100), (900, 1)], &[(99, 900, POL)]);
+        assert_eq!(find_launched_window(&t, 100, "PlayOnline", &[]), None);
+
+        let t = table(
+            &[(100, 1), (200, 100), (900, 1)],
+            &[(99, 900, POL), (11, 200, POL)],
+        );
+        assert_eq!(
+            find_launched_window(&t, 100, "PlayOnline", &[]),
+            Some(WindowMatch::ProcessTree(11))
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_title_once_the_tree_has_exited() {
+        let t = table(&[(900, 1)], &[(98, 900, POL), (99, 900, POL)]);
+        assert_eq!(
+            find_launched_window(&t, 100, "PlayOnline", &[98]),
+            Some(WindowMatch::TitleOnly(99))
+        );
+        assert_eq!(find_launched_window(&t, 100, "PlayOnline", &[98, 99]), None);
+        assert_eq!(find_launched_window(&t, 100, "FINAL FANTASY", &[]), None);
+    }
+
+    #[test]
+    fn skips_claimed_and_wrongly_titled_windows_in_the_tree() {
+        let t = table(
+            &[(100, 1), (200, 100)],
+            &[(10, 200, "Windower"), (11, 200, POL), (12, 200, POL)],
+        );
+        assert_eq!(
+            find_launched_window(&t, 100, "PlayOnline", &[11]),
+            Some(WindowMatch::ProcessTree(12))
+        );
+        // Still running, so other processes' windows don't count
+        assert_eq!(find_launched_window(&t, 100, "PlayOnline", &[11, 12]), None);
+    }
+}
@@ -1,51 +1,213 @@
-use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
-use std::thread;
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 const PML_BODY: &str = r#"<pml><head><meta http-equiv="Content-Type" content="text/x-playonline-pml;charset=UTF-8"><title>Fast</title></head><body><timer name="fast" href="gameto:1" enable="1" delay="0"></body></pml>"#;
 
-fn handle_connection(mut stream: TcpStream) {
-    // Read the request (we don't care about the contents)
+/// How often the proxy checks whether it should stop waiting for a connection
+const ACCEPT_POLL: Duration = Duration::from_millis(50);
+
+/// Time PlayOnline gets to send its request once connected
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Largest request (headers plus body) we are willing to read
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+/// The parts of an HTTP/1.1 request the proxy and control API look at
+pub struct HttpRequest {
+    pub method: String,
+    /// Request path without the query string
+    pub path: String,
+    headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    /// Header value by case-insensitive name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Read one request: the request line, headers, and a body of `Content-Length` bytes
+pub fn read_request(stream: &mut impl Read) -> Result<HttpRequest, String> {
+    let mut data = Vec::new();
     let mut buf = [0u8; 1024];
-    let _ = stream.read(&mut buf);
+    let header_end = loop {
+        if let Some(i) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break i;
+        }
+        if data.len() > MAX_REQUEST_BYTES {
+            return Err("request headers too large".into());
+        }
+        let n = stream.read(&mut buf).map_err(|e| e.to_string())?;
+        if n == 0 {
+            return Err("connection closed before the request was complete".into());
+        }
+        data.extend_from_slice(&buf[..n]);
+    };
 
+    let head = std::str::from_utf8(&data[..header_end]).map_err(|_| "request headers are not UTF-8")?;
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let (Some(method), Some(target)) = (request_line.next(), request_line.next()) else {
+        return Err("malformed request line".into());
+    };
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(n, v)| (n.trim().to_string(), v.trim().to_string()))
+        .collect();
+
+    let mut request = HttpRequest {
+        method: method.to_string(),
+        path: target.split('?').next().unwrap_or(target).to_string(),
+        headers,
+        body: data[header_end + 4..].to_vec(),
+    };
+
+    let length: usize = match request.header("content-length") {
+        Some(v) => v.parse().map_err(|_| "invalid Content-Length")?,
+        None => 0,
+    };
+    if length > MAX_REQUEST_BYTES {
+        return Err("request body too large".into());
+    }
+    while request.body.len() < length {
+        let n = stream.read(&mut buf).map_err(|e| e.to_string())?;
+        if n == 0 {
+            return Err("connection closed before the body was complete".into());
+        }
+        request.body.extend_from_slice(&buf[..n]);
+    }
+    request.body.truncate(length);
+    Ok(request)
+}
+
+/// Write a complete response and close the exchange (`Connection: close`)
+pub fn write_response(stream: &mut impl Write, status: u16, content_type: &str, body: &str) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    };
     let response = format!(
-        "HTTP/1.1 200 OK\r\nContent-Type: text/x-playonline-pml;charset=UTF-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
-        PML_BODY.len(),
-        PML_BODY,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        content_type,
+        body.len(),
+        body,
     );
+    stream.write_all(response.as_bytes())?;
+    stream.flush()
+}
+
+fn handle_connection(mut stream: TcpStream) {
+    // Accepted sockets can inherit the listener's non-blocking mode
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+
+    // Read the request (we don't care about the contents)
+    let mut buf = [0u8; 1024];
+    let _ = stream.read(&mut buf);
 
-    let _ = stream.write_all(response.as_bytes());
-    let _ = stream.flush();
+    let _ = write_response(&mut stream, 200, "text/x-playonline-pml;charset=UTF-8", PML_BODY);
     log::info!("Proxy served PML redirect response");
 }
 
+/// A proxy waiting in a background thread for PlayOnline's one request.
+/// Dropping it, e.g. when a login fails part way, stops the wait and closes the port.
+pub struct Proxy {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<Option<Instant>>>,
+}
+
+impl Proxy {
+    /// Wait until the request is served or the deadline passes, returning when it was served
+    pub fn wait(mut self) -> Option<Instant> {
+        self.thread.take().and_then(|t| t.join().ok().flatten())
+    }
+}
+
+impl Drop for Proxy {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
 /// Start the proxy server in a background thread.
-/// It serves one request then shuts down.
+/// It serves one request then shuts down, or gives up after `accept_within`.
 /// Uses SO_REUSEADDR so the port can be rebound immediately after closing.
-pub fn start_proxy(port: u16) -> Result<thread::JoinHandle<()>, Box<dyn std::error::Error>> {
-    let listener = bind_with_reuse(port)?;
+pub fn start_proxy(port: u16, accept_within: Duration) -> Result<Proxy, Box<dyn std::error::Error>> {
+    let listener = bind_with_reuse(Ipv4Addr::UNSPECIFIED, port)?;
+    // Polled, so the wait can end at the deadline or when the proxy is dropped
+    listener.set_nonblocking(true)?;
 
     log::info!("Proxy server listening on port {}", port);
 
-    let handle = thread::spawn(move || {
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::clone(&stop);
+    let deadline = Instant::now() + accept_within;
+    // listener moves into the thread and drops when it ends, closing the socket
+    let thread = thread::spawn(move || serve_one(&listener, deadline, &stopped));
+
+    Ok(Proxy {
+        stop,
+        thread: Some(thread),
+    })
+}
+
+fn serve_one(listener: &TcpListener, deadline: Instant, stop: &AtomicBool) -> Option<Instant> {
+    loop {
         match listener.accept() {
             Ok((stream, addr)) => {
                 log::info!("Proxy accepted connection from {}", addr);
                 handle_connection(stream);
+                return Some(Instant::now());
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                if stop.load(Ordering::SeqCst) {
+                    log::debug!("Proxy stopped before PlayOnline connected");
+                    return None;
+                }
+                if Instant::now() >= deadline {
+                    log::warn!("PlayOnline didn't connect to the proxy in time");
+                    return None;
+                }
+                thread::sleep(ACCEPT_POLL);
             }
             Err(e) => {
                 log::error!("Proxy error accepting connection: {}", e);
+                return None;
             }
         }
-        // listener drops here, socket closes
-    });
+    }
+}
 
-    Ok(handle)
+/// Listen on 127.0.0.1 only, for services that must not be reachable from other machines.
+/// Unlike the proxy this doesn't set SO_REUSEADDR, which on Windows would let another
+/// process bind the same port and take its connections.
+pub fn bind_localhost(port: u16) -> Result<TcpListener, Box<dyn std::error::Error>> {
+    Ok(TcpListener::bind((Ipv4Addr::LOCALHOST, port))?)
 }
 
+/// Listen on `ip` with SO_REUSEADDR, the way the proxy binds its port
 #[cfg(windows)]
-fn bind_with_reuse(port: u16) -> Result<TcpListener, Box<dyn std::error::Error>> {
+pub fn bind_with_reuse(ip: Ipv4Addr, port: u16) -> Result<TcpListener, Box<dyn std::error::Error>> {
     use std::os::windows::io::FromRawSocket;
     use windows_sys::Win32::Networking::WinSock::{
         socket, bind as wsa_bind, listen, setsockopt,
@@ -79,8 +241,7 @@ fn bind_with_reuse(port: u16) -> Result<TcpListener, Box<dyn std::error::Error>>
         let mut addr: SOCKADDR_IN = std::mem::zeroed();
         addr.sin_family = AF_INET;
         addr.sin_port = port.to_be();
-        // 0.0.0.0
-        addr.sin_addr.S_un.S_addr = 0;
+        addr.sin_addr.S_un.S_addr = u32::from(ip).to_be();
 
         if wsa_bind(
             sock as usize,
@@ -88,7 +249,7 @@ fn bind_with_reuse(port: u16) -> Result<TcpListener, Box<dyn std::error::Error>>
             std::mem::size_of::<SOCKADDR_IN>() as i32,
         ) == SOCKET_ERROR
         {
-            return Err(format!("Failed to bind proxy on port {}", port).into());
+            return Err(format!("Failed to bind {}:{}", ip, port).into());
         }
 
         if listen(sock as usize, 1) == SOCKET_ERROR {
@@ -100,9 +261,68 @@ fn bind_with_reuse(port: u16) -> Result<TcpListener, Box<dyn std::error::Error>>
 }
 
 #[cfg(not(windows))]
-fn bind_with_reuse(port: u16) -> Result<TcpListener, Box<dyn std::error::Error>> {
-    use std::net::SocketAddr;
-    let addr: SocketAddr = format!("0.0.0.0:{}", port).parse()?;
-    let listener = TcpListener::bind(addr)?;
+pub fn bind_with_reuse(ip: Ipv4Addr, port: u16) -> Result<TcpListener, Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind((ip, port))?;
     Ok(listener)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A port nothing is listening on
+    fn free_port() -> u16 {
+        TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    #[test]
+    fn serves_one_request_then_closes() {
+        let port = free_port();
+        let proxy = start_proxy(port, Duration::from_secs(10)).unwrap();
+        let started = Instant::now();
+
+        let mut client = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+        client.write_all(b"GET / HTTP/1.1\r\nHost: wh000.pol.com\r\n\r\n").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with(PML_BODY));
+
+        assert!(proxy.wait().is_some_and(|served| served >= started));
+        assert!(TcpStream::connect((Ipv4Addr::LOCALHOST, port)).is_err());
+    }
+
+    #[test]
+    fn gives_up_at_the_deadline() {
+        let port = free_port();
+        let started = Instant::now();
+        let proxy = start_proxy(port, Duration::from_millis(100)).unwrap();
+        assert_eq!(proxy.wait(), None);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(TcpStream::connect((Ipv4Addr::LOCALHOST, port)).is_err());
+    }
+
+    #[test]
+    fn stops_when_dropped() {
+        let port = free_port();
+        let started = Instant::now();
+        drop(start_proxy(port, Duration::from_secs(60)).unwrap());
+        assert!(started.elapsed() < Duration::from_secs(5));
+        // The port is free for the next login
+        drop(start_proxy(port, Duration::from_secs(60)).unwrap());
+    }
+
+    #[test]
+    fn a_client_that_never_sends_does_not_hold_the_proxy() {
+        let port = free_port();
+        let proxy = start_proxy(port, Duration::from_secs(10)).unwrap();
+        let _silent = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+        let started = Instant::now();
+        assert!(proxy.wait().is_some());
+        assert!(started.elapsed() < READ_TIMEOUT + Duration::from_secs(2));
+    }
+}
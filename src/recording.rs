@@ -0,0 +1,52 @@
+use crate::win32::{self, KeyDirection, RecordedKey};
+
+/// Parse a recording in the table format printed by `record`:
+///
+/// ```text
+/// #      Key                  Dir    Delay
+/// 0      DOWN                 DOWN   +0ms
+/// 1      DOWN                 UP     +122ms  | screen=(...) pol_offset=(...)
+/// ```
+///
+/// Header, separator and any other lines that don't start with an event index are skipped.
+pub fn parse_recording(text: &str) -> Result<Vec<RecordedKey>, String> {
+    let mut keys = Vec::new();
+
+    for (line_no, line) in text.lines().enumerate() {
+        let mut fields = line.split_whitespace();
+        let Some(index) = fields.next() else { continue };
+        if index.parse::<usize>().is_err() {
+            continue;
+        }
+
+        let line_no = line_no + 1;
+        let (Some(name), Some(dir), Some(delay)) = (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(format!("line {}: expected '<#> <key> <DOWN|UP> +<delay>ms'", line_no));
+        };
+
+        let vk_code = win32::vk_from_name(name)
+            .ok_or_else(|| format!("line {}: unknown key '{}'", line_no, name))?;
+        let direction = match dir {
+            "DOWN" => KeyDirection::Down,
+            "UP" => KeyDirection::Up,
+            other => return Err(format!("line {}: unknown direction '{}'", line_no, other)),
+        };
+        let delay_ms = delay
+            .strip_prefix('+')
+            .and_then(|d| d.strip_suffix("ms"))
+            .and_then(|d| d.parse().ok())
+            .ok_or_else(|| format!("line {}: invalid delay '{}'", line_no, delay))?;
+
+        keys.push(RecordedKey {
+            vk_code,
+            direction,
+            delay_ms,
+        });
+    }
+
+    if keys.is_empty() {
+        return Err("recording contains no key events".into());
+    }
+    Ok(keys)
+}
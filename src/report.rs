@@ -0,0 +1,373 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// The process and window a launched character is running in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instance {
+    /// Process started for the character (Windower)
+    pub pid: u32,
+    /// See `win32::window_id`
+    pub window: isize,
+}
+
+/// What happened to a single character during a run, as observed by the launcher.
+#[derive(Debug, Clone)]
+pub struct CharacterOutcome {
+    pub name: String,
+    pub slot: u8,
+    pub launched: bool,
+    pub login: LoginResult,
+    /// Number of login attempts made (0 if the launch failed)
+    pub attempts: u32,
+    /// Time from starting Windower to the POL window appearing
+    pub time_to_window: Option<Duration>,
+    /// Time from the start of the successful login attempt to the proxy serving POL
+    pub time_to_proxy: Option<Duration>,
+    /// Time from the end of the login to the FFXI game window appearing
+    pub time_to_game: Option<Duration>,
+    /// Where the character is running, if it launched
+    pub instance: Option<Instance>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginResult {
+    Success,
+    Failed,
+    NotAttempted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Success,
+    PartialFailure,
+    TotalFailure,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CharacterReport {
+    pub name: String,
+    pub slot: u8,
+    pub launched: bool,
+    pub login: LoginResult,
+    pub attempts: u32,
+    pub time_to_window_ms: Option<u64>,
+    pub time_to_proxy_ms: Option<u64>,
+    pub time_to_game_ms: Option<u64>,
+    pub error: Option<String>,
+    #[serde(skip)]
+    pub instance: Option<Instance>,
+}
+
+impl CharacterReport {
+    pub fn succeeded(&self) -> bool {
+        self.launched && self.login == LoginResult::Success
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunReport {
+    pub version: &'static str,
+    pub status: RunStatus,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub characters: Vec<CharacterReport>,
+}
+
+impl RunReport {
+    /// Build a report from launcher outcomes and the last error logged for each character.
+    pub fn build(outcomes: &[CharacterOutcome], errors: &HashMap<String, String>) -> Self {
+        let characters: Vec<CharacterReport> = outcomes
+            .iter()
+            .map(|o| {
+                // An error from an attempt that was retried doesn't belong to a success
+                let succeeded = o.launched && o.login == LoginResult::Success;
+                CharacterReport {
+                    name: o.name.clone(),
+                    slot: o.slot,
+                    launched: o.launched,
+                    login: o.login,
+                    attempts: o.attempts,
+                    time_to_window_ms: o.time_to_window.map(|d| d.as_millis() as u64),
+                    time_to_proxy_ms: o.time_to_proxy.map(|d| d.as_millis() as u64),
+                    time_to_game_ms: o.time_to_game.map(|d| d.as_millis() as u64),
+                    error: errors.get(&o.name).filter(|_| !succeeded).cloned(),
+                    instance: o.instance,
+                }
+            })
+            .collect();
+
+        let succeeded = characters.iter().filter(|c| c.succeeded()).count();
+        let failed = characters.len() - succeeded;
+        let status = if failed == 0 && succeeded > 0 {
+            RunStatus::Success
+        } else if succeeded == 0 {
+            RunStatus::TotalFailure
+        } else {
+            RunStatus::PartialFailure
+        };
+
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            status,
+            succeeded,
+            failed,
+            characters,
+        }
+    }
+
+    /// Render a plain-text table for the console
+    pub fn render_table(&self) -> String {
+        let name_width = self
+            .characters
+            .iter()
+            .map(|c| c.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("Character".len());
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{:<nw$}  {:<6}  {:<13}  {:<8}  {:<8}  {:<8}  {:<8}  Error",
+            "Character",
+            "Launch",
+            "Login",
+            "Attempts",
+            "Window",
+            "Proxy",
+            "Game",
+            nw = name_width,
+        );
+        let _ = writeln!(out, "{}", "-".repeat(name_width + 68));
+        for c in &self.characters {
+            let _ = writeln!(
+                out,
+                "{:<nw$}  {:<6}  {:<13}  {:<8}  {:<8}  {:<8}  {:<8}  {}",
+                c.name,
+                if c.launched { "ok" } else { "failed" },
+                login_label(c.login),
+                c.attempts,
+                format_ms(c.time_to_window_ms),
+                format_ms(c.time_to_proxy_ms),
+                format_ms(c.time_to_game_ms),
+                c.error.as_deref().unwrap_or("-"),
+                nw = name_width,
+            );
+        }
+        let _ = writeln!(
+            out,
+            "\n{} succeeded, {} failed",
+            self.succeeded, self.failed
+        );
+        out
+    }
+
+    /// Render the report as a Markdown document
+    pub fn render_markdown(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# login-rs run report\n");
+        let _ = writeln!(out, "- Version: {}", self.version);
+        let _ = writeln!(out, "- Status: {}", status_label(self.status));
+        let _ = writeln!(out, "- Succeeded: {}", self.succeeded);
+        let _ = writeln!(out, "- Failed: {}\n", self.failed);
+        let _ = writeln!(
+            out,
+            "| Character | Slot | Launch | Login | Attempts | Time to window | Time to proxy | Time to game | Error |"
+        );
+        let _ = writeln!(out, "|---|---|---|---|---|---|---|---|---|");
+        for c in &self.characters {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} | {} | {} | {} | {} | {} |",
+                c.name,
+                c.slot,
+                if c.launched { "ok" } else { "failed" },
+                login_label(c.login),
+                c.attempts,
+                format_ms(c.time_to_window_ms),
+                format_ms(c.time_to_proxy_ms),
+                format_ms(c.time_to_game_ms),
+                c.error.as_deref().unwrap_or("").replace('|', "\\|"),
+            );
+        }
+        out
+    }
+
+    /// Write `report.json` and `report.md` into the given run directory
+    pub fn write_to(&self, dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(dir.join("report.json"), serde_json::to_string_pretty(self)?)?;
+        fs::write(dir.join("report.md"), self.render_markdown())?;
+        Ok(())
+    }
+}
+
+fn login_label(login: LoginResult) -> &'static str {
+    match login {
+        LoginResult::Success => "ok",
+        LoginResult::Failed => "failed",
+        LoginResult::NotAttempted => "not attempted",
+    }
+}
+
+fn status_label(status: RunStatus) -> &'static str {
+    match status {
+        RunStatus::Success => "success",
+        RunStatus::PartialFailure => "partial failure",
+        RunStatus::TotalFailure => "total failure",
+    }
+}
+
+fn format_ms(ms: Option<u64>) -> String {
+    match ms {
+        Some(ms) => format!("{:.1}s", ms as f64 / 1000.0),
+        None => "-".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(name: &str, launched: bool, login: LoginResult) -> CharacterOutcome {
+        CharacterOutcome {
+            name: name.to_string(),
+            slot: 2,
+            launched,
+            login,
+            attempts: if launched { 1 } else { 0 },
+            time_to_window: launched.then(|| Duration::from_millis(4200)),
+            time_to_proxy: (login == LoginResult::Success).then(|| Duration::from_millis(12_345)),
+            time_to_game: None,
+            instance: None,
+        }
+    }
+
+    fn errors(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(name, error)| (name.to_string(), error.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn all_succeeded() {
+        let report = RunReport::build(
+            &[
+                outcome("A", true, LoginResult::Success),
+                outcome("B", true, LoginResult::Success),
+            ],
+            &HashMap::new(),
+        );
+        assert_eq!(report.status, RunStatus::Success);
+        assert_eq!((report.succeeded, report.failed), (2, 0));
+        assert!(report.characters.iter().all(|c| c.error.is_none()));
+    }
+
+    #[test]
+    fn partial_failure_keeps_each_characters_error() {
+        let report = RunReport::build(
+            &[
+                outcome("A", true, LoginResult::Success),
+                outcome("B", true, LoginResult::Failed),
+            ],
+            &errors(&[("B", "login: window closed")]),
+        );
+        assert_eq!(report.status, RunStatus::PartialFailure);
+        assert_eq!((report.succeeded, report.failed), (1, 1));
+        assert_eq!(report.characters[0].error, None);
+        assert_eq!(report.characters[1].error.as_deref(), Some("login: window closed"));
+    }
+
+    #[test]
+    fn errors_from_retried_attempts_are_dropped_on_success() {
+        let mut retried = outcome("A", true, LoginResult::Success);
+        retried.attempts = 2;
+        let report = RunReport::build(&[retried], &errors(&[("A", "login: proxy timed out")]));
+        assert_eq!(report.status, RunStatus::Success);
+        assert_eq!(report.characters[0].error, None);
+    }
+
+    #[test]
+    fn total_failure() {
+        let report = RunReport::build(
+            &[
+                outcome("A", true, LoginResult::Failed),
+                outcome("B", false, LoginResult::NotAttempted),
+            ],
+            &HashMap::new(),
+        );
+        assert_eq!(report.status, RunStatus::TotalFailure);
+        assert_eq!((report.succeeded, report.failed), (0, 2));
+    }
+
+    #[test]
+    fn not_attempted_counts_as_failed() {
+        let report = RunReport::build(
+            &[
+                outcome("A", true, LoginResult::Success),
+                outcome("B", true, LoginResult::NotAttempted),
+                outcome("C", false, LoginResult::NotAttempted),
+            ],
+            &HashMap::new(),
+        );
+        assert_eq!(report.status, RunStatus::PartialFailure);
+        assert_eq!((report.succeeded, report.failed), (1, 2));
+        assert!(!report.characters[1].succeeded());
+        assert_eq!(report.characters[2].attempts, 0);
+    }
+
+    #[test]
+    fn table_lists_each_character() {
+        let report = RunReport::build(
+            &[
+                outcome("MyWarrior", true, LoginResult::Success),
+                outcome("MyMule", false, LoginResult::NotAttempted),
+            ],
+            &errors(&[("MyMule", "launch: Windower not found")]),
+        );
+        let table = report.render_table();
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].starts_with("Character  Launch  Login"));
+        assert_eq!(lines[1], "-".repeat("Character".len() + 68));
+        assert_eq!(
+            lines[2].split_whitespace().collect::<Vec<_>>(),
+            ["MyWarrior", "ok", "ok", "1", "4.2s", "12.3s", "-", "-"]
+        );
+        assert!(lines[3].starts_with("MyMule     failed  not attempted  0"));
+        assert!(lines[3].ends_with("launch: Windower not found"));
+        assert_eq!(lines.last(), Some(&"1 succeeded, 1 failed"));
+    }
+
+    #[test]
+    fn markdown_escapes_pipes_in_errors() {
+        let report = RunReport::build(
+            &[outcome("A", true, LoginResult::Failed)],
+            &errors(&[("A", "login: a|b")]),
+        );
+        let markdown = report.render_markdown();
+        assert!(markdown.contains("- Status: total failure"));
+        assert!(markdown.contains("| A | 2 | ok | failed | 1 | 4.2s | - | - | login: a\\|b |"));
+    }
+
+    #[test]
+    fn json_uses_milliseconds_and_hides_the_instance() {
+        let mut success = outcome("A", true, LoginResult::Success);
+        success.instance = Some(Instance { pid: 42, window: 7 });
+        let report = RunReport::build(&[success], &HashMap::new());
+        let value = serde_json::to_value(&report).unwrap();
+        assert_eq!(value["status"], "success");
+        assert_eq!(value["succeeded"], 1);
+        let character = &value["characters"][0];
+        assert_eq!(character["login"], "success");
+        assert_eq!(character["time_to_window_ms"], 4200);
+        assert_eq!(character["time_to_proxy_ms"], 12345);
+        assert!(character["time_to_game_ms"].is_null());
+        assert!(character.get("instance").is_none());
+    }
+}
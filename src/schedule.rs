@@ -0,0 +1,709 @@
+use crate::clock::Clock;
+use crate::config::{Config, Overlap, ScheduleConfig, ScheduleTimezone};
+use crate::events::{Event, Output};
+use crate::launcher::Launcher;
+use chrono::{
+    DateTime, Datelike, Duration as TimeDelta, Local, LocalResult, NaiveDate, NaiveDateTime,
+    TimeZone, Timelike, Utc,
+};
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+/// Longest single sleep between schedule checks, so a changed system clock or a
+/// machine waking from sleep is noticed within a minute
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// How far ahead to look for a matching time before deciding an expression never fires.
+/// Eight years covers Feb 29 across a skipped leap year (e.g. 2096 to 2104).
+const SEARCH_YEARS: i32 = 8;
+
+const MONTH_NAMES: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAY_NAMES: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A parsed five-field cron expression: minute, hour, day of month, month, day of week
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Day of month started with `*`; see `matches_day`
+    any_day: bool,
+    /// Day of week started with `*`
+    any_weekday: bool,
+}
+
+impl Cron {
+    /// Parse an expression like `30 18 * * fri`. Supports `*`, lists, ranges, `/step`,
+    /// month and weekday names, and the macros `@hourly`, `@daily`, `@weekly`, `@monthly`, `@yearly`.
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let expanded = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other if other.starts_with('@') => return Err(format!("unknown macro '{}'", other)),
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!(
+                "expected 5 fields (minute hour day month weekday), found {}",
+                fields.len()
+            ));
+        };
+
+        let mut weekdays = parse_field(weekday, "weekday", 0, 7, WEEKDAY_NAMES)?;
+        // Both 0 and 7 mean Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays & !(1 << 7)) | 1;
+        }
+        Ok(Self {
+            minutes: parse_field(minute, "minute", 0, 59, &[])?,
+            hours: parse_field(hour, "hour", 0, 23, &[])?,
+            days: parse_field(day, "day", 1, 31, &[])?,
+            months: parse_field(month, "month", 1, 12, MONTH_NAMES)?,
+            weekdays,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+
+    /// Cron's day rule: when both day of month and day of week are restricted,
+    /// a day matching either one fires
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    /// First time strictly after `after` that matches, in `after`'s time zone.
+    ///
+    /// Times are matched against the zone's wall clock. A time skipped by a DST jump fires
+    /// at the first minute after the jump; a time repeated when clocks go back fires once,
+    /// on its first occurrence.
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let tz = after.timezone();
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)? + TimeDelta::minutes(1);
+        let last_year = start.year() + SEARCH_YEARS;
+        let mut t = start;
+
+        while t.year() <= last_year {
+            if self.months & (1 << t.month()) == 0 {
+                t = first_of_next_month(t)?;
+                continue;
+            }
+            if !self.matches_day(t.date()) {
+                t = t.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if self.hours & (1 << t.hour()) == 0 {
+                t = t.with_minute(0)? + TimeDelta::hours(1);
+                continue;
+            }
+            if self.minutes & (1 << t.minute()) == 0 {
+                t += TimeDelta::minutes(1);
+                continue;
+            }
+
+            let found = match tz.from_local_datetime(&t) {
+                LocalResult::Single(time) => Some(time),
+                // Not every zone lists the earlier of the two first
+                LocalResult::Ambiguous(a, b) => Some(a.min(b)),
+                LocalResult::None => after_gap(&tz, t),
+            };
+            if let Some(time) = found.filter(|time| time > after) {
+                return Some(time);
+            }
+            t += TimeDelta::minutes(1);
+        }
+        None
+    }
+}
+
+/// First valid local time after `t`, which falls in a DST gap
+fn after_gap<Tz: TimeZone>(tz: &Tz, mut t: NaiveDateTime) -> Option<DateTime<Tz>> {
+    // Gaps are at most a few hours; give up after a day
+    for _ in 0..24 * 60 {
+        t += TimeDelta::minutes(1);
+        if let Some(time) = tz.from_local_datetime(&t).earliest() {
+            return Some(time);
+        }
+    }
+    None
+}
+
+fn first_of_next_month(t: NaiveDateTime) -> Option<NaiveDateTime> {
+    let (year, month) = if t.month() == 12 {
+        (t.year() + 1, 1)
+    } else {
+        (t.year(), t.month() + 1)
+    };
+    NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)
+}
+
+/// Parse one field into a bitmask where bit `n` is set if value `n` matches
+fn parse_field(field: &str, name: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |s: &str| -> Result<u32, String> {
+        let lower = s.to_ascii_lowercase();
+        if let Some(i) = names.iter().position(|n| *n == lower) {
+            // Month names count from 1, weekday names from 0, like their numbers
+            return Ok(i as u32 + min);
+        }
+        let n: u32 = s.parse().map_err(|_| format!("invalid {} '{}'", name, s))?;
+        if n < min || n > max {
+            return Err(format!("{} {} is out of range ({}-{})", name, n, min, max));
+        }
+        Ok(n)
+    };
+
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("invalid step '{}' in {} field", step, name))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (value(a)?, value(b)?),
+                // `5/15` means every 15 starting at 5
+                None if step > 1 => (value(range)?, max),
+                None => {
+                    let n = value(range)?;
+                    (n, n)
+                }
+            },
+        };
+        if from > to {
+            return Err(format!("{} range {} is backwards", name, range));
+        }
+        for n in (from..=to).step_by(step as usize) {
+            mask |= 1 << n;
+        }
+    }
+    Ok(mask)
+}
+
+/// When `cron` next fires after `after`, in its configured time zone
+pub fn next_fire(
+    cron: &Cron,
+    timezone: ScheduleTimezone,
+    after: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    match timezone {
+        ScheduleTimezone::Utc => cron.next_after(&after),
+        ScheduleTimezone::Local => cron
+            .next_after(&after.with_timezone(&Local))
+            .map(|t| t.with_timezone(&Utc)),
+    }
+}
+
+/// A fire time as shown to the user, in the schedule's own time zone
+pub fn display_time(time: DateTime<Utc>, timezone: ScheduleTimezone) -> String {
+    const FORMAT: &str = "%Y-%m-%d %H:%M %:z";
+    match timezone {
+        ScheduleTimezone::Utc => time.format(FORMAT).to_string(),
+        ScheduleTimezone::Local => time.with_timezone(&Local).format(FORMAT).to_string(),
+    }
+}
+
+struct Entry<'c> {
+    schedule: &'c ScheduleConfig,
+    cron: Cron,
+    next: Option<DateTime<Utc>>,
+}
+
+/// Logs characters in at the times in the config's `schedule` section
+pub struct Scheduler<'a> {
+    config: &'a Config,
+    entries: Vec<Entry<'a>>,
+    /// Start and end of the last run, to tell which fire times it overlapped
+    last_run: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    launcher: &'a mut dyn Launcher,
+    out: &'a Output,
+}
+
+impl<'a> Scheduler<'a> {
+    /// Parse every schedule and work out its first fire time after `now`
+    pub fn new(
+        config: &'a Config,
+        launcher: &'a mut dyn Launcher,
+        out: &'a Output,
+        now: DateTime<Utc>,
+    ) -> Result<Self, String> {
+        let entries = config
+            .schedule
+            .iter()
+            .map(|schedule| {
+                let cron = Cron::parse(&schedule.cron)
+                    .map_err(|e| format!("schedule '{}': {}", schedule.cron, e))?;
+                let next = next_fire(&cron, schedule.timezone, now);
+                Ok(Entry {
+                    schedule,
+                    cron,
+                    next,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self {
+            config,
+            entries,
+            last_run: None,
+            launcher,
+            out,
+        })
+    }
+
+    /// Earliest upcoming fire time across all schedules
+    pub fn next_fire(&self) -> Option<DateTime<Utc>> {
+        self.entries.iter().filter_map(|e| e.next).min()
+    }
+
+    /// Report the next run that will happen
+    pub fn announce_next(&self) {
+        let Some((entry, next)) = self
+            .entries
+            .iter()
+            .filter_map(|e| e.next.map(|next| (e, next)))
+            .min_by_key(|(_, next)| *next)
+        else {
+            return;
+        };
+        let characters = self.config.filter_characters(&entry.schedule.characters);
+        self.out.emit(Event::ScheduledRunNext {
+            schedule: &entry.schedule.cron,
+            at: display_time(next, entry.schedule.timezone),
+            characters: characters.iter().map(|ch| ch.name.as_str()).collect(),
+        });
+    }
+
+    /// Run every schedule that is due. Schedules that came due while the previous run was
+    /// still going are skipped or run now, depending on their `overlap` setting. Schedules
+    /// due at the same time share one run. Returns whether a run happened.
+    pub fn tick(&mut self, clock: &dyn Clock) -> bool {
+        let now = clock.utc_now();
+        let mut names: Vec<String> = Vec::new();
+        let mut fired: Vec<&str> = Vec::new();
+
+        for entry in &mut self.entries {
+            let Some(due) = entry.next.filter(|t| *t <= now) else {
+                continue;
+            };
+            // Missed fire times collapse into one; the next is counted from now
+            entry.next = next_fire(&entry.cron, entry.schedule.timezone, now);
+
+            let overlapped = self
+                .last_run
+                .is_some_and(|(start, end)| due >= start && due < end);
+            if overlapped && entry.schedule.overlap == Overlap::Skip {
+                self.out.emit(Event::ScheduledRunSkipped {
+                    schedule: &entry.schedule.cron,
+                    at: display_time(due, entry.schedule.timezone),
+                });
+                continue;
+            }
+
+            fired.push(&entry.schedule.cron);
+            if entry.schedule.characters.is_empty() {
+                names = self
+                    .config
+                    .characters
+                    .iter()
+                    .map(|ch| ch.name.clone())
+                    .collect();
+            } else {
+                names.extend(entry.schedule.characters.iter().cloned());
+            }
+        }
+
+        if fired.is_empty() {
+            return false;
+        }
+
+        let characters = self.config.filter_characters(&names);
+        self.out
+            .emit(Event::ScheduledRunStarted { schedules: fired });
+        self.launcher
+            .login(self.config, &characters, &AtomicBool::new(false));
+        self.last_run = Some((now, clock.utc_now()));
+        true
+    }
+
+    /// Wait for and run scheduled logins until the process is stopped
+    pub fn run(&mut self, clock: &dyn Clock) -> ! {
+        let mut announce = true;
+        loop {
+            if self.tick(clock) {
+                // Check again before announcing; more may have come due during the run
+                announce = true;
+                continue;
+            }
+            if announce {
+                self.announce_next();
+                announce = false;
+            }
+            let wait = self
+                .next_fire()
+                .and_then(|next| (next - clock.utc_now()).to_std().ok())
+                .unwrap_or(MAX_SLEEP);
+            clock.sleep(wait.min(MAX_SLEEP));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::config::{Character, ConfigFormat};
+    use crate::events::OutputFormat;
+    use crate::launcher::MockLauncher;
+    use crate::report::RunReport;
+    use chrono::{FixedOffset, MappedLocalTime};
+    use serde_json::Value;
+    use std::sync::{Arc, Mutex};
+
+    /// Values set in a field's bitmask
+    fn bits(mask: u64) -> Vec<u32> {
+        (0..64).filter(|n| mask & (1 << n) != 0).collect()
+    }
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text).unwrap().with_timezone(&Utc)
+    }
+
+    fn next(expr: &str, after: &str) -> String {
+        let next = Cron::parse(expr).unwrap().next_after(&utc(after)).unwrap();
+        next.format("%Y-%m-%d %H:%M %a").to_string()
+    }
+
+    #[test]
+    fn fields_accept_lists_ranges_steps_and_names() {
+        let cron = Cron::parse("*/15 9-17/4 1,15,31 jan,JUL-sep mon-fri").unwrap();
+        assert_eq!(bits(cron.minutes), [0, 15, 30, 45]);
+        assert_eq!(bits(cron.hours), [9, 13, 17]);
+        assert_eq!(bits(cron.days), [1, 15, 31]);
+        assert_eq!(bits(cron.months), [1, 7, 8, 9]);
+        assert_eq!(bits(cron.weekdays), [1, 2, 3, 4, 5]);
+        assert!(!cron.any_day && !cron.any_weekday);
+
+        // `n/step` runs from n to the end of the range
+        assert_eq!(bits(Cron::parse("5/20 * * * *").unwrap().minutes), [5, 25, 45]);
+        let every = Cron::parse("* * * * *").unwrap();
+        assert_eq!(bits(every.hours), (0..=23).collect::<Vec<_>>());
+        assert_eq!(bits(every.weekdays), (0..=7).collect::<Vec<_>>()[..7]);
+        assert!(every.any_day && every.any_weekday);
+    }
+
+    #[test]
+    fn zero_and_seven_both_mean_sunday() {
+        for weekday in ["0", "7", "sun", "SUN", "0,7"] {
+            let cron = Cron::parse(&format!("0 0 * * {}", weekday)).unwrap();
+            assert_eq!(bits(cron.weekdays), [0], "{}", weekday);
+        }
+        assert_eq!(bits(Cron::parse("0 0 * * 5-7").unwrap().weekdays), [0, 5, 6]);
+    }
+
+    #[test]
+    fn macros_expand_to_their_expressions() {
+        assert_eq!(Cron::parse("@daily").unwrap(), Cron::parse("0 0 * * *").unwrap());
+        assert_eq!(Cron::parse("@midnight").unwrap(), Cron::parse("0 0 * * *").unwrap());
+        assert_eq!(Cron::parse(" @weekly ").unwrap(), Cron::parse("0 0 * * sun").unwrap());
+        assert_eq!(Cron::parse("@hourly").unwrap(), Cron::parse("0 * * * *").unwrap());
+        assert_eq!(Cron::parse("@monthly").unwrap(), Cron::parse("0 0 1 * *").unwrap());
+        assert_eq!(Cron::parse("@annually").unwrap(), Cron::parse("0 0 1 jan *").unwrap());
+    }
+
+    #[test]
+    fn bad_expressions_say_what_is_wrong() {
+        for (expr, error) in [
+            ("0 0 * *", "expected 5 fields (minute hour day month weekday), found 4"),
+            ("60 0 * * *", "minute 60 is out of range (0-59)"),
+            ("0 0 0 * *", "day 0 is out of range (1-31)"),
+            ("0 0 * 13 *", "month 13 is out of range (1-12)"),
+            ("0 0 * * 8", "weekday 8 is out of range (0-7)"),
+            ("0 0 * * fry", "invalid weekday 'fry'"),
+            ("0 17-9 * * *", "hour range 17-9 is backwards"),
+            ("*/0 * * * *", "invalid step '0' in minute field"),
+            ("@reboot", "unknown macro '@reboot'"),
+        ] {
+            assert_eq!(Cron::parse(expr).unwrap_err(), error, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn next_fire_is_strictly_after_the_given_time() {
+        // 2026-01-01 is a Thursday
+        assert_eq!(next("30 18 * * fri", "2026-01-01T12:00:00Z"), "2026-01-02 18:30 Fri");
+        assert_eq!(next("30 18 * * fri", "2026-01-02T18:30:00Z"), "2026-01-09 18:30 Fri");
+        assert_eq!(next("30 18 * * fri", "2026-01-02T18:29:59Z"), "2026-01-02 18:30 Fri");
+        assert_eq!(next("0 0 1 * *", "2026-12-15T00:00:00Z"), "2027-01-01 00:00 Fri");
+        assert_eq!(next("0 12 29 feb *", "2026-03-01T00:00:00Z"), "2028-02-29 12:00 Tue");
+        assert_eq!(next("0 0 31 * *", "2026-04-01T00:00:00Z"), "2026-05-31 00:00 Sun");
+        assert!(Cron::parse("0 0 30 feb *").unwrap().next_after(&Utc::now()).is_none());
+    }
+
+    #[test]
+    fn day_of_month_and_weekday_either_match_when_both_are_set() {
+        // The 13th, or any Friday
+        let cron = "0 9 13 * fri";
+        assert_eq!(next(cron, "2026-03-01T00:00:00Z"), "2026-03-06 09:00 Fri");
+        assert_eq!(next(cron, "2026-03-06T09:00:00Z"), "2026-03-13 09:00 Fri");
+        assert_eq!(next(cron, "2026-04-10T09:00:00Z"), "2026-04-13 09:00 Mon");
+        // With a `*` in either, both must match: a 13th that is a Friday
+        assert_eq!(next("0 9 13 * *", "2026-04-01T00:00:00Z"), "2026-04-13 09:00 Mon");
+        assert_eq!(next("0 9 * * fri", "2026-04-10T09:00:00Z"), "2026-04-17 09:00 Fri");
+        assert_eq!(next("0 9 */1 * fri", "2026-04-10T09:00:00Z"), "2026-04-17 09:00 Fri");
+    }
+
+    #[test]
+    fn fixed_offset_zones_match_their_wall_clock() {
+        let tokyo = FixedOffset::east_opt(9 * 3600).unwrap();
+        let after = utc("2026-01-01T00:00:00Z").with_timezone(&tokyo);
+        let next = Cron::parse("0 8 * * *").unwrap().next_after(&after).unwrap();
+        // 00:00 UTC is already 09:00 in Tokyo
+        assert_eq!(next.to_rfc3339(), "2026-01-02T08:00:00+09:00");
+        assert_eq!(next.with_timezone(&Utc), utc("2026-01-01T23:00:00Z"));
+    }
+
+    /// Central European time: +01:00, or +02:00 from 2026-03-29 01:00 UTC (02:00 local
+    /// jumps to 03:00) until 2026-10-25 01:00 UTC (03:00 local goes back to 02:00)
+    #[derive(Debug, Clone, Copy)]
+    struct Cet;
+
+    impl Cet {
+        fn at(instant: &NaiveDateTime) -> FixedOffset {
+            let starts = utc("2026-03-29T01:00:00Z").naive_utc();
+            let ends = utc("2026-10-25T01:00:00Z").naive_utc();
+            let hours = if (starts..ends).contains(instant) { 2 } else { 1 };
+            FixedOffset::east_opt(hours * 3600).unwrap()
+        }
+    }
+
+    impl TimeZone for Cet {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            Cet
+        }
+
+        fn offset_from_local_date(&self, _: &NaiveDate) -> MappedLocalTime<FixedOffset> {
+            unimplemented!("only date-times are used")
+        }
+
+        fn offset_from_local_datetime(
+            &self,
+            local: &NaiveDateTime,
+        ) -> MappedLocalTime<FixedOffset> {
+            let valid: Vec<FixedOffset> = [1, 2]
+                .map(|h| FixedOffset::east_opt(h * 3600).unwrap())
+                .into_iter()
+                .filter(|offset| Cet::at(&(*local - *offset)) == *offset)
+                .collect();
+            match valid[..] {
+                [] => LocalResult::None,
+                [one] => LocalResult::Single(one),
+                // Listed later one first, which `next_after` must not rely on
+                [a, b] => LocalResult::Ambiguous(a, b),
+                _ => unreachable!(),
+            }
+        }
+
+        fn offset_from_utc_date(&self, _: &NaiveDate) -> FixedOffset {
+            unimplemented!("only date-times are used")
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            Cet::at(utc)
+        }
+    }
+
+    fn next_cet(expr: &str, after_utc: &str) -> String {
+        let after = utc(after_utc).with_timezone(&Cet);
+        let next = Cron::parse(expr).unwrap().next_after(&after).unwrap();
+        format!("{} = {}", next.to_rfc3339(), next.with_timezone(&Utc).format("%H:%M UTC"))
+    }
+
+    #[test]
+    fn time_skipped_by_dst_fires_right_after_the_jump() {
+        // 02:30 doesn't exist on 2026-03-29
+        assert_eq!(
+            next_cet("30 2 * * *", "2026-03-28T12:00:00Z"),
+            "2026-03-29T03:00:00+02:00 = 01:00 UTC"
+        );
+        assert_eq!(
+            next_cet("30 2 * * *", "2026-03-29T01:00:00Z"),
+            "2026-03-30T02:30:00+02:00 = 00:30 UTC"
+        );
+        // Hours either side of the gap are unaffected
+        assert_eq!(
+            next_cet("30 1,3 * * *", "2026-03-29T00:00:00Z"),
+            "2026-03-29T01:30:00+01:00 = 00:30 UTC"
+        );
+        assert_eq!(
+            next_cet("30 1,3 * * *", "2026-03-29T00:30:00Z"),
+            "2026-03-29T03:30:00+02:00 = 01:30 UTC"
+        );
+    }
+
+    #[test]
+    fn time_repeated_by_dst_fires_once() {
+        // 02:30 happens at 00:30 UTC (+02:00) and again at 01:30 UTC (+01:00)
+        assert_eq!(
+            next_cet("30 2 * * *", "2026-10-24T12:00:00Z"),
+            "2026-10-25T02:30:00+02:00 = 00:30 UTC"
+        );
+        for after in ["2026-10-25T00:30:00Z", "2026-10-25T01:10:00Z", "2026-10-25T01:30:00Z"] {
+            assert_eq!(
+                next_cet("30 2 * * *", after),
+                "2026-10-26T02:30:00+01:00 = 01:30 UTC",
+                "after {}",
+                after
+            );
+        }
+        // Hourly schedules too: the second 02:00 is skipped, 03:00 comes two hours later
+        assert_eq!(
+            next_cet("0 * * * *", "2026-10-25T00:30:00Z"),
+            "2026-10-25T03:00:00+01:00 = 02:00 UTC"
+        );
+    }
+
+    /// Runs take `duration` on the fake clock
+    struct SlowLauncher<'c> {
+        inner: MockLauncher,
+        clock: &'c FakeClock,
+        duration: Duration,
+    }
+
+    impl Launcher for SlowLauncher<'_> {
+        fn login(&mut self, config: &Config, characters: &[&Character], cancel: &AtomicBool) -> RunReport {
+            self.clock.advance(self.duration);
+            self.inner.login(config, characters, cancel)
+        }
+    }
+
+    fn config(schedule: Value) -> Config {
+        let json = serde_json::json!({
+            "version": 2,
+            "schedule": schedule,
+            "characters": [
+                {"name": "MyWarrior", "slot": 1, "password": "Passw0rd!"},
+                {"name": "MyMage", "slot": 2, "password": "Passw0rd!"},
+                {"name": "MyThief", "slot": 3, "password": "Passw0rd!"}
+            ]
+        });
+        Config::parse(&json.to_string(), ConfigFormat::Json).unwrap()
+    }
+
+    fn recording_output() -> (Output, Arc<Mutex<Vec<Value>>>) {
+        let out = Output::new(OutputFormat::Json);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        out.subscribe(move |e| sink.lock().unwrap().push(serde_json::to_value(e).unwrap()));
+        (out, events)
+    }
+
+    fn minutes(n: u64) -> Duration {
+        Duration::from_secs(n * 60)
+    }
+
+    #[test]
+    fn runs_that_come_due_during_a_run_are_skipped_or_queued() {
+        let config = config(serde_json::json!([
+            {"cron": "0 * * * *", "characters": ["MyWarrior"], "timezone": "utc", "overlap": "skip"},
+            {"cron": "30 * * * *", "characters": ["MyMage"], "timezone": "utc", "overlap": "queue"},
+            {"cron": "15 1 * * *", "characters": ["MyThief"], "timezone": "utc"}
+        ]));
+        let clock = FakeClock::new(utc("2026-01-01T00:00:00Z"));
+        let (out, events) = recording_output();
+        let mut launcher = SlowLauncher {
+            inner: MockLauncher::default(),
+            clock: &clock,
+            duration: minutes(70),
+        };
+        let mut scheduler = Scheduler::new(&config, &mut launcher, &out, clock.utc_now()).unwrap();
+        assert_eq!(scheduler.next_fire(), Some(utc("2026-01-01T00:30:00Z")));
+        assert!(!scheduler.tick(&clock));
+
+        // 00:30 to 01:40: the 01:00 and 01:15 runs are skipped, the 01:30 run is queued
+        clock.advance(minutes(30));
+        assert!(scheduler.tick(&clock));
+        assert!(scheduler.tick(&clock));
+        assert_eq!(clock.utc_now(), utc("2026-01-01T02:50:00Z"));
+        drop(scheduler);
+
+        let names = |run: &[&str]| run.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(launcher.inner.runs, [names(&["MyMage"]), names(&["MyMage"])]);
+        let events = events.lock().unwrap();
+        let skipped: Vec<&Value> = events
+            .iter()
+            .filter(|e| e["event"] == "scheduled_run_skipped")
+            .map(|e| &e["schedule"])
+            .collect();
+        assert_eq!(skipped, ["0 * * * *", "15 1 * * *"]);
+        let skipped_at = events.iter().find(|e| e["event"] == "scheduled_run_skipped").unwrap();
+        assert_eq!(skipped_at["at"], "2026-01-01 01:00 +00:00");
+    }
+
+    #[test]
+    fn schedules_due_together_share_one_run() {
+        let config = config(serde_json::json!([
+            {"cron": "0 9 * * *", "characters": ["MyThief"], "timezone": "utc"},
+            {"cron": "0 9 * * mon-fri", "characters": ["MyWarrior"], "timezone": "utc"},
+            {"cron": "0 9 * * sat", "timezone": "utc"}
+        ]));
+        // A Friday
+        let clock = FakeClock::new(utc("2026-01-02T08:00:00Z"));
+        let out = Output::new(OutputFormat::Json);
+        let mut launcher = MockLauncher::default();
+        let mut scheduler = Scheduler::new(&config, &mut launcher, &out, clock.utc_now()).unwrap();
+
+        clock.advance(minutes(60));
+        assert!(scheduler.tick(&clock));
+        assert!(!scheduler.tick(&clock));
+        // Saturday's schedule has no characters, so it runs everyone
+        clock.advance(Duration::from_secs(24 * 3600));
+        assert!(scheduler.tick(&clock));
+        drop(scheduler);
+
+        let names = |run: &[&str]| run.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            launcher.runs,
+            [names(&["MyWarrior", "MyThief"]), names(&["MyWarrior", "MyMage", "MyThief"])]
+        );
+    }
+
+    #[test]
+    fn missed_fire_times_collapse_into_one_run() {
+        let config = config(serde_json::json!([
+            {"cron": "0 * * * *", "timezone": "utc", "characters": ["MyMage"]}
+        ]));
+        let clock = FakeClock::new(utc("2026-01-01T00:00:00Z"));
+        let out = Output::new(OutputFormat::Json);
+        let mut launcher = MockLauncher::default();
+        let mut scheduler = Scheduler::new(&config, &mut launcher, &out, clock.utc_now()).unwrap();
+
+        // The machine slept through three fire times
+        clock.advance(minutes(3 * 60 + 20));
+        assert!(scheduler.tick(&clock));
+        assert!(!scheduler.tick(&clock));
+        assert_eq!(scheduler.next_fire(), Some(utc("2026-01-01T04:00:00Z")));
+        drop(scheduler);
+        assert_eq!(launcher.runs.len(), 1);
+    }
+}
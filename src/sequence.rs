@@ -0,0 +1,285 @@
+use crate::clock::Clock;
+use crate::input::KeySender;
+use crate::typing::InputEvent;
+use std::time::{Duration, Instant};
+
+/// One batch of keys in a `Sequence`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// What the keys are sent at, e.g. "the title screen". Names the step in events
+    /// and errors.
+    pub name: &'static str,
+    /// Time the game needs after the previous step, or the start, before these keys
+    pub delay: Duration,
+    pub events: Vec<InputEvent>,
+}
+
+/// What a `Sequence::poll` did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Progress {
+    /// Nothing sent: the next step isn't due, or its window can't take keys yet
+    Waiting,
+    /// The named step's keys were sent
+    Sent(&'static str),
+    /// The named step's window couldn't take keys within the timeout
+    Failed { step: &'static str, error: String },
+}
+
+/// Sends scripted steps to one window, each after its delay. A step whose window
+/// isn't ready (e.g. can't be brought to the front) is retried on later polls, for
+/// up to `timeout` past its delay. Keys and time are passed in, so a sequence can be
+/// driven by a fake clock and a recording `KeySender`.
+pub struct Sequence {
+    steps: Vec<Step>,
+    next: usize,
+    timeout: Duration,
+    /// When the previous step was sent, or the sequence started
+    since: Instant,
+}
+
+impl Sequence {
+    pub fn new(steps: Vec<Step>, timeout: Duration, started_at: Instant) -> Self {
+        Self {
+            steps,
+            next: 0,
+            timeout,
+            since: started_at,
+        }
+    }
+
+    /// When the next step is due, `None` once finished
+    pub fn due(&self) -> Option<Instant> {
+        self.steps
+            .get(self.next)
+            .map(|step| self.since + step.delay)
+    }
+
+    /// Whether every step was sent, or one failed
+    pub fn finished(&self) -> bool {
+        self.next >= self.steps.len()
+    }
+
+    /// Send the next step if it is due and its window is ready
+    pub fn poll(&mut self, keys: &dyn KeySender, clock: &dyn Clock) -> Progress {
+        let Some(step) = self.steps.get(self.next) else {
+            return Progress::Waiting;
+        };
+        let due = self.since + step.delay;
+        if clock.now() < due {
+            return Progress::Waiting;
+        }
+        match keys.ready(step.name) {
+            Ok(()) => {
+                keys.send(&step.events);
+                self.next += 1;
+                self.since = clock.now();
+                Progress::Sent(step.name)
+            }
+            Err(e) if clock.now().saturating_duration_since(due) >= self.timeout => {
+                self.next = self.steps.len();
+                Progress::Failed {
+                    step: step.name,
+                    error: format!("{} (tried for {}s)", e, self.timeout.as_secs()),
+                }
+            }
+            Err(e) => {
+                log::debug!("Retrying {}: {}", step.name, e);
+                Progress::Waiting
+            }
+        }
+    }
+}
+
+/// Press and release the key with scan code `scan`, holding it `hold_ms`
+pub fn press(scan: u16, hold_ms: u64) -> Vec<InputEvent> {
+    vec![
+        InputEvent::Scan { scan, up: false },
+        InputEvent::Pause(hold_ms),
+        InputEvent::Scan { scan, up: true },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::config::LobbyConfig;
+    use crate::lobby;
+    use std::sync::Mutex;
+
+    /// Records each batch of keys; the window refuses keys for the first `refuse`
+    /// `ready` checks
+    #[derive(Default)]
+    struct RecordingKeys {
+        refuse: Mutex<u32>,
+        checked: Mutex<Vec<String>>,
+        sent: Mutex<Vec<Vec<InputEvent>>>,
+    }
+
+    impl RecordingKeys {
+        fn refusing(refuse: u32) -> Self {
+            Self {
+                refuse: Mutex::new(refuse),
+                ..Self::default()
+            }
+        }
+
+        fn sent(&self) -> Vec<Vec<InputEvent>> {
+            self.sent.lock().unwrap().clone()
+        }
+    }
+
+    impl KeySender for RecordingKeys {
+        fn ready(&self, step: &str) -> Result<(), String> {
+            self.checked.lock().unwrap().push(step.to_string());
+            let mut refuse = self.refuse.lock().unwrap();
+            if *refuse > 0 {
+                *refuse -= 1;
+                return Err("another window stayed in front".to_string());
+            }
+            Ok(())
+        }
+
+        fn press_key(&self, _vk: u16, _hold_ms: u64) {}
+
+        fn scroll_up(&self) {}
+
+        fn send(&self, events: &[InputEvent]) {
+            self.sent.lock().unwrap().push(events.to_vec());
+        }
+    }
+
+    fn secs(n: u64) -> Duration {
+        Duration::from_secs(n)
+    }
+
+    fn step(name: &'static str, delay: Duration, scan: u16) -> Step {
+        Step {
+            name,
+            delay,
+            events: press(scan, 100),
+        }
+    }
+
+    /// Poll every second until finished, returning what happened at each second
+    fn run(
+        sequence: &mut Sequence,
+        keys: &RecordingKeys,
+        clock: &FakeClock,
+    ) -> Vec<(u64, Progress)> {
+        let start = clock.elapsed();
+        let mut progress = Vec::new();
+        while !sequence.finished() {
+            match sequence.poll(keys, clock) {
+                Progress::Waiting => {}
+                p => progress.push(((clock.elapsed() - start).as_secs(), p)),
+            }
+            clock.advance(secs(1));
+        }
+        progress
+    }
+
+    #[test]
+    fn sends_each_step_after_its_delay_from_the_last() {
+        let clock = FakeClock::new(chrono::Utc::now());
+        let keys = RecordingKeys::default();
+        let steps = vec![step("first", secs(5), 1), step("second", secs(3), 2)];
+        let mut sequence = Sequence::new(steps, secs(30), clock.now());
+        assert_eq!(sequence.due(), Some(clock.now() + secs(5)));
+
+        assert_eq!(
+            run(&mut sequence, &keys, &clock),
+            [(5, Progress::Sent("first")), (8, Progress::Sent("second"))]
+        );
+        assert_eq!(keys.sent(), [press(1, 100), press(2, 100)]);
+        assert_eq!(*keys.checked.lock().unwrap(), ["first", "second"]);
+        assert_eq!(sequence.due(), None);
+        assert_eq!(sequence.poll(&keys, &clock), Progress::Waiting);
+    }
+
+    #[test]
+    fn nothing_is_sent_or_checked_before_a_step_is_due() {
+        let clock = FakeClock::new(chrono::Utc::now());
+        let keys = RecordingKeys::default();
+        let mut sequence = Sequence::new(vec![step("first", secs(5), 1)], secs(30), clock.now());
+        clock.advance(secs(4));
+        assert_eq!(sequence.poll(&keys, &clock), Progress::Waiting);
+        assert!(keys.checked.lock().unwrap().is_empty());
+        assert!(keys.sent().is_empty());
+    }
+
+    #[test]
+    fn retries_a_step_while_its_window_is_not_ready() {
+        let clock = FakeClock::new(chrono::Utc::now());
+        let keys = RecordingKeys::refusing(3);
+        let steps = vec![step("first", secs(5), 1), step("second", secs(2), 2)];
+        let mut sequence = Sequence::new(steps, secs(30), clock.now());
+
+        // The next step's delay runs from when the late one was sent
+        assert_eq!(
+            run(&mut sequence, &keys, &clock),
+            [(8, Progress::Sent("first")), (10, Progress::Sent("second"))]
+        );
+        assert_eq!(keys.checked.lock().unwrap().len(), 5);
+        assert_eq!(keys.sent().len(), 2);
+    }
+
+    #[test]
+    fn fails_a_step_whose_window_stays_unready_past_the_timeout() {
+        let clock = FakeClock::new(chrono::Utc::now());
+        let keys = RecordingKeys::refusing(u32::MAX);
+        let steps = vec![step("first", secs(5), 1), step("second", secs(2), 2)];
+        let mut sequence = Sequence::new(steps, secs(10), clock.now());
+
+        assert_eq!(
+            run(&mut sequence, &keys, &clock),
+            [(
+                15,
+                Progress::Failed {
+                    step: "first",
+                    error: "another window stayed in front (tried for 10s)".to_string()
+                }
+            )]
+        );
+        // The rest of the sequence is dropped
+        assert!(sequence.finished());
+        assert!(keys.sent().is_empty());
+        assert!(keys.checked.lock().unwrap().iter().all(|s| s == "first"));
+    }
+
+    fn lobby_run(skip_opening: bool) -> Vec<(u64, Progress)> {
+        let clock = FakeClock::new(chrono::Utc::now());
+        let keys = RecordingKeys::default();
+        let config = LobbyConfig {
+            skip_opening,
+            ..LobbyConfig::default()
+        };
+        let mut sequence = Sequence::new(lobby::select_character(&config, 2), secs(30), clock.now());
+        run(&mut sequence, &keys, &clock)
+    }
+
+    #[test]
+    fn lobby_skipping_the_opening() {
+        assert_eq!(
+            lobby_run(true),
+            [
+                (5, Progress::Sent("the opening movie")),
+                (8, Progress::Sent("the title screen")),
+                (10, Progress::Sent("the character list")),
+                (12, Progress::Sent("the confirmation prompt")),
+            ]
+        );
+    }
+
+    #[test]
+    fn lobby_waiting_out_the_opening() {
+        assert_eq!(
+            lobby_run(false),
+            [
+                (20, Progress::Sent("the title screen")),
+                (22, Progress::Sent("the character list")),
+                (24, Progress::Sent("the confirmation prompt")),
+            ]
+        );
+    }
+}
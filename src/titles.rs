@@ -0,0 +1,190 @@
+use crate::config::{Character, TitleConfig};
+use crate::processes::WindowEntry;
+
+/// Title prefix of PlayOnline windows, before they are renamed for their character
+pub const POL_TITLE: &str = "PlayOnline Viewer";
+
+/// Title of the game window FFXI opens once PlayOnline hands over to it
+pub const GAME_TITLE: &str = "FINAL FANTASY XI";
+
+/// Placeholders available in title templates, written as `{name}`
+pub const TITLE_VARS: &[&str] = &["name", "slot", "job"];
+
+/// Placeholders in `template`, in order. Fails on an unclosed or stray brace.
+pub fn placeholders(template: &str) -> Result<Vec<&str>, String> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        if rest[start..].starts_with('}') {
+            return Err("'}' without a matching '{'".to_string());
+        }
+        let Some(len) = rest[start + 1..].find('}') else {
+            return Err("'{' without a matching '}'".to_string());
+        };
+        names.push(&rest[start + 1..start + 1 + len]);
+        rest = &rest[start + 1 + len + 1..];
+    }
+    Ok(names)
+}
+
+/// Check that `template` only uses known placeholders, and that every character has
+/// what it uses
+pub fn check_template(template: &str, characters: &[Character]) -> Result<(), String> {
+    let names = placeholders(template)?;
+    if let Some(unknown) = names.iter().find(|n| !TITLE_VARS.contains(n)) {
+        return Err(format!(
+            "unknown placeholder {{{}}} (expected one of {})",
+            unknown,
+            TITLE_VARS
+                .iter()
+                .map(|v| format!("{{{}}}", v))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    if names.contains(&"job") {
+        if let Some(ch) = characters.iter().find(|ch| ch.job.is_none()) {
+            return Err(format!("uses {{job}}, but character '{}' has no job", ch.name));
+        }
+    }
+    Ok(())
+}
+
+/// Fill in `template` for `character`. Assumes `check_template` passed.
+pub fn render(template: &str, character: &Character) -> String {
+    template
+        .replace("{name}", &character.name)
+        .replace("{slot}", &character.slot.to_string())
+        .replace("{job}", character.job.as_deref().unwrap_or_default())
+}
+
+/// The title `window` should get, if it is one of `character`'s PlayOnline or game
+/// windows still carrying the title the game gave it (e.g. because the game reset
+/// it after being renamed). `None` if it should be left alone.
+pub fn wanted_title(
+    titles: &TitleConfig,
+    character: &Character,
+    window: &WindowEntry,
+) -> Option<String> {
+    let template = if window.title.starts_with(GAME_TITLE) {
+        &titles.game
+    } else if window.title.starts_with(POL_TITLE) {
+        &titles.playonline
+    } else {
+        return None;
+    };
+    if template.is_empty() {
+        return None;
+    }
+    let title = render(template, character);
+    (window.title != title).then_some(title)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn character(name: &str, job: Option<&str>) -> Character {
+        serde_json::from_value(json!({ "name": name, "slot": 2, "job": job })).unwrap()
+    }
+
+    fn window(title: &str) -> WindowEntry {
+        WindowEntry {
+            id: 1,
+            pid: 100,
+            title: title.to_string(),
+        }
+    }
+
+    fn titles(playonline: &str, game: &str) -> TitleConfig {
+        TitleConfig {
+            playonline: playonline.to_string(),
+            game: game.to_string(),
+        }
+    }
+
+    #[test]
+    fn finds_placeholders_in_order() {
+        assert_eq!(placeholders("{name} ({job}) #{slot}"), Ok(vec!["name", "job", "slot"]));
+        assert_eq!(placeholders("no placeholders"), Ok(vec![]));
+        assert_eq!(placeholders("{}"), Ok(vec![""]));
+    }
+
+    #[test]
+    fn rejects_stray_and_unclosed_braces() {
+        let stray = Err("'}' without a matching '{'".to_string());
+        let unclosed = Err("'{' without a matching '}'".to_string());
+        assert_eq!(placeholders("name}"), stray);
+        assert_eq!(placeholders("{name}}"), stray);
+        assert_eq!(placeholders("{name"), unclosed);
+        assert_eq!(placeholders("{name} - {job"), unclosed);
+    }
+
+    #[test]
+    fn rejects_unknown_placeholders() {
+        let chars = [character("MyWarrior", Some("WAR"))];
+        let error = check_template("{name} {server}", &chars).unwrap_err();
+        assert_eq!(
+            error,
+            "unknown placeholder {server} (expected one of {name}, {slot}, {job})"
+        );
+        assert!(check_template("{Name}", &chars).is_err());
+        // Doubled braces aren't an escape
+        assert!(check_template("{{name}}", &chars).is_err());
+        assert_eq!(check_template("{name} {slot} {job}", &chars), Ok(()));
+    }
+
+    #[test]
+    fn job_must_be_set_for_every_character_when_used() {
+        let chars = [character("MyWarrior", Some("WAR")), character("MyMage", None)];
+        assert_eq!(
+            check_template("{name} ({job})", &chars).unwrap_err(),
+            "uses {job}, but character 'MyMage' has no job"
+        );
+        assert_eq!(check_template("{name}", &chars), Ok(()));
+    }
+
+    #[test]
+    fn renders_every_placeholder() {
+        let ch = character("MyWarrior", Some("WAR/NIN"));
+        assert_eq!(render("{name} {slot} {job} {name}", &ch), "MyWarrior 2 WAR/NIN MyWarrior");
+    }
+
+    #[test]
+    fn wanted_title_renames_untouched_windows() {
+        let config = titles("PlayOnline Viewer - {name}", "{name} ({job})");
+        let ch = character("MyWarrior", Some("WAR"));
+        assert_eq!(
+            wanted_title(&config, &ch, &window("PlayOnline Viewer")),
+            Some("PlayOnline Viewer - MyWarrior".to_string())
+        );
+        assert_eq!(
+            wanted_title(&config, &ch, &window("FINAL FANTASY XI")),
+            Some("MyWarrior (WAR)".to_string())
+        );
+        assert_eq!(wanted_title(&config, &ch, &window("Windower")), None);
+    }
+
+    #[test]
+    fn wanted_title_leaves_windows_already_renamed() {
+        let ch = character("MyWarrior", None);
+        let config = titles("PlayOnline Viewer - {name}", "FINAL FANTASY XI - {name}");
+        // Still starts with the game's own title, but already carries the name
+        assert_eq!(wanted_title(&config, &ch, &window("PlayOnline Viewer - MyWarrior")), None);
+        assert_eq!(wanted_title(&config, &ch, &window("FINAL FANTASY XI - MyWarrior")), None);
+        // Renamed for someone else, e.g. after a window was handed over
+        assert_eq!(
+            wanted_title(&config, &ch, &window("FINAL FANTASY XI - MyMage")),
+            Some("FINAL FANTASY XI - MyWarrior".to_string())
+        );
+    }
+
+    #[test]
+    fn empty_templates_leave_titles_alone() {
+        let ch = character("MyWarrior", None);
+        let config = titles("", "");
+        assert_eq!(wanted_title(&config, &ch, &window("PlayOnline Viewer")), None);
+        assert_eq!(wanted_title(&config, &ch, &window("FINAL FANTASY XI")), None);
+    }
+}
@@ -0,0 +1,548 @@
+use crate::config::{InputConfig, KeyboardLayout, TypingStrategy};
+use crate::win32;
+
+/// Pause after each typed character, so PlayOnline doesn't drop keys
+const CHAR_DELAY_MS: u64 = 50;
+
+const VK_SHIFT: u16 = 0x10;
+const VK_CONTROL: u16 = 0x11;
+const VK_MENU: u16 = 0x12;
+const VK_SPACE: u16 = 0x20;
+
+const SCAN_LSHIFT: u16 = 0x2A;
+const SCAN_LCTRL: u16 = 0x1D;
+const SCAN_LALT: u16 = 0x38;
+const SCAN_SPACE: u16 = 0x39;
+
+/// The key and modifiers that produce a character on some keyboard layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyStroke {
+    pub vk: u16,
+    pub scan: u16,
+    pub shift: bool,
+    /// Ctrl+Alt, which Windows treats as AltGr
+    pub altgr: bool,
+}
+
+/// One step of typing text, ready to be sent with `win32::send_input`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputEvent {
+    /// Press or release a virtual key
+    Key { vk: u16, up: bool },
+    /// Press or release a key by hardware scan code. Extended keys such as the
+    /// arrows carry `0xE0` in the high byte, e.g. `0xE050` for Down.
+    Scan { scan: u16, up: bool },
+    /// Press or release a UTF-16 code unit, whatever the keyboard layout
+    Unicode { unit: u16, up: bool },
+    /// Put text on the clipboard and press Ctrl+V
+    Paste(String),
+    /// Wait this many milliseconds
+    Pause(u64),
+}
+
+/// Turn `text` into the input events that type it with `input`'s strategy and layout.
+/// Fails without typing anything if some character can't be typed that way.
+pub fn translate(text: &str, input: &InputConfig) -> Result<Vec<InputEvent>, String> {
+    check_typeable(text, input)?;
+
+    let mut events = Vec::new();
+    match input.typing_strategy() {
+        TypingStrategy::Clipboard => {
+            events.push(InputEvent::Paste(text.to_string()));
+        }
+        TypingStrategy::Unicode => {
+            for ch in text.chars() {
+                let mut units = [0u16; 2];
+                for &unit in ch.encode_utf16(&mut units).iter() {
+                    events.push(InputEvent::Unicode { unit, up: false });
+                    events.push(InputEvent::Unicode { unit, up: true });
+                }
+                events.push(InputEvent::Pause(CHAR_DELAY_MS));
+            }
+        }
+        TypingStrategy::VirtualKey | TypingStrategy::ScanCode => {
+            let scan = input.typing_strategy() == TypingStrategy::ScanCode;
+            let key = |vk: u16, scan_code: u16, up: bool| {
+                if scan {
+                    InputEvent::Scan {
+                        scan: scan_code,
+                        up,
+                    }
+                } else {
+                    InputEvent::Key { vk, up }
+                }
+            };
+            for ch in text.chars() {
+                // Checked by `check_typeable` above
+                let Some(stroke) = key_for(input.layout, ch) else {
+                    continue;
+                };
+                let mut modifiers = Vec::new();
+                if stroke.shift {
+                    modifiers.push((VK_SHIFT, SCAN_LSHIFT));
+                }
+                if stroke.altgr {
+                    modifiers.push((VK_CONTROL, SCAN_LCTRL));
+                    modifiers.push((VK_MENU, SCAN_LALT));
+                }
+                for &(vk, sc) in &modifiers {
+                    events.push(key(vk, sc, false));
+                }
+                events.push(key(stroke.vk, stroke.scan, false));
+                events.push(key(stroke.vk, stroke.scan, true));
+                for &(vk, sc) in modifiers.iter().rev() {
+                    events.push(key(vk, sc, true));
+                }
+                events.push(InputEvent::Pause(CHAR_DELAY_MS));
+            }
+        }
+    }
+    Ok(events)
+}
+
+/// Whether `input` can type every character of `text`. The error gives character
+/// positions, never the text itself.
+pub fn check_typeable(text: &str, input: &InputConfig) -> Result<(), String> {
+    let missing = untypeable(text, input);
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let positions: Vec<String> = missing.iter().map(|i| i.to_string()).collect();
+    let noun = if missing.len() == 1 {
+        "character"
+    } else {
+        "characters"
+    };
+    Err(format!(
+        "{} {} can't be typed with the {} strategy on the {} keyboard layout",
+        noun,
+        positions.join(", "),
+        strategy_name(input.typing_strategy()),
+        layout_name(input.layout)
+    ))
+}
+
+/// 1-based positions of the characters in `text` that `input` can't type
+pub fn untypeable(text: &str, input: &InputConfig) -> Vec<usize> {
+    match input.typing_strategy() {
+        TypingStrategy::Unicode | TypingStrategy::Clipboard => Vec::new(),
+        TypingStrategy::VirtualKey | TypingStrategy::ScanCode => text
+            .chars()
+            .enumerate()
+            .filter(|(_, ch)| key_for(input.layout, *ch).is_none())
+            .map(|(i, _)| i + 1)
+            .collect(),
+    }
+}
+
+/// The key that types `ch` on `layout`. Dead keys, which only type a character
+/// together with the next key, are left out of the built-in layouts.
+pub fn key_for(layout: KeyboardLayout, ch: char) -> Option<KeyStroke> {
+    let table = match layout {
+        KeyboardLayout::Auto => return win32::key_for_char(ch),
+        KeyboardLayout::Us => &US,
+        KeyboardLayout::Uk => &UK,
+        KeyboardLayout::De => &DE,
+        KeyboardLayout::Fr => &FR,
+        KeyboardLayout::Jp => &JP,
+    };
+    table.key_for(ch)
+}
+
+pub fn strategy_name(strategy: TypingStrategy) -> &'static str {
+    match strategy {
+        TypingStrategy::VirtualKey => "virtual_key",
+        TypingStrategy::ScanCode => "scan_code",
+        TypingStrategy::Unicode => "unicode",
+        TypingStrategy::Clipboard => "clipboard",
+    }
+}
+
+pub fn layout_name(layout: KeyboardLayout) -> &'static str {
+    match layout {
+        KeyboardLayout::Auto => "auto",
+        KeyboardLayout::Us => "us",
+        KeyboardLayout::Uk => "uk",
+        KeyboardLayout::De => "de",
+        KeyboardLayout::Fr => "fr",
+        KeyboardLayout::Jp => "jp",
+    }
+}
+
+/// Scan codes of the three letter rows, left to right
+const LETTER_SCANS: [&[u16]; 3] = [
+    &[0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19],
+    &[0x1E, 0x1F, 0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27],
+    &[0x2C, 0x2D, 0x2E, 0x2F, 0x30, 0x31, 0x32],
+];
+
+/// A built-in keyboard layout
+struct LayoutTable {
+    /// Letters on each row of `LETTER_SCANS`. Their virtual key is the capital letter.
+    letters: [&'static str; 3],
+    /// Letters with an AltGr character, as (letter, character)
+    letter_altgr: &'static [(char, char)],
+    /// Every other key: scan code, virtual key and its plain, Shift and AltGr
+    /// characters. `\0` marks a missing or dead-key character.
+    keys: &'static [(u16, u16, &'static str)],
+}
+
+impl LayoutTable {
+    fn key_for(&self, ch: char) -> Option<KeyStroke> {
+        let stroke = |vk, scan, shift, altgr| KeyStroke {
+            vk,
+            scan,
+            shift,
+            altgr,
+        };
+        if ch == ' ' {
+            return Some(stroke(VK_SPACE, SCAN_SPACE, false, false));
+        }
+        for (row, letters) in self.letters.iter().enumerate() {
+            for (i, letter) in letters.chars().enumerate() {
+                let vk = letter.to_ascii_uppercase() as u16;
+                let scan = LETTER_SCANS[row][i];
+                if ch == letter {
+                    return Some(stroke(vk, scan, false, false));
+                }
+                if ch == letter.to_ascii_uppercase() {
+                    return Some(stroke(vk, scan, true, false));
+                }
+                if self.letter_altgr.contains(&(letter, ch)) {
+                    return Some(stroke(vk, scan, false, true));
+                }
+            }
+        }
+        for &(scan, vk, chars) in self.keys {
+            match chars.chars().position(|c| c == ch && c != '\0') {
+                Some(0) => return Some(stroke(vk, scan, false, false)),
+                Some(1) => return Some(stroke(vk, scan, true, false)),
+                Some(2) => return Some(stroke(vk, scan, false, true)),
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+const US: LayoutTable = LayoutTable {
+    letters: ["qwertyuiop", "asdfghjkl", "zxcvbnm"],
+    letter_altgr: &[],
+    keys: &[
+        (0x29, 0xC0, "`~"),
+        (0x02, 0x31, "1!"),
+        (0x03, 0x32, "2@"),
+        (0x04, 0x33, "3#"),
+        (0x05, 0x34, "4$"),
+        (0x06, 0x35, "5%"),
+        (0x07, 0x36, "6^"),
+        (0x08, 0x37, "7&"),
+        (0x09, 0x38, "8*"),
+        (0x0A, 0x39, "9("),
+        (0x0B, 0x30, "0)"),
+        (0x0C, 0xBD, "-_"),
+        (0x0D, 0xBB, "=+"),
+        (0x1A, 0xDB, "[{"),
+        (0x1B, 0xDD, "]}"),
+        (0x2B, 0xDC, "\\|"),
+        (0x27, 0xBA, ";:"),
+        (0x28, 0xDE, "'\""),
+        (0x33, 0xBC, ",<"),
+        (0x34, 0xBE, ".>"),
+        (0x35, 0xBF, "/?"),
+    ],
+};
+
+const UK: LayoutTable = LayoutTable {
+    letters: ["qwertyuiop", "asdfghjkl", "zxcvbnm"],
+    letter_altgr: &[],
+    keys: &[
+        (0x29, 0xDF, "`¬¦"),
+        (0x02, 0x31, "1!"),
+        (0x03, 0x32, "2\""),
+        (0x04, 0x33, "3£"),
+        (0x05, 0x34, "4$€"),
+        (0x06, 0x35, "5%"),
+        (0x07, 0x36, "6^"),
+        (0x08, 0x37, "7&"),
+        (0x09, 0x38, "8*"),
+        (0x0A, 0x39, "9("),
+        (0x0B, 0x30, "0)"),
+        (0x0C, 0xBD, "-_"),
+        (0x0D, 0xBB, "=+"),
+        (0x1A, 0xDB, "[{"),
+        (0x1B, 0xDD, "]}"),
+        (0x2B, 0xDE, "#~"),
+        (0x27, 0xBA, ";:"),
+        (0x28, 0xC0, "'@"),
+        (0x56, 0xDC, "\\|"),
+        (0x33, 0xBC, ",<"),
+        (0x34, 0xBE, ".>"),
+        (0x35, 0xBF, "/?"),
+    ],
+};
+
+const DE: LayoutTable = LayoutTable {
+    letters: ["qwertzuiop", "asdfghjkl", "yxcvbnm"],
+    letter_altgr: &[('q', '@'), ('e', '€'), ('m', 'µ')],
+    keys: &[
+        (0x29, 0xDC, "\0°"),
+        (0x02, 0x31, "1!"),
+        (0x03, 0x32, "2\"²"),
+        (0x04, 0x33, "3§³"),
+        (0x05, 0x34, "4$"),
+        (0x06, 0x35, "5%"),
+        (0x07, 0x36, "6&"),
+        (0x08, 0x37, "7/{"),
+        (0x09, 0x38, "8(["),
+        (0x0A, 0x39, "9)]"),
+        (0x0B, 0x30, "0=}"),
+        (0x0C, 0xDB, "ß?\\"),
+        (0x1A, 0xBA, "üÜ"),
+        (0x1B, 0xBB, "+*~"),
+        (0x2B, 0xBF, "#'"),
+        (0x27, 0xC0, "öÖ"),
+        (0x28, 0xDE, "äÄ"),
+        (0x56, 0xE2, "<>|"),
+        (0x33, 0xBC, ",;"),
+        (0x34, 0xBE, ".:"),
+        (0x35, 0xBD, "-_"),
+    ],
+};
+
+const FR: LayoutTable = LayoutTable {
+    letters: ["azertyuiop", "qsdfghjklm", "wxcvbn"],
+    letter_altgr: &[('e', '€')],
+    keys: &[
+        (0x29, 0xDE, "²"),
+        (0x02, 0x31, "&1"),
+        (0x03, 0x32, "é2"),
+        (0x04, 0x33, "\"3#"),
+        (0x05, 0x34, "'4{"),
+        (0x06, 0x35, "(5["),
+        (0x07, 0x36, "-6|"),
+        (0x08, 0x37, "è7"),
+        (0x09, 0x38, "_8\\"),
+        (0x0A, 0x39, "ç9^"),
+        (0x0B, 0x30, "à0@"),
+        (0x0C, 0xDB, ")°]"),
+        (0x0D, 0xBB, "=+}"),
+        (0x1B, 0xBA, "$£¤"),
+        (0x28, 0xC0, "ù%"),
+        (0x2B, 0xDC, "*µ"),
+        (0x56, 0xE2, "<>"),
+        (0x32, 0xBC, ",?"),
+        (0x33, 0xBE, ";."),
+        (0x34, 0xBF, ":/"),
+        (0x35, 0xDF, "!§"),
+    ],
+};
+
+const JP: LayoutTable = LayoutTable {
+    letters: ["qwertyuiop", "asdfghjkl", "zxcvbnm"],
+    letter_altgr: &[],
+    keys: &[
+        (0x02, 0x31, "1!"),
+        (0x03, 0x32, "2\""),
+        (0x04, 0x33, "3#"),
+        (0x05, 0x34, "4$"),
+        (0x06, 0x35, "5%"),
+        (0x07, 0x36, "6&"),
+        (0x08, 0x37, "7'"),
+        (0x09, 0x38, "8("),
+        (0x0A, 0x39, "9)"),
+        (0x0B, 0x30, "0"),
+        (0x0C, 0xBD, "-="),
+        (0x0D, 0xDE, "^~"),
+        (0x7D, 0xDC, "\\|"),
+        (0x1A, 0xC0, "@`"),
+        (0x1B, 0xDB, "[{"),
+        (0x27, 0xBB, ";+"),
+        (0x28, 0xBA, ":*"),
+        (0x2B, 0xDD, "]}"),
+        (0x33, 0xBC, ",<"),
+        (0x34, 0xBE, ".>"),
+        (0x35, 0xBF, "/?"),
+        (0x73, 0xE2, "\\_"),
+    ],
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::InputBackendKind;
+    use InputEvent::{Key, Pause, Scan, Unicode};
+
+    fn input(strategy: TypingStrategy, layout: KeyboardLayout) -> InputConfig {
+        InputConfig {
+            strategy,
+            layout,
+            ..InputConfig::default()
+        }
+    }
+
+    fn stroke(vk: u16, scan: u16, shift: bool, altgr: bool) -> Option<KeyStroke> {
+        Some(KeyStroke {
+            vk,
+            scan,
+            shift,
+            altgr,
+        })
+    }
+
+    #[test]
+    fn us_letters_digits_and_symbols() {
+        let us = KeyboardLayout::Us;
+        assert_eq!(key_for(us, 'a'), stroke(0x41, 0x1E, false, false));
+        assert_eq!(key_for(us, 'A'), stroke(0x41, 0x1E, true, false));
+        assert_eq!(key_for(us, 'm'), stroke(0x4D, 0x32, false, false));
+        assert_eq!(key_for(us, '7'), stroke(0x37, 0x08, false, false));
+        assert_eq!(key_for(us, '@'), stroke(0x32, 0x03, true, false));
+        assert_eq!(key_for(us, '"'), stroke(0xDE, 0x28, true, false));
+        assert_eq!(key_for(us, ' '), stroke(VK_SPACE, SCAN_SPACE, false, false));
+        assert_eq!(key_for(us, '€'), None);
+        assert_eq!(key_for(us, '\0'), None);
+    }
+
+    #[test]
+    fn uk_moves_symbols_and_has_altgr() {
+        let uk = KeyboardLayout::Uk;
+        assert_eq!(key_for(uk, '"'), stroke(0x32, 0x03, true, false));
+        assert_eq!(key_for(uk, '@'), stroke(0xC0, 0x28, true, false));
+        assert_eq!(key_for(uk, '£'), stroke(0x33, 0x04, true, false));
+        assert_eq!(key_for(uk, '€'), stroke(0x34, 0x05, false, true));
+        assert_eq!(key_for(uk, '\\'), stroke(0xDC, 0x56, false, false));
+    }
+
+    #[test]
+    fn de_swaps_y_and_z_and_leaves_out_dead_keys() {
+        let de = KeyboardLayout::De;
+        assert_eq!(key_for(de, 'z'), stroke(0x5A, 0x15, false, false));
+        assert_eq!(key_for(de, 'Y'), stroke(0x59, 0x2C, true, false));
+        assert_eq!(key_for(de, '@'), stroke(0x51, 0x10, false, true));
+        assert_eq!(key_for(de, 'µ'), stroke(0x4D, 0x32, false, true));
+        assert_eq!(key_for(de, '{'), stroke(0x37, 0x08, false, true));
+        assert_eq!(key_for(de, 'ß'), stroke(0xDB, 0x0C, false, false));
+        assert_eq!(key_for(de, '?'), stroke(0xDB, 0x0C, true, false));
+        assert_eq!(key_for(de, '°'), stroke(0xDC, 0x29, true, false));
+        // ^ and ` are dead keys on a German keyboard
+        assert_eq!(key_for(de, '^'), None);
+        assert_eq!(key_for(de, '`'), None);
+    }
+
+    #[test]
+    fn fr_is_azerty_with_shifted_digits() {
+        let fr = KeyboardLayout::Fr;
+        assert_eq!(key_for(fr, 'a'), stroke(0x41, 0x10, false, false));
+        assert_eq!(key_for(fr, 'q'), stroke(0x51, 0x1E, false, false));
+        assert_eq!(key_for(fr, 'M'), stroke(0x4D, 0x27, true, false));
+        assert_eq!(key_for(fr, 'w'), stroke(0x57, 0x2C, false, false));
+        assert_eq!(key_for(fr, '&'), stroke(0x31, 0x02, false, false));
+        assert_eq!(key_for(fr, '1'), stroke(0x31, 0x02, true, false));
+        assert_eq!(key_for(fr, '@'), stroke(0x30, 0x0B, false, true));
+        assert_eq!(key_for(fr, '€'), stroke(0x45, 0x12, false, true));
+        assert_eq!(key_for(fr, '²'), stroke(0xDE, 0x29, false, false));
+    }
+
+    #[test]
+    fn jp_has_its_own_symbol_keys() {
+        let jp = KeyboardLayout::Jp;
+        assert_eq!(key_for(jp, '@'), stroke(0xC0, 0x1A, false, false));
+        assert_eq!(key_for(jp, '`'), stroke(0xC0, 0x1A, true, false));
+        assert_eq!(key_for(jp, '('), stroke(0x38, 0x09, true, false));
+        assert_eq!(key_for(jp, ':'), stroke(0xBA, 0x28, false, false));
+        assert_eq!(key_for(jp, '_'), stroke(0xE2, 0x73, true, false));
+        // On both the yen and the ro key; the yen key comes first
+        assert_eq!(key_for(jp, '\\'), stroke(0xDC, 0x7D, false, false));
+    }
+
+    #[test]
+    fn scan_codes_wrap_keys_in_their_modifiers() {
+        let us = input(TypingStrategy::ScanCode, KeyboardLayout::Us);
+        assert_eq!(
+            translate("aB", &us).unwrap(),
+            [
+                Scan { scan: 0x1E, up: false },
+                Scan { scan: 0x1E, up: true },
+                Pause(CHAR_DELAY_MS),
+                Scan { scan: SCAN_LSHIFT, up: false },
+                Scan { scan: 0x30, up: false },
+                Scan { scan: 0x30, up: true },
+                Scan { scan: SCAN_LSHIFT, up: true },
+                Pause(CHAR_DELAY_MS),
+            ]
+        );
+    }
+
+    #[test]
+    fn virtual_keys_press_altgr_as_ctrl_alt() {
+        let de = input(TypingStrategy::VirtualKey, KeyboardLayout::De);
+        assert_eq!(
+            translate("@", &de).unwrap(),
+            [
+                Key { vk: VK_CONTROL, up: false },
+                Key { vk: VK_MENU, up: false },
+                Key { vk: 0x51, up: false },
+                Key { vk: 0x51, up: true },
+                Key { vk: VK_MENU, up: true },
+                Key { vk: VK_CONTROL, up: true },
+                Pause(CHAR_DELAY_MS),
+            ]
+        );
+    }
+
+    #[test]
+    fn unicode_sends_both_halves_of_a_surrogate_pair() {
+        let unicode = input(TypingStrategy::Unicode, KeyboardLayout::Us);
+        assert_eq!(
+            translate("é😀", &unicode).unwrap(),
+            [
+                Unicode { unit: 0xE9, up: false },
+                Unicode { unit: 0xE9, up: true },
+                Pause(CHAR_DELAY_MS),
+                Unicode { unit: 0xD83D, up: false },
+                Unicode { unit: 0xD83D, up: true },
+                Unicode { unit: 0xDE00, up: false },
+                Unicode { unit: 0xDE00, up: true },
+                Pause(CHAR_DELAY_MS),
+            ]
+        );
+    }
+
+    #[test]
+    fn clipboard_pastes_the_whole_text() {
+        let clipboard = input(TypingStrategy::Clipboard, KeyboardLayout::Us);
+        assert_eq!(
+            translate("Pa^ss", &clipboard).unwrap(),
+            [InputEvent::Paste("Pa^ss".to_string())]
+        );
+    }
+
+    #[test]
+    fn post_message_types_anything_as_unicode() {
+        let mut de = input(TypingStrategy::ScanCode, KeyboardLayout::De);
+        assert!(check_typeable("^", &de).is_err());
+        de.backend = InputBackendKind::PostMessage;
+        assert_eq!(check_typeable("^", &de), Ok(()));
+        assert_eq!(translate("^", &de).unwrap()[0], Unicode { unit: 0x5E, up: false });
+    }
+
+    #[test]
+    fn untypeable_characters_are_reported_by_position() {
+        let de = input(TypingStrategy::ScanCode, KeyboardLayout::De);
+        assert_eq!(untypeable("Pa^ss`", &de), [3, 6]);
+        let error = translate("Pa^ss`", &de).unwrap_err();
+        assert_eq!(
+            error,
+            "characters 3, 6 can't be typed with the scan_code strategy on the de keyboard layout"
+        );
+        assert!(!error.contains("Pa^ss"));
+
+        let us = input(TypingStrategy::VirtualKey, KeyboardLayout::Us);
+        assert_eq!(
+            check_typeable("ab€", &us).unwrap_err(),
+            "character 3 can't be typed with the virtual_key strategy on the us keyboard layout"
+        );
+        assert_eq!(check_typeable("", &us), Ok(()));
+    }
+}
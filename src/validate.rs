@@ -0,0 +1,540 @@
+use crate::checks::CheckResult;
+use crate::config::layers::{self, Layer};
+use crate::config::{
+    check_password, expand, find_unknown_fields, migrate, suggest, Config, InputBackendKind, LayoutMode,
+    REGIONS,
+};
+use crate::schedule::{self, Cron};
+use crate::typing;
+use crate::webhooks;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Run every static config check against a config file and the layers merged onto it.
+/// The "Config" check is `Config::validate`, the one every login runs; the others add
+/// warnings and details only shown here. Nothing here touches the filesystem or network;
+/// see `doctor` for that.
+/// `config_dir` is the directory relative paths in the config are resolved against.
+pub fn validate_config(layers: &[Layer], config_dir: &Path) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+
+    let files: Vec<&Layer> = layers.iter().filter(|l| l.contents.is_some()).collect();
+    let syntax = match files.as_slice() {
+        [only] => format!("Valid {}", only.format.name()),
+        many => many
+            .iter()
+            .map(|l| format!("{} ({})", l.origin, l.format.name()))
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+    results.push(CheckResult::pass("Syntax", syntax));
+
+    if let Some(main) = layers.iter().find(|l| l.is_main) {
+        results.push(check_version(&main.value));
+    }
+    results.push(check_unknown_fields(layers));
+
+    let merged = match layers::merge(layers) {
+        Ok(m) => m,
+        Err(e) => {
+            results.push(CheckResult::fail("Merge", e));
+            return results;
+        }
+    };
+    results.push(check_region(&merged.value));
+
+    let mut config = match Config::from_layers(layers) {
+        Ok(c) => c,
+        Err(e) => {
+            results.push(CheckResult::fail("Schema", e.to_string()));
+            return results;
+        }
+    };
+    results.push(CheckResult::pass("Schema", "All fields have the expected types"));
+    results.push(match config.validate() {
+        Ok(()) => CheckResult::pass(
+            "Config",
+            format!("{} character(s), passes the checks run before logging in", config.characters.len()),
+        ),
+        Err(e) => CheckResult::fail("Config", e.to_string()),
+    });
+
+    results.push(check_paths(&mut config, layers, config_dir));
+    results.push(check_duplicate_names(&config));
+    results.push(check_duplicate_slots(&config));
+    results.push(check_passwords(&config));
+    results.push(check_delays(&config));
+    results.push(check_input(&config));
+    results.push(check_layout(&config));
+    results.push(check_titles(&config));
+    results.push(check_lobby(&config));
+    results.push(check_api(&config));
+    results.push(check_webhooks(&config));
+    results.push(check_watchdog(&config));
+    results.push(check_groups(&config));
+    results.push(check_schedule(&config));
+
+    results
+}
+
+fn check_unknown_fields(layers: &[Layer]) -> CheckResult {
+    let main_version = layers::main_version(layers).unwrap_or(migrate::CURRENT_VERSION);
+    let mut unknown = Vec::new();
+    let mut ignored = Vec::new();
+    for layer in layers {
+        // Older versions ignored unknown keys, and migrating drops them
+        let old = layers::upgrade(layer, main_version).is_ok_and(|(_, from)| from < migrate::CURRENT_VERSION);
+        for field in find_unknown_fields(&layer.value) {
+            let field = if layer.is_main {
+                field.to_string()
+            } else {
+                format!("{}: {}", layer.origin, field)
+            };
+            if old {
+                ignored.push(field);
+            } else {
+                unknown.push(field);
+            }
+        }
+    }
+    if !unknown.is_empty() {
+        CheckResult::fail("Unknown fields", unknown.join("; "))
+    } else if !ignored.is_empty() {
+        CheckResult::warn(
+            "Unknown fields",
+            format!("{}; ignored, and removed by `login-rs config migrate`", ignored.join("; ")),
+        )
+    } else {
+        CheckResult::pass("Unknown fields", "None")
+    }
+}
+
+fn check_version(value: &Value) -> CheckResult {
+    let mut upgraded = value.clone();
+    match migrate::migrate(&mut upgraded) {
+        Ok(v) if v == migrate::CURRENT_VERSION => {
+            CheckResult::pass("Version", format!("{} (current)", v))
+        }
+        Ok(v) => CheckResult::warn(
+            "Version",
+            format!(
+                "{} (current is {}); run `login-rs config migrate` to upgrade the file",
+                v,
+                migrate::CURRENT_VERSION
+            ),
+        ),
+        Err(e) => CheckResult::fail("Version", e),
+    }
+}
+
+fn check_region(value: &Value) -> CheckResult {
+    match value.get("region") {
+        None => CheckResult::pass("Region", "us (default)"),
+        Some(Value::String(region)) if REGIONS.contains(&region.as_str()) => {
+            CheckResult::pass("Region", region.as_str())
+        }
+        Some(Value::String(region)) => {
+            let lowered = region.to_ascii_lowercase();
+            let hint = REGIONS
+                .iter()
+                .find(|r| **r == lowered)
+                .copied()
+                .or_else(|| suggest(region, REGIONS));
+            let detail = match hint {
+                Some(h) => format!("'{}' is not a region (did you mean '{}'?)", region, h),
+                None => format!("'{}' is not a region (expected one of {})", region, REGIONS.join(", ")),
+            };
+            CheckResult::fail("Region", detail)
+        }
+        Some(other) => CheckResult::fail("Region", format!("expected a string, found {}", other)),
+    }
+}
+
+fn check_paths(config: &mut Config, layers: &[Layer], config_dir: &Path) -> CheckResult {
+    let base_dir = |field: &str| layers::base_dir(layers, field, config_dir);
+    match config.expand_paths(&base_dir, &expand::process_env) {
+        Ok(()) => CheckResult::pass(
+            "Paths",
+            format!(
+                "windower_path {}, playonline_dir {}",
+                config.windower_path.display(),
+                config.playonline_dir.display()
+            ),
+        ),
+        Err(e) => CheckResult::fail("Paths", e),
+    }
+}
+
+fn check_duplicate_names(config: &Config) -> CheckResult {
+    // Names are matched case-insensitively on the command line, so compare them that way here
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for ch in &config.characters {
+        *counts.entry(ch.name.to_ascii_lowercase()).or_default() += 1;
+    }
+    let mut dupes: Vec<&str> = Vec::new();
+    for ch in &config.characters {
+        let repeated = counts[&ch.name.to_ascii_lowercase()] > 1;
+        if repeated && !dupes.iter().any(|d| d.eq_ignore_ascii_case(&ch.name)) {
+            dupes.push(&ch.name);
+        }
+    }
+
+    if dupes.is_empty() {
+        CheckResult::pass("Duplicate names", "None")
+    } else {
+        CheckResult::fail("Duplicate names", format!("Defined more than once: {}", dupes.join(", ")))
+    }
+}
+
+fn check_duplicate_slots(config: &Config) -> CheckResult {
+    let mut by_slot: HashMap<u8, Vec<&str>> = HashMap::new();
+    for ch in &config.characters {
+        by_slot.entry(ch.slot).or_default().push(&ch.name);
+    }
+    let mut dupes: Vec<(u8, Vec<&str>)> = by_slot
+        .into_iter()
+        .filter(|(_, names)| names.len() > 1)
+        .collect();
+    dupes.sort_by_key(|(slot, _)| *slot);
+
+    if dupes.is_empty() {
+        CheckResult::pass("Duplicate slots", "None")
+    } else {
+        let detail = dupes
+            .iter()
+            .map(|(slot, names)| format!("slot {} used by {}", slot, names.join(", ")))
+            .collect::<Vec<_>>()
+            .join("; ");
+        CheckResult::fail("Duplicate slots", detail)
+    }
+}
+
+fn check_passwords(config: &Config) -> CheckResult {
+    let policy = |name: &str, password: &str, problems: &mut Vec<String>| {
+        if let Err(e) = check_password(password, &config.input) {
+            problems.push(format!("{}: {}", name, e));
+        }
+    };
+    let mut problems = Vec::new();
+    let mut unset_vars = Vec::new();
+    for ch in &config.characters {
+        // Setting both or neither of password and password_env fails the Config check
+        match (&ch.password_env, ch.password.is_empty()) {
+            (Some(var), true) => match std::env::var(var) {
+                Ok(password) => policy(&ch.name, &password, &mut problems),
+                Err(_) => unset_vars.push(format!("{} ({})", var, ch.name)),
+            },
+            (None, false) => policy(&ch.name, &ch.password, &mut problems),
+            _ => {}
+        }
+    }
+
+    if !problems.is_empty() {
+        CheckResult::fail("Passwords", problems.join("; "))
+    } else if !unset_vars.is_empty() {
+        CheckResult::warn(
+            "Passwords",
+            format!("Environment variable not set: {}", unset_vars.join(", ")),
+        )
+    } else {
+        CheckResult::pass(
+            "Passwords",
+            format!(
+                "Set for every character and typeable with the {} strategy",
+                typing::strategy_name(config.input.typing_strategy())
+            ),
+        )
+    }
+}
+
+fn check_delays(config: &Config) -> CheckResult {
+    if config.launch_delay_seconds == 0 {
+        CheckResult::warn(
+            "Delays",
+            "launch_delay_seconds is 0; launching instances back to back can mismatch windows",
+        )
+    } else {
+        CheckResult::pass(
+            "Delays",
+            format!(
+                "stagger {}s, launch {}s, game window timeout {}s",
+                config.stagger_delay_seconds,
+                config.launch_delay_seconds,
+                config.game_window_timeout_seconds
+            ),
+        )
+    }
+}
+
+fn check_input(config: &Config) -> CheckResult {
+    let input = &config.input;
+    let detail = match input.backend {
+        InputBackendKind::SendInput => format!(
+            "send_input backend, {} strategy, {} layout, input blocked for at most {}s",
+            typing::strategy_name(input.strategy),
+            typing::layout_name(input.layout),
+            input.max_block_seconds
+        ),
+        InputBackendKind::PostMessage => format!(
+            "post_message backend, up to {} logins at once",
+            input.parallel_logins()
+        ),
+    };
+    CheckResult::pass("Input", detail)
+}
+
+fn check_layout(config: &Config) -> CheckResult {
+    let Some(layout) = &config.layout else {
+        return CheckResult::pass("Layout", "Off");
+    };
+    if layout.mode == LayoutMode::Manual && layout.windows.is_empty() {
+        return CheckResult::warn(
+            "Layout",
+            "manual layout with no layout.windows entries, so no window is moved",
+        );
+    }
+    let mode = match layout.mode {
+        LayoutMode::Grid => "grid",
+        LayoutMode::Cascade => "cascade",
+        LayoutMode::Manual => "manual",
+    };
+    let mut detail = format!("{} on monitor {}", mode, layout.monitor);
+    if !layout.windows.is_empty() {
+        detail.push_str(&format!(", {} fixed window(s)", layout.windows.len()));
+    }
+    CheckResult::pass("Layout", detail)
+}
+
+fn check_titles(config: &Config) -> CheckResult {
+    let describe = |template: &str| {
+        if template.is_empty() {
+            "left alone".to_string()
+        } else {
+            format!("\"{}\"", template)
+        }
+    };
+    CheckResult::pass(
+        "Window titles",
+        format!(
+            "PlayOnline {}, game {}",
+            describe(&config.titles.playonline),
+            describe(&config.titles.game)
+        ),
+    )
+}
+
+fn check_lobby(config: &Config) -> CheckResult {
+    let selecting = config
+        .characters
+        .iter()
+        .filter(|ch| ch.ffxi_character_index.is_some())
+        .count();
+    if selecting == 0 {
+        return CheckResult::pass("Lobby", "Off (no character has ffxi_character_index)");
+    }
+    let lobby = &config.lobby;
+    if !lobby.skip_opening && lobby.title_screen_delay_seconds == 0 {
+        return CheckResult::warn(
+            "Lobby",
+            "lobby.title_screen_delay_seconds is 0; keys sent during the opening movie are lost",
+        );
+    }
+    let opening = if lobby.skip_opening {
+        "opening skipped".to_string()
+    } else {
+        format!("title screen after {}s", lobby.title_screen_delay_seconds)
+    };
+    CheckResult::pass(
+        "Lobby",
+        format!(
+            "{} character(s) select an FFXI character, {}, {}s step timeout",
+            selecting, opening, lobby.step_timeout_seconds
+        ),
+    )
+}
+
+fn check_api(config: &Config) -> CheckResult {
+    let Some(api) = &config.api else {
+        return CheckResult::pass("Control API", "Not configured");
+    };
+    if !api.token.trim().is_empty() && api.token.len() < 16 {
+        CheckResult::warn(
+            "Control API",
+            "api.token is shorter than 16 characters; use a long random token",
+        )
+    } else {
+        CheckResult::pass("Control API", format!("127.0.0.1:{}", api.port))
+    }
+}
+
+fn check_webhooks(config: &Config) -> CheckResult {
+    if config.webhooks.is_empty() {
+        CheckResult::pass("Webhooks", "None")
+    } else {
+        let urls: Vec<String> = config
+            .webhooks
+            .iter()
+            .map(|hook| webhooks::display_url(&hook.url))
+            .collect();
+        CheckResult::pass("Webhooks", urls.join(", "))
+    }
+}
+
+fn check_watchdog(config: &Config) -> CheckResult {
+    match &config.watchdog {
+        None => CheckResult::pass("Watchdog", "Off"),
+        Some(w) if w.max_restarts_per_hour == 0 => CheckResult::warn(
+            "Watchdog",
+            "max_restarts_per_hour is 0, so crashed characters are reported but never relaunched",
+        ),
+        Some(w) => CheckResult::pass(
+            "Watchdog",
+            format!(
+                "every {}s, up to {} restart(s) per character per hour",
+                w.check_interval_seconds, w.max_restarts_per_hour
+            ),
+        ),
+    }
+}
+
+fn check_groups(config: &Config) -> CheckResult {
+    if config.groups.is_empty() {
+        CheckResult::pass("Groups", "None")
+    } else {
+        let groups: Vec<String> = config
+            .groups
+            .iter()
+            .map(|(name, members)| format!("{} ({})", name, members.len()))
+            .collect();
+        CheckResult::pass("Groups", groups.join(", "))
+    }
+}
+
+fn check_schedule(config: &Config) -> CheckResult {
+    if config.schedule.is_empty() {
+        return CheckResult::pass("Schedule", "None");
+    }
+    let now = chrono::Utc::now();
+    // Entries that don't parse or never fire fail the Config check
+    let upcoming: Vec<String> = config
+        .schedule
+        .iter()
+        .filter_map(|entry| {
+            let cron = Cron::parse(&entry.cron).ok()?;
+            let next = schedule::next_fire(&cron, entry.timezone, now)?;
+            Some(format!(
+                "'{}' next at {}",
+                entry.cron,
+                schedule::display_time(next, entry.timezone)
+            ))
+        })
+        .collect();
+    CheckResult::pass("Schedule", upcoming.join("; "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checks::CheckStatus;
+    use crate::config::ConfigFormat;
+
+    fn layer(json: &str) -> Layer {
+        Layer {
+            origin: "config.json".to_string(),
+            format: ConfigFormat::Json,
+            contents: Some(json.to_string()),
+            value: serde_json::from_str(json).unwrap(),
+            is_main: true,
+        }
+    }
+
+    fn validate(json: &str) -> Vec<CheckResult> {
+        validate_config(&[layer(json)], Path::new("/configs"))
+    }
+
+    fn check<'a>(results: &'a [CheckResult], name: &str) -> &'a CheckResult {
+        results
+            .iter()
+            .find(|r| r.name == name)
+            .unwrap_or_else(|| panic!("no {} check in {:?}", name, results))
+    }
+
+    #[test]
+    fn good_config_passes_every_check() {
+        let results = validate(
+            r#"{ "version": 2, "characters": [
+                { "name": "MyWarrior", "slot": 1, "password": "Passw0rd!" },
+                { "name": "MyMule", "slot": 2, "password": "0therPass" }
+            ] }"#,
+        );
+        assert!(results.iter().all(|r| r.status == CheckStatus::Pass), "{:?}", results);
+    }
+
+    #[test]
+    fn unknown_field_and_misspelled_region() {
+        let results = validate(
+            r#"{ "version": 2, "regoin": "us", "characters": [
+                { "name": "MyWarrior", "slot": 1, "password": "Passw0rd!" }
+            ] }"#,
+        );
+        let unknown = check(&results, "Unknown fields");
+        assert_eq!(unknown.status, CheckStatus::Fail);
+        assert!(unknown.detail.contains("regoin"), "{}", unknown.detail);
+        // Nothing past the unknown field check can load the config
+        assert!(results.iter().all(|r| r.name != "Config"));
+
+        let results = validate(
+            r#"{ "version": 2, "region": "US", "characters": [
+                { "name": "MyWarrior", "slot": 1, "password": "Passw0rd!" }
+            ] }"#,
+        );
+        assert_eq!(check(&results, "Region").detail, "'US' is not a region (did you mean 'us'?)");
+    }
+
+    #[test]
+    fn duplicates_and_bad_slots() {
+        let results = validate(
+            r#"{ "version": 2, "characters": [
+                { "name": "MyWarrior", "slot": 1, "password": "Passw0rd!" },
+                { "name": "mywarrior", "slot": 1, "password": "Passw0rd!" },
+                { "name": "MyMule", "slot": 21, "password": "Passw0rd!" }
+            ] }"#,
+        );
+        assert_eq!(
+            check(&results, "Config").detail,
+            "Character 'MyMule' has invalid slot 21 (must be 1-20)"
+        );
+        assert_eq!(check(&results, "Duplicate names").detail, "Defined more than once: MyWarrior");
+        assert_eq!(
+            check(&results, "Duplicate slots").detail,
+            "slot 1 used by MyWarrior, mywarrior"
+        );
+    }
+
+    #[test]
+    fn unset_password_variable_is_only_a_warning() {
+        let results = validate(
+            r#"{ "version": 2, "characters": [
+                { "name": "MyWarrior", "slot": 1, "password_env": "LOGIN_RS_TEST_UNSET_PASSWORD" }
+            ] }"#,
+        );
+        // Logging in reads the variable later, so the startup checks don't need it yet
+        assert_eq!(check(&results, "Config").status, CheckStatus::Pass);
+        let passwords = check(&results, "Passwords");
+        assert_eq!(passwords.status, CheckStatus::Warn);
+        assert_eq!(
+            passwords.detail,
+            "Environment variable not set: LOGIN_RS_TEST_UNSET_PASSWORD (MyWarrior)"
+        );
+    }
+
+    #[test]
+    fn old_version_is_a_warning() {
+        let results = validate(
+            r#"{ "characters": [{ "name": "MyWarrior", "slot": 1, "password": "Passw0rd!" }] }"#,
+        );
+        assert_eq!(check(&results, "Version").status, CheckStatus::Warn);
+        assert_eq!(check(&results, "Schema").status, CheckStatus::Pass);
+    }
+}
@@ -0,0 +1,491 @@
+use crate::clock::Clock;
+use crate::config::{Config, WatchdogConfig};
+use crate::events::{Event, Output};
+use crate::launcher::{self, Launcher};
+use crate::processes::ProcessQuery;
+use crate::report::{Instance, RunReport};
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+
+/// How long a character may have no window before it counts as gone. PlayOnline
+/// closes its window before FFXI opens its own, so a short gap is normal.
+const LOST_AFTER: Duration = Duration::from_secs(30);
+
+/// Restarts older than this no longer count towards `max_restarts_per_hour`
+const RESTART_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+struct Watched {
+    name: String,
+    instance: Instance,
+    /// When the character was first seen without a window
+    missing_since: Option<Instant>,
+    /// When it was relaunched, oldest first
+    restarts: Vec<Instant>,
+}
+
+/// Keeps launched characters running, relaunching any whose game closes
+pub struct Watchdog {
+    settings: WatchdogConfig,
+    watched: Vec<Watched>,
+    last_check: Option<Instant>,
+}
+
+impl Watchdog {
+    pub fn new(settings: WatchdogConfig) -> Self {
+        Self {
+            settings,
+            watched: Vec::new(),
+            last_check: None,
+        }
+    }
+
+    /// Start watching every character `report` launched, replacing any earlier instance
+    pub fn track(&mut self, report: &RunReport) {
+        for ch in &report.characters {
+            let Some(instance) = ch.instance else {
+                continue;
+            };
+            match self.watched.iter_mut().find(|w| w.name == ch.name) {
+                Some(w) => {
+                    w.instance = instance;
+                    w.missing_since = None;
+                }
+                None => self.watched.push(Watched {
+                    name: ch.name.clone(),
+                    instance,
+                    missing_since: None,
+                    restarts: Vec::new(),
+                }),
+            }
+        }
+    }
+
+    /// Names of the characters being watched
+    pub fn watching(&self) -> Vec<&str> {
+        self.watched.iter().map(|w| w.name.as_str()).collect()
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(self.settings.check_interval_seconds)
+    }
+
+    /// Run `check` if `check_interval_seconds` has passed since the last one.
+    /// For loops that wake up more often than the watchdog needs to.
+    pub fn tick(
+        &mut self,
+        config: &Config,
+        launcher: &mut dyn Launcher,
+        processes: &dyn ProcessQuery,
+        clock: &dyn Clock,
+        out: &Output,
+    ) {
+        let now = clock.now();
+        if self
+            .last_check
+            .is_some_and(|last| now.duration_since(last) < self.interval())
+        {
+            return;
+        }
+        self.last_check = Some(now);
+        self.check(config, launcher, processes, clock, out);
+    }
+
+    /// Look for each watched character's windows among its process tree, renaming any
+    /// the game gave back its own title, and relaunch characters that have had none for a while
+    pub fn check(
+        &mut self,
+        config: &Config,
+        launcher: &mut dyn Launcher,
+        processes: &dyn ProcessQuery,
+        clock: &dyn Clock,
+        out: &Output,
+    ) {
+        let table = processes.snapshot();
+        let now = clock.now();
+
+        let mut lost = Vec::new();
+        for w in &mut self.watched {
+            let windows = table.windows_of(w.instance.pid);
+            // Put back titles the game reset
+            if let Some(character) = config.characters.iter().find(|ch| ch.name == w.name) {
+                launcher::retitle(config, character, &windows);
+            }
+            if windows.iter().any(|win| win.id == w.instance.window) {
+                w.missing_since = None;
+                continue;
+            }
+            if let Some(window) = windows.first() {
+                // The game replaced its window, e.g. PlayOnline handing over to FFXI
+                log::debug!("{} moved to window '{}'", w.name, window.title);
+                w.instance.window = window.id;
+                w.missing_since = None;
+                continue;
+            }
+            let since = *w.missing_since.get_or_insert(now);
+            if now.duration_since(since) >= LOST_AFTER {
+                let reason = if table.tree_running(w.instance.pid) {
+                    "window closed"
+                } else {
+                    "process exited"
+                };
+                lost.push((w.name.clone(), reason));
+            }
+        }
+
+        for (name, reason) in lost {
+            self.relaunch(&name, reason, config, launcher, clock, out);
+        }
+    }
+
+    fn relaunch(
+        &mut self,
+        name: &str,
+        reason: &str,
+        config: &Config,
+        launcher: &mut dyn Launcher,
+        clock: &dyn Clock,
+        out: &Output,
+    ) {
+        let Some(character) = config
+            .characters
+            .iter()
+            .find(|ch| ch.name.eq_ignore_ascii_case(name))
+        else {
+            // Removed from the config since it was launched
+            self.watched.retain(|w| w.name != name);
+            return;
+        };
+        let Some(w) = self.watched.iter_mut().find(|w| w.name == name) else {
+            return;
+        };
+
+        out.emit(Event::GameLost {
+            character: name,
+            reason,
+        });
+
+        let now = clock.now();
+        w.restarts
+            .retain(|t| now.duration_since(*t) < RESTART_WINDOW);
+        let max = self.settings.max_restarts_per_hour;
+        if w.restarts.len() as u32 >= max {
+            out.emit(Event::WatchdogGaveUp {
+                character: name,
+                restarts: max,
+            });
+            self.watched.retain(|w| w.name != name);
+            return;
+        }
+        w.restarts.push(now);
+        out.emit(Event::Relaunching {
+            character: name,
+            restart: w.restarts.len() as u32,
+            max,
+        });
+
+        // If the launch fails the old instance stays, still missing, so the next check retries
+        let report = launcher.login(config, &[character], &AtomicBool::new(false));
+        self.track(&report);
+    }
+
+    /// Check every `check_interval_seconds` until there is nothing left to watch
+    pub fn run(
+        &mut self,
+        config: &Config,
+        launcher: &mut dyn Launcher,
+        processes: &dyn ProcessQuery,
+        clock: &dyn Clock,
+        out: &Output,
+    ) {
+        while !self.watched.is_empty() {
+            clock.sleep(self.interval());
+            self.check(config, launcher, processes, clock, out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FakeClock;
+    use crate::config::ConfigFormat;
+    use crate::events::OutputFormat;
+    use crate::launcher::MockLauncher;
+    use crate::processes::{ProcessEntry, ProcessTable, WindowEntry};
+    use serde_json::Value;
+    use std::sync::{Arc, Mutex};
+
+    fn config(names: &[&str]) -> Config {
+        let characters: Vec<Value> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                serde_json::json!({"name": name, "slot": i + 1, "password": "Passw0rd!"})
+            })
+            .collect();
+        let json = serde_json::json!({"version": 2, "characters": characters}).to_string();
+        Config::parse(&json, ConfigFormat::Json).unwrap()
+    }
+
+    fn settings(max_restarts_per_hour: u32) -> WatchdogConfig {
+        WatchdogConfig {
+            max_restarts_per_hour,
+            check_interval_seconds: 10,
+        }
+    }
+
+    /// An output that keeps every event as JSON
+    fn recording_output() -> (Output, Arc<Mutex<Vec<Value>>>) {
+        let out = Output::new(OutputFormat::Json);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        out.subscribe(move |e| sink.lock().unwrap().push(serde_json::to_value(e).unwrap()));
+        (out, events)
+    }
+
+    fn names(events: &Mutex<Vec<Value>>) -> Vec<String> {
+        let events = events.lock().unwrap();
+        events.iter().map(|e| e["event"].as_str().unwrap().to_string()).collect()
+    }
+
+    /// A process table tests can change between checks
+    #[derive(Default)]
+    struct FakeProcesses(Mutex<ProcessTable>);
+
+    impl FakeProcesses {
+        fn set(&self, processes: &[(u32, u32)], windows: &[(isize, u32)]) {
+            *self.0.lock().unwrap() = ProcessTable {
+                processes: processes
+                    .iter()
+                    .map(|&(pid, parent)| ProcessEntry { pid, parent })
+                    .collect(),
+                windows: windows
+                    .iter()
+                    .map(|&(id, pid)| WindowEntry {
+                        id,
+                        pid,
+                        title: "FINAL FANTASY XI".to_string(),
+                    })
+                    .collect(),
+            };
+        }
+    }
+
+    impl ProcessQuery for FakeProcesses {
+        fn snapshot(&self) -> ProcessTable {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    struct Fixture {
+        config: Config,
+        launcher: MockLauncher,
+        processes: FakeProcesses,
+        clock: FakeClock,
+        watchdog: Watchdog,
+    }
+
+    impl Fixture {
+        /// Launch `names` through a mock launcher (pids 1001, 1002, ... with windows of
+        /// the same ids) and watch them
+        fn new(names: &[&str], max_restarts_per_hour: u32) -> Self {
+            let config = config(names);
+            let mut launcher = MockLauncher::default();
+            let characters: Vec<_> = config.characters.iter().collect();
+            let report = launcher.login(&config, &characters, &AtomicBool::new(false));
+            let mut watchdog = Watchdog::new(settings(max_restarts_per_hour));
+            watchdog.track(&report);
+            Self {
+                config,
+                launcher,
+                processes: FakeProcesses::default(),
+                clock: FakeClock::new(chrono::Utc::now()),
+                watchdog,
+            }
+        }
+
+        fn check(&mut self, out: &Output) {
+            self.watchdog
+                .check(&self.config, &mut self.launcher, &self.processes, &self.clock, out);
+        }
+
+        /// Check once to notice the missing window, then again once it counts as lost
+        fn lose(&mut self, out: &Output) {
+            self.check(out);
+            self.clock.advance(LOST_AFTER);
+            self.check(out);
+        }
+    }
+
+    #[test]
+    fn follows_a_window_that_moves_to_a_new_id() {
+        let (out, events) = recording_output();
+        let mut f = Fixture::new(&["MyWarrior"], 3);
+        // PlayOnline's window closed; FFXI opened one in a child process
+        f.processes.set(&[(1001, 1), (2001, 1001)], &[(77, 2001)]);
+        f.check(&out);
+        f.clock.advance(LOST_AFTER * 2);
+        f.check(&out);
+
+        assert!(names(&events).is_empty());
+        assert_eq!(f.launcher.runs.len(), 1);
+        assert_eq!(f.watchdog.watched[0].instance.window, 77);
+
+        // Now only the new window counts
+        f.processes.set(&[(1001, 1), (2001, 1001)], &[(1001, 1001)]);
+        f.check(&out);
+        assert_eq!(f.watchdog.watched[0].instance.window, 1001);
+    }
+
+    #[test]
+    fn waits_out_the_grace_period_before_relaunching() {
+        let (out, events) = recording_output();
+        let mut f = Fixture::new(&["MyWarrior"], 3);
+        f.processes.set(&[(1001, 1)], &[]);
+        f.check(&out);
+        f.clock.advance(LOST_AFTER - Duration::from_secs(1));
+        f.check(&out);
+        assert!(names(&events).is_empty());
+        assert_eq!(f.launcher.runs.len(), 1);
+
+        f.clock.advance(Duration::from_secs(1));
+        f.check(&out);
+        assert_eq!(names(&events), ["game_lost", "relaunching"]);
+        assert_eq!(f.launcher.runs.len(), 2);
+        assert_eq!(f.watchdog.watched[0].instance.pid, 1002);
+    }
+
+    #[test]
+    fn a_window_back_within_the_grace_period_resets_it() {
+        let (out, events) = recording_output();
+        let mut f = Fixture::new(&["MyWarrior"], 3);
+        f.processes.set(&[(1001, 1)], &[]);
+        f.check(&out);
+        f.clock.advance(Duration::from_secs(20));
+        f.processes.set(&[(1001, 1)], &[(1001, 1001)]);
+        f.check(&out);
+        f.processes.set(&[(1001, 1)], &[]);
+        f.clock.advance(Duration::from_secs(20));
+        f.check(&out);
+
+        assert!(names(&events).is_empty());
+    }
+
+    #[test]
+    fn tells_a_closed_window_from_an_exited_process() {
+        let (out, events) = recording_output();
+        let mut f = Fixture::new(&["MyWarrior", "MyMage"], 3);
+        // MyWarrior's Windower (1001) is gone but its child still runs; MyMage's tree is gone
+        f.processes.set(&[(2001, 1001)], &[]);
+        f.lose(&out);
+
+        let events = events.lock().unwrap();
+        let lost: Vec<_> = events
+            .iter()
+            .filter(|e| e["event"] == "game_lost")
+            .map(|e| (e["character"].as_str().unwrap(), e["reason"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            lost,
+            [("MyWarrior", "window closed"), ("MyMage", "process exited")]
+        );
+    }
+
+    #[test]
+    fn gives_up_after_max_restarts_in_an_hour() {
+        let (out, events) = recording_output();
+        let mut f = Fixture::new(&["MyWarrior"], 2);
+        for _ in 0..3 {
+            f.lose(&out);
+        }
+
+        assert_eq!(
+            names(&events),
+            [
+                "game_lost",
+                "relaunching",
+                "game_lost",
+                "relaunching",
+                "game_lost",
+                "watchdog_gave_up"
+            ]
+        );
+        let events = events.lock().unwrap();
+        let restarts: Vec<_> = events
+            .iter()
+            .filter(|e| e["event"] == "relaunching")
+            .map(|e| (e["restart"].as_u64().unwrap(), e["max"].as_u64().unwrap()))
+            .collect();
+        assert_eq!(restarts, [(1, 2), (2, 2)]);
+        assert_eq!(f.launcher.runs.len(), 3);
+        assert!(f.watchdog.watching().is_empty());
+    }
+
+    #[test]
+    fn restarts_older_than_an_hour_stop_counting() {
+        let (out, events) = recording_output();
+        let mut f = Fixture::new(&["MyWarrior"], 2);
+        f.lose(&out);
+        f.lose(&out);
+        f.clock.advance(RESTART_WINDOW);
+        f.lose(&out);
+
+        let events = events.lock().unwrap();
+        let restarts: Vec<_> = events
+            .iter()
+            .filter(|e| e["event"] == "relaunching")
+            .map(|e| e["restart"].as_u64().unwrap())
+            .collect();
+        assert_eq!(restarts, [1, 2, 1]);
+        assert!(!events.iter().any(|e| e["event"] == "watchdog_gave_up"));
+        assert_eq!(f.watchdog.watching(), ["MyWarrior"]);
+    }
+
+    #[test]
+    fn drops_characters_removed_from_the_config() {
+        let (out, events) = recording_output();
+        let mut f = Fixture::new(&["MyWarrior", "MyMage"], 3);
+        f.config = config(&["MyWarrior"]);
+        f.processes.set(&[(1001, 1)], &[(1001, 1001)]);
+        f.lose(&out);
+
+        assert!(names(&events).is_empty());
+        assert_eq!(f.launcher.runs.len(), 1);
+        assert_eq!(f.watchdog.watching(), ["MyWarrior"]);
+    }
+
+    #[test]
+    fn tick_checks_once_per_interval() {
+        let (out, events) = recording_output();
+        let mut f = Fixture::new(&["MyWarrior"], 3);
+        let tick = |f: &mut Fixture| {
+            f.watchdog
+                .tick(&f.config, &mut f.launcher, &f.processes, &f.clock, &out)
+        };
+        tick(&mut f);
+        f.clock.advance(Duration::from_secs(29));
+        tick(&mut f);
+        // Missing for longer than the grace period, but the next check isn't due yet
+        f.clock.advance(Duration::from_secs(5));
+        tick(&mut f);
+        assert!(names(&events).is_empty());
+        f.clock.advance(Duration::from_secs(5));
+        tick(&mut f);
+        assert_eq!(names(&events), ["game_lost", "relaunching"]);
+    }
+
+    #[test]
+    fn run_returns_once_nothing_is_left_to_watch() {
+        let (out, events) = recording_output();
+        let mut f = Fixture::new(&["MyWarrior"], 1);
+        f.watchdog
+            .run(&f.config, &mut f.launcher, &f.processes, &f.clock, &out);
+
+        assert_eq!(
+            names(&events),
+            ["game_lost", "relaunching", "game_lost", "watchdog_gave_up"]
+        );
+        // Each loss takes the grace period, rounded up to whole check intervals
+        assert_eq!(f.clock.elapsed(), Duration::from_secs(80));
+    }
+}
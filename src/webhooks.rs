@@ -0,0 +1,486 @@
+use crate::config::{Config, WebhookConfig};
+use crate::events::{Event, Output};
+use serde_json::Value;
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Events a webhook can subscribe to
+pub const WEBHOOK_EVENTS: &[&str] = &[
+    "run_started",
+    "character_logged_in",
+    "character_failed",
+    "run_finished",
+];
+
+/// Placeholders available in body templates, written as `{{name}}`
+pub const TEMPLATE_VARS: &[&str] = &[
+    "event",
+    "host",
+    "character",
+    "characters",
+    "status",
+    "succeeded",
+    "failed",
+];
+
+/// Methods a webhook may use
+const METHODS: &[&str] = &["GET", "POST", "PUT", "PATCH", "DELETE"];
+
+/// Shown in payloads in place of any password or token
+const REDACTED: &str = "********";
+
+/// Pause before the first retry, doubled every attempt after
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// A webhook-worthy event with the values its templates can use
+pub struct Notification {
+    pub event: &'static str,
+    vars: Vec<(&'static str, String)>,
+    /// The event as JSON, sent when a webhook has no body template
+    payload: Value,
+}
+
+impl Notification {
+    pub fn from_event(event: &Event) -> Option<Self> {
+        let (name, vars) = match event {
+            Event::RunStarted { characters, .. } => (
+                "run_started",
+                vec![(
+                    "characters",
+                    characters.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", "),
+                )],
+            ),
+            Event::LoginComplete { character } => {
+                ("character_logged_in", vec![("character", character.to_string())])
+            }
+            Event::LoginFailed { character }
+            | Event::LaunchFailed { character }
+            | Event::GameWindowNotFound { character, .. }
+            | Event::LobbyFailed { character, .. } => {
+                ("character_failed", vec![("character", character.to_string())])
+            }
+            Event::RunFinished { report } => (
+                "run_finished",
+                vec![
+                    ("status", serde_json::to_value(report.status).ok()?.as_str()?.to_string()),
+                    ("succeeded", report.succeeded.to_string()),
+                    ("failed", report.failed.to_string()),
+                    (
+                        "characters",
+                        report.characters.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", "),
+                    ),
+                ],
+            ),
+            _ => return None,
+        };
+
+        let host = std::env::var("COMPUTERNAME")
+            .or_else(|_| std::env::var("HOSTNAME"))
+            .unwrap_or_default();
+        let mut all_vars = vec![("event", name.to_string()), ("host", host)];
+        all_vars.extend(vars);
+
+        Some(Self {
+            event: name,
+            vars: all_vars,
+            payload: serde_json::to_value(event).ok()?,
+        })
+    }
+
+    fn var(&self, name: &str) -> &str {
+        self.vars
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map_or("", |(_, v)| v.as_str())
+    }
+}
+
+/// Check a webhook definition: URL scheme, method, event names and template placeholders
+pub fn validate_hook(hook: &WebhookConfig) -> Result<(), String> {
+    if !(hook.url.starts_with("http://") || hook.url.starts_with("https://")) {
+        return Err(format!("url {} must start with http:// or https://", display_url(&hook.url)));
+    }
+    if !METHODS.contains(&hook.method.to_ascii_uppercase().as_str()) {
+        return Err(format!("method '{}' must be one of {}", hook.method, METHODS.join(", ")));
+    }
+    if let Some(unknown) = hook.events.iter().find(|e| !WEBHOOK_EVENTS.contains(&e.as_str())) {
+        return Err(format!(
+            "unknown event '{}' (expected one of {})",
+            unknown,
+            WEBHOOK_EVENTS.join(", ")
+        ));
+    }
+    if let Some(body) = &hook.body {
+        let mut unknown = None;
+        visit_strings(body, &mut |s| {
+            for name in placeholders(s) {
+                if !TEMPLATE_VARS.contains(&name) && unknown.is_none() {
+                    unknown = Some(name.to_string());
+                }
+            }
+        });
+        if let Some(name) = unknown {
+            return Err(format!(
+                "unknown placeholder {{{{{}}}}} in body (available: {})",
+                name,
+                TEMPLATE_VARS.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn visit_strings(value: &Value, f: &mut impl FnMut(&str)) {
+    match value {
+        Value::String(s) => f(s),
+        Value::Array(items) => items.iter().for_each(|v| visit_strings(v, f)),
+        Value::Object(obj) => obj.values().for_each(|v| visit_strings(v, f)),
+        _ => {}
+    }
+}
+
+/// Names of the `{{name}}` placeholders in `text`
+fn placeholders(text: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        names.push(rest[start + 2..start + 2 + len].trim());
+        rest = &rest[start + 2 + len + 2..];
+    }
+    names
+}
+
+/// `text` with each `{{name}}`, spaces inside the braces allowed, replaced by its value
+fn fill_text(text: &str, notification: &Notification) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        out.push_str(notification.var(rest[start + 2..start + 2 + len].trim()));
+        rest = &rest[start + 2 + len + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn fill(value: &Value, notification: &Notification) -> Value {
+    match value {
+        Value::String(s) => Value::String(fill_text(s, notification)),
+        Value::Array(items) => Value::Array(items.iter().map(|v| fill(v, notification)).collect()),
+        Value::Object(obj) => Value::Object(
+            obj.iter()
+                .map(|(k, v)| (k.clone(), fill(v, notification)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Request body for `hook`: its template filled in, or the event itself as JSON.
+/// Any secret that ends up in the body is replaced with `********`.
+pub fn render(hook: &WebhookConfig, notification: &Notification, secrets: &[String]) -> String {
+    let mut body = match &hook.body {
+        Some(template) => fill(template, notification),
+        None => notification.payload.clone(),
+    };
+    redact(&mut body, secrets);
+    body.to_string()
+}
+
+/// Replace secrets inside string values, leaving the JSON structure intact
+pub fn redact(value: &mut Value, secrets: &[String]) {
+    match value {
+        Value::String(s) => {
+            for secret in secrets.iter().filter(|s| !s.is_empty()) {
+                if s.contains(secret.as_str()) {
+                    *s = s.replace(secret.as_str(), REDACTED);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| redact(v, secrets)),
+        Value::Object(obj) => obj.values_mut().for_each(|v| redact(v, secrets)),
+        _ => {}
+    }
+}
+
+/// Scheme and host only, for logs; webhook URLs often carry a token in the path or query
+pub fn display_url(url: &str) -> String {
+    match url.split_once("://") {
+        Some((scheme, rest)) => {
+            let host = rest.split(['/', '?']).next().unwrap_or_default();
+            format!("{}://{}/…", scheme, host)
+        }
+        None => "<invalid url>".to_string(),
+    }
+}
+
+/// Send `body` to `hook`, retrying transport errors, 429 and 5xx responses.
+/// Waits `retry_delay` before the first retry, doubling it for each one after.
+pub fn deliver(hook: &WebhookConfig, body: &str, retry_delay: Duration) -> Result<(), String> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(hook.timeout_seconds))
+        .build();
+    let method = hook.method.to_ascii_uppercase();
+
+    let mut delay = retry_delay;
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let mut request = agent
+            .request(&method, &hook.url)
+            .set("Content-Type", "application/json")
+            .set("User-Agent", concat!("login-rs/", env!("CARGO_PKG_VERSION")));
+        for (name, value) in &hook.headers {
+            request = request.set(name, value);
+        }
+        let result = if method == "GET" {
+            request.call()
+        } else {
+            request.send_string(body)
+        };
+
+        let error = match result {
+            Ok(_) => return Ok(()),
+            Err(ureq::Error::Status(code, _)) if code != 429 && code < 500 => {
+                return Err(format!("HTTP {}", code));
+            }
+            Err(ureq::Error::Status(code, _)) => format!("HTTP {}", code),
+            // Transport errors can include the URL, which may contain a token
+            Err(ureq::Error::Transport(t)) => t.kind().to_string(),
+        };
+
+        if attempt > hook.retries {
+            return Err(format!("{} (after {} attempt(s))", error, attempt));
+        }
+        log::warn!(
+            "Webhook {} failed ({}), retrying in {:?}",
+            display_url(&hook.url),
+            error,
+            delay
+        );
+        thread::sleep(delay);
+        delay *= 2;
+    }
+}
+
+enum Job {
+    Send { hook: usize, body: String },
+    Stop,
+}
+
+/// Delivers webhooks on a background thread so slow endpoints never hold up a login
+pub struct Webhooks {
+    sender: Sender<Job>,
+    worker: JoinHandle<()>,
+}
+
+impl Webhooks {
+    /// Subscribe the config's webhooks to `out`. Returns `None` if there are none.
+    pub fn start(config: &Config, out: &Output) -> Option<Self> {
+        if config.webhooks.is_empty() {
+            return None;
+        }
+
+        let hooks = config.webhooks.clone();
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let worker = thread::spawn(move || {
+            while let Ok(Job::Send { hook, body }) = receiver.recv() {
+                let hook = &hooks[hook];
+                match deliver(hook, &body, RETRY_DELAY) {
+                    Ok(()) => log::info!("Webhook {} delivered", display_url(&hook.url)),
+                    Err(e) => log::error!("Webhook {} failed: {}", display_url(&hook.url), e),
+                }
+            }
+        });
+
+        let hooks = config.webhooks.clone();
+        let secrets = config.secrets();
+        let events = sender.clone();
+        out.subscribe(move |event| {
+            let Some(notification) = Notification::from_event(event) else {
+                return;
+            };
+            for (i, hook) in hooks.iter().enumerate() {
+                if hook.events.is_empty() || hook.events.iter().any(|e| e == notification.event) {
+                    let body = render(hook, &notification, &secrets);
+                    let _ = events.send(Job::Send { hook: i, body });
+                }
+            }
+        });
+
+        Some(Self { sender, worker })
+    }
+
+    /// Wait for queued webhooks to be delivered (or give up). Call before exiting.
+    pub fn finish(self) {
+        let _ = self.sender.send(Job::Stop);
+        let _ = self.worker.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy;
+    use crate::report::RunReport;
+    use serde_json::json;
+    use std::net::TcpListener;
+    use std::sync::mpsc::Receiver;
+
+    /// What the stand-in server saw of one request
+    struct Received {
+        method: String,
+        path: String,
+        content_type: Option<String>,
+        api_key: Option<String>,
+        body: String,
+    }
+
+    /// Answer one request with each of `statuses` in turn, then stop listening.
+    /// Returns the server's URL and the requests it received.
+    fn stand_in(statuses: &[u16]) -> (String, Receiver<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks/abc123", listener.local_addr().unwrap());
+        let (sender, received) = mpsc::channel();
+        let statuses = statuses.to_vec();
+        thread::spawn(move || {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let request = proxy::read_request(&mut stream).unwrap();
+                let _ = sender.send(Received {
+                    method: request.method.clone(),
+                    path: request.path.clone(),
+                    content_type: request.header("content-type").map(String::from),
+                    api_key: request.header("x-api-key").map(String::from),
+                    body: String::from_utf8(request.body).unwrap(),
+                });
+                proxy::write_response(&mut stream, status, "application/json", "{}").unwrap();
+            }
+        });
+        (url, received)
+    }
+
+    fn hook(fields: Value) -> WebhookConfig {
+        let mut hook = json!({"url": "http://127.0.0.1:1/", "timeout_seconds": 5});
+        hook.as_object_mut()
+            .unwrap()
+            .extend(fields.as_object().unwrap().clone());
+        serde_json::from_value(hook).unwrap()
+    }
+
+    fn failed(character: &str) -> Notification {
+        Notification::from_event(&Event::LoginFailed { character }).unwrap()
+    }
+
+    #[test]
+    fn posts_the_filled_template_with_secrets_redacted() {
+        let (url, received) = stand_in(&[200]);
+        let hook = hook(json!({
+            "url": url,
+            "headers": {"X-Api-Key": "k3y"},
+            "body": {
+                "text": "{{character}} failed ({{event}})",
+                "debug": ["password was Passw0rd!", 3, "{{ character }} {{"]
+            }
+        }));
+        let body = render(&hook, &failed("MyWarrior"), &["Passw0rd!".to_string(), String::new()]);
+        deliver(&hook, &body, Duration::ZERO).unwrap();
+
+        let request = received.recv().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/hooks/abc123");
+        assert_eq!(request.content_type.as_deref(), Some("application/json"));
+        assert_eq!(request.api_key.as_deref(), Some("k3y"));
+        assert_eq!(
+            serde_json::from_str::<Value>(&request.body).unwrap(),
+            json!({
+                "text": "MyWarrior failed (character_failed)",
+                "debug": ["password was ********", 3, "MyWarrior {{"]
+            })
+        );
+    }
+
+    #[test]
+    fn without_a_template_the_event_is_sent() {
+        let (url, received) = stand_in(&[204]);
+        let hook = hook(json!({"url": url, "method": "put"}));
+        let body = render(&hook, &failed("MyWarrior"), &[]);
+        deliver(&hook, &body, Duration::ZERO).unwrap();
+
+        let request = received.recv().unwrap();
+        assert_eq!(request.method, "PUT");
+        assert_eq!(request.body, r#"{"event":"login_failed","character":"MyWarrior"}"#);
+    }
+
+    #[test]
+    fn get_sends_no_body() {
+        let (url, received) = stand_in(&[200]);
+        let hook = hook(json!({"url": url, "method": "GET"}));
+        deliver(&hook, "{}", Duration::ZERO).unwrap();
+
+        let request = received.recv().unwrap();
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.body, "");
+    }
+
+    #[test]
+    fn server_errors_and_rate_limits_are_retried() {
+        let (url, received) = stand_in(&[503, 429, 200]);
+        let hook = hook(json!({"url": url, "retries": 2}));
+        deliver(&hook, "{}", Duration::ZERO).unwrap();
+        assert_eq!(received.try_iter().count(), 3);
+    }
+
+    #[test]
+    fn retries_run_out() {
+        let (url, received) = stand_in(&[500, 502]);
+        let hook = hook(json!({"url": url, "retries": 1}));
+        let err = deliver(&hook, "{}", Duration::ZERO).unwrap_err();
+        assert_eq!(err, "HTTP 502 (after 2 attempt(s))");
+        assert_eq!(received.try_iter().count(), 2);
+    }
+
+    #[test]
+    fn client_errors_are_not_retried() {
+        let (url, received) = stand_in(&[404, 200]);
+        let hook = hook(json!({"url": url, "retries": 3}));
+        assert_eq!(deliver(&hook, "{}", Duration::ZERO).unwrap_err(), "HTTP 404");
+        assert_eq!(received.try_iter().count(), 1);
+    }
+
+    #[test]
+    fn run_finished_fills_the_summary_placeholders() {
+        let report = RunReport::build(&[], &Default::default());
+        let finished = Event::RunFinished { report: &report };
+        let notification = Notification::from_event(&finished).unwrap();
+        assert_eq!(notification.event, "run_finished");
+        let hook = hook(json!({"body": "{{status}}: {{succeeded}} ok, {{failed}} failed"}));
+        assert_eq!(
+            render(&hook, &notification, &[]),
+            json!(format!("{}: 0 ok, 0 failed", notification.var("status"))).to_string()
+        );
+        assert!(Notification::from_event(&Event::Done).is_none());
+    }
+
+    #[test]
+    fn hooks_are_checked() {
+        assert!(validate_hook(&hook(json!({}))).is_ok());
+        for (fields, error) in [
+            (json!({"url": "ftp://example.com/x?token=s3cret"}), "url ftp://example.com/… must"),
+            (json!({"method": "FETCH"}), "method 'FETCH' must be one of"),
+            (json!({"events": ["run_exploded"]}), "unknown event 'run_exploded'"),
+            (json!({"body": {"a": ["{{nope}}"]}}), "unknown placeholder {{nope}}"),
+        ] {
+            let err = validate_hook(&hook(fields)).unwrap_err();
+            assert!(err.starts_with(error), "{}", err);
+            assert!(!err.contains("s3cret"));
+        }
+    }
+}
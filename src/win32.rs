@@ -4,7 +4,9 @@ pub enum KeyDirection {
     Up,
 }
 
+// Only replayed on Windows
 #[derive(Debug, Clone)]
+#[cfg_attr(not(windows), allow(dead_code))]
 pub struct RecordedKey {
     pub vk_code: u16,
     pub direction: KeyDirection,
@@ -14,6 +16,8 @@ pub struct RecordedKey {
 #[cfg(windows)]
 mod platform {
     use super::{KeyDirection, RecordedKey};
+    use crate::layout::{Monitor, Rect};
+    use crate::typing::{InputEvent, KeyStroke};
     use std::ffi::OsStr;
     use std::os::windows::ffi::OsStrExt;
     use std::path::Path;
@@ -33,6 +37,7 @@ mod platform {
         GetMessageW, MSG, WH_KEYBOARD_LL, KBDLLHOOKSTRUCT,
         WM_KEYDOWN, WM_KEYUP, WM_SYSKEYDOWN, WM_SYSKEYUP,
         GetWindowRect, SetCursorPos, WindowFromPoint, GetCursorPos,
+        GetWindowThreadProcessId,
     };
     use windows_sys::Win32::Foundation::POINT;
     use windows_sys::Win32::Foundation::RECT;
@@ -68,12 +73,20 @@ mod platform {
         }
     }
 
-    /// Re-launch the current process elevated via UAC, then exit
+    /// Re-launch the current process elevated via UAC, wait for it, and exit with its exit code
     pub fn elevate_self() -> ! {
+        use crate::exit_code::ExitCode;
+        use windows_sys::Win32::System::Threading::{
+            GetExitCodeProcess, WaitForSingleObject, INFINITE,
+        };
+
         let exe = std::env::current_exe()
             .expect("Failed to get current exe path");
-        let args: Vec<String> = std::env::args().skip(1).collect();
-        let args_str = args.join(" ");
+        let args_str = std::env::args()
+            .skip(1)
+            .map(|a| super::quote_arg(&a))
+            .collect::<Vec<_>>()
+            .join(" ");
 
         let verb: Vec<u16> = OsStr::new("runas")
             .encode_wide().chain(std::iter::once(0)).collect();
@@ -98,10 +111,20 @@ mod platform {
         let success = unsafe { ShellExecuteExW(&mut sei) };
         if success == FALSE {
             eprintln!("Failed to elevate. Please run as administrator.");
-            std::process::exit(1);
+            ExitCode::ElevationRefused.exit();
+        }
+
+        // Propagate the elevated instance's exit code so callers can script around it
+        let mut exit_code = ExitCode::Success.code() as u32;
+        if !sei.hProcess.is_null() {
+            unsafe {
+                WaitForSingleObject(sei.hProcess, INFINITE);
+                GetExitCodeProcess(sei.hProcess, &mut exit_code);
+                windows_sys::Win32::Foundation::CloseHandle(sei.hProcess);
+            }
         }
 
-        std::process::exit(0);
+        std::process::exit(exit_code as i32);
     }
 
     /// Launch a process with optional command-line arguments and return its process ID
@@ -155,83 +178,62 @@ mod platform {
         Ok(proc_info.dwProcessId)
     }
 
-    /// Find a window by title prefix, returning its HWND
-    pub fn find_window_by_title_prefix(prefix: &str) -> Option<HWND> {
-        struct SearchData {
-            prefix: Vec<u16>,
-            result: Option<HWND>,
-        }
-
-        let prefix_wide: Vec<u16> = OsStr::new(prefix)
-            .encode_wide()
-            .collect();
-
-        let mut data = SearchData {
-            prefix: prefix_wide,
-            result: None,
+    /// Every running process as (pid, parent pid)
+    pub fn list_processes() -> Vec<(u32, u32)> {
+        use windows_sys::Win32::Foundation::{CloseHandle, INVALID_HANDLE_VALUE};
+        use windows_sys::Win32::System::Diagnostics::ToolHelp::{
+            CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+            TH32CS_SNAPPROCESS,
         };
 
-        unsafe extern "system" fn callback(hwnd: HWND, lparam: LPARAM) -> BOOL {
-            let data = &mut *(lparam as *mut SearchData);
-            if IsWindowVisible(hwnd) == FALSE {
-                return TRUE; // continue
+        let mut processes = Vec::new();
+        unsafe {
+            let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+            if snapshot == INVALID_HANDLE_VALUE {
+                return processes;
             }
-            let mut title = [0u16; 256];
-            let len = GetWindowTextW(hwnd, title.as_mut_ptr(), 256);
-            if len > 0 {
-                let title_slice = &title[..len as usize];
-                if title_slice.starts_with(&data.prefix) {
-                    data.result = Some(hwnd);
-                    return FALSE; // stop
-                }
+            let mut entry: PROCESSENTRY32W = std::mem::zeroed();
+            entry.dwSize = std::mem::size_of::<PROCESSENTRY32W>() as u32;
+            let mut more = Process32FirstW(snapshot, &mut entry);
+            while more != FALSE {
+                processes.push((entry.th32ProcessID, entry.th32ParentProcessID));
+                more = Process32NextW(snapshot, &mut entry);
             }
-            TRUE // continue
-        }
-
-        unsafe {
-            EnumWindows(Some(callback), &mut data as *mut SearchData as LPARAM);
+            CloseHandle(snapshot);
         }
-
-        data.result
+        processes
     }
 
-    /// Find all windows matching a title prefix
-    pub fn find_windows_by_title_prefix(prefix: &str) -> Vec<HWND> {
-        struct SearchData {
-            prefix: Vec<u16>,
-            results: Vec<HWND>,
-        }
-
-        let prefix_wide: Vec<u16> = OsStr::new(prefix)
-            .encode_wide()
-            .collect();
-
-        let mut data = SearchData {
-            prefix: prefix_wide,
-            results: Vec::new(),
-        };
-
+    /// Every visible top-level window as (handle, owning pid, title)
+    pub fn list_windows() -> Vec<(HWND, u32, String)> {
         unsafe extern "system" fn callback(hwnd: HWND, lparam: LPARAM) -> BOOL {
-            let data = &mut *(lparam as *mut SearchData);
+            let windows = &mut *(lparam as *mut Vec<(HWND, u32, String)>);
             if IsWindowVisible(hwnd) == FALSE {
                 return TRUE;
             }
+            let mut pid = 0u32;
+            GetWindowThreadProcessId(hwnd, &mut pid);
             let mut title = [0u16; 256];
-            let len = GetWindowTextW(hwnd, title.as_mut_ptr(), 256);
-            if len > 0 {
-                let title_slice = &title[..len as usize];
-                if title_slice.starts_with(&data.prefix) {
-                    data.results.push(hwnd);
-                }
-            }
+            let len = GetWindowTextW(hwnd, title.as_mut_ptr(), 256).max(0) as usize;
+            windows.push((hwnd, pid, String::from_utf16_lossy(&title[..len])));
             TRUE
         }
 
+        let mut windows: Vec<(HWND, u32, String)> = Vec::new();
         unsafe {
-            EnumWindows(Some(callback), &mut data as *mut SearchData as LPARAM);
+            EnumWindows(Some(callback), &mut windows as *mut Vec<(HWND, u32, String)> as LPARAM);
         }
+        windows
+    }
 
-        data.results
+    /// A window handle as a plain integer, for code that doesn't deal in raw handles
+    pub fn window_id(hwnd: HWND) -> isize {
+        hwnd as isize
+    }
+
+    /// Inverse of `window_id`
+    pub fn window_handle(id: isize) -> HWND {
+        id as HWND
     }
 
     /// Set a window's title
@@ -245,6 +247,96 @@ mod platform {
         }
     }
 
+    /// Move and resize a window without activating it
+    pub fn move_window(hwnd: HWND, rect: Rect) {
+        use windows_sys::Win32::UI::WindowsAndMessaging::{SetWindowPos, SWP_NOACTIVATE, SWP_NOZORDER};
+        unsafe {
+            ShowWindow(hwnd, SW_RESTORE);
+            SetWindowPos(
+                hwnd,
+                std::ptr::null_mut(),
+                rect.x,
+                rect.y,
+                rect.width,
+                rect.height,
+                SWP_NOZORDER | SWP_NOACTIVATE,
+            );
+        }
+    }
+
+    /// Work in physical pixels on every monitor, so window positions and monitor
+    /// sizes aren't scaled behind our back on high-DPI displays
+    pub fn enable_dpi_awareness() {
+        use windows_sys::Win32::UI::HiDpi::{
+            SetProcessDpiAwarenessContext, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
+        };
+        unsafe {
+            // Fails harmlessly if awareness was already set
+            SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+        }
+    }
+
+    /// Every connected monitor, in the order Windows lists them
+    pub fn list_monitors() -> Vec<Monitor> {
+        use windows_sys::Win32::Graphics::Gdi::{
+            EnumDisplayMonitors, GetMonitorInfoW, HDC, HMONITOR, MONITORINFO,
+        };
+        use windows_sys::Win32::UI::HiDpi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI};
+        use windows_sys::Win32::UI::WindowsAndMessaging::MONITORINFOF_PRIMARY;
+
+        fn rect(r: &RECT) -> Rect {
+            Rect {
+                x: r.left,
+                y: r.top,
+                width: r.right - r.left,
+                height: r.bottom - r.top,
+            }
+        }
+
+        unsafe extern "system" fn callback(
+            monitor: HMONITOR,
+            _hdc: HDC,
+            _clip: *mut RECT,
+            lparam: LPARAM,
+        ) -> BOOL {
+            let monitors = &mut *(lparam as *mut Vec<Monitor>);
+            let mut info: MONITORINFO = std::mem::zeroed();
+            info.cbSize = std::mem::size_of::<MONITORINFO>() as u32;
+            if GetMonitorInfoW(monitor, &mut info) == FALSE {
+                return TRUE;
+            }
+            let (mut dpi_x, mut dpi_y) = (96u32, 96u32);
+            if GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) != 0 {
+                dpi_x = 96;
+            }
+            monitors.push(Monitor {
+                bounds: rect(&info.rcMonitor),
+                work_area: rect(&info.rcWork),
+                scale: dpi_x as f64 / 96.0,
+                primary: info.dwFlags & MONITORINFOF_PRIMARY != 0,
+            });
+            TRUE
+        }
+
+        let mut monitors: Vec<Monitor> = Vec::new();
+        unsafe {
+            EnumDisplayMonitors(
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                Some(callback),
+                &mut monitors as *mut Vec<Monitor> as LPARAM,
+            );
+        }
+        monitors
+    }
+
+    /// The window currently in front, if any
+    pub fn foreground_window() -> Option<HWND> {
+        use windows_sys::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+        let hwnd = unsafe { GetForegroundWindow() };
+        (!hwnd.is_null()).then_some(hwnd)
+    }
+
     /// Focus a window
     pub fn focus_window(hwnd: HWND) {
         unsafe {
@@ -312,56 +404,158 @@ mod platform {
         std::thread::sleep(std::time::Duration::from_millis(hold_ms));
     }
 
-    /// Type text character by character using SendInput and VkKeyScan
-    pub fn type_text(text: &str) {
-        use windows_sys::Win32::UI::Input::KeyboardAndMouse::VkKeyScanA;
+    /// The key that types `ch` on the keyboard layout login-rs is running under
+    pub fn key_for_char(ch: char) -> Option<KeyStroke> {
+        use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
+            GetKeyboardLayout, MapVirtualKeyExW, VkKeyScanExW, MAPVK_VK_TO_VSC,
+        };
 
-        for ch in text.bytes() {
-            let result = unsafe { VkKeyScanA(ch as i8) };
+        // Characters outside the Basic Multilingual Plane have no key
+        let unit = u16::try_from(ch as u32).ok()?;
+        unsafe {
+            let layout = GetKeyboardLayout(0);
+            let result = VkKeyScanExW(unit, layout);
+            if result == -1 {
+                return None;
+            }
             let vk = (result & 0xFF) as u16;
-            let shift_state = ((result >> 8) & 0xFF) as u16;
-
-            let needs_shift = shift_state & 1 != 0;
-            let vk_shift: u16 = 0x10;
-
-            if needs_shift {
-                // Shift down
-                let mut shift_down: [INPUT; 1] = unsafe { std::mem::zeroed() };
-                shift_down[0].r#type = INPUT_KEYBOARD;
-                shift_down[0].Anonymous.ki = KEYBDINPUT {
-                    wVk: vk_shift, wScan: 0, dwFlags: 0, time: 0, dwExtraInfo: 0,
-                };
-                unsafe { SendInput(1, shift_down.as_ptr(), std::mem::size_of::<INPUT>() as i32); }
+            let shift_state = ((result >> 8) & 0xFF) as u8;
+            // Bit 1 is Shift, bit 2 Ctrl, bit 4 Alt; Ctrl or Alt alone (without the
+            // other) isn't used for typing text
+            let altgr = shift_state & 0x06 == 0x06;
+            if shift_state & 0x06 != 0 && !altgr {
+                return None;
             }
+            Some(KeyStroke {
+                vk,
+                scan: MapVirtualKeyExW(vk as u32, MAPVK_VK_TO_VSC, layout) as u16,
+                shift: shift_state & 0x01 != 0,
+                altgr,
+            })
+        }
+    }
 
-            // Key down + up
-            let mut inputs: [INPUT; 2] = unsafe { std::mem::zeroed() };
-            inputs[0].r#type = INPUT_KEYBOARD;
-            inputs[0].Anonymous.ki = KEYBDINPUT {
-                wVk: vk, wScan: 0, dwFlags: 0, time: 0, dwExtraInfo: 0,
+    /// Send events made by `typing::translate`. Key events between pauses go out in
+    /// one `SendInput` call so nothing can slip in between a key and its modifiers.
+    pub fn send_input(events: &[InputEvent]) {
+        use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
+            KEYEVENTF_EXTENDEDKEY, KEYEVENTF_SCANCODE, KEYEVENTF_UNICODE,
+        };
+
+        let flush = |batch: &mut Vec<INPUT>| {
+            if !batch.is_empty() {
+                unsafe {
+                    SendInput(batch.len() as u32, batch.as_ptr(), std::mem::size_of::<INPUT>() as i32);
+                }
+                batch.clear();
+            }
+        };
+
+        let mut batch: Vec<INPUT> = Vec::new();
+        for event in events {
+            let (vk, scan, flags, up) = match *event {
+                InputEvent::Key { vk, up } => (vk, 0, 0, up),
+                InputEvent::Scan { scan, up } if scan >> 8 == 0xE0 => {
+                    (0, scan & 0xFF, KEYEVENTF_SCANCODE | KEYEVENTF_EXTENDEDKEY, up)
+                }
+                InputEvent::Scan { scan, up } => (0, scan, KEYEVENTF_SCANCODE, up),
+                InputEvent::Unicode { unit, up } => (0, unit, KEYEVENTF_UNICODE, up),
+                InputEvent::Paste(ref text) => {
+                    flush(&mut batch);
+                    paste_text(text);
+                    continue;
+                }
+                InputEvent::Pause(ms) => {
+                    flush(&mut batch);
+                    std::thread::sleep(std::time::Duration::from_millis(ms));
+                    continue;
+                }
             };
-            inputs[1].r#type = INPUT_KEYBOARD;
-            inputs[1].Anonymous.ki = KEYBDINPUT {
-                wVk: vk, wScan: 0, dwFlags: KEYEVENTF_KEYUP, time: 0, dwExtraInfo: 0,
+            let mut input: INPUT = unsafe { std::mem::zeroed() };
+            input.r#type = INPUT_KEYBOARD;
+            input.Anonymous.ki = KEYBDINPUT {
+                wVk: vk,
+                wScan: scan,
+                dwFlags: flags | if up { KEYEVENTF_KEYUP } else { 0 },
+                time: 0,
+                dwExtraInfo: 0,
             };
-            unsafe { SendInput(2, inputs.as_ptr(), std::mem::size_of::<INPUT>() as i32); }
-
-            if needs_shift {
-                // Shift up
-                let mut shift_up: [INPUT; 1] = unsafe { std::mem::zeroed() };
-                shift_up[0].r#type = INPUT_KEYBOARD;
-                shift_up[0].Anonymous.ki = KEYBDINPUT {
-                    wVk: vk_shift, wScan: 0, dwFlags: KEYEVENTF_KEYUP, time: 0, dwExtraInfo: 0,
-                };
-                unsafe { SendInput(1, shift_up.as_ptr(), std::mem::size_of::<INPUT>() as i32); }
+            batch.push(input);
+        }
+        flush(&mut batch);
+    }
+
+    /// Post a key press or release to a window's message queue. The window doesn't
+    /// need focus, and the user's own input is left alone.
+    pub fn post_key(hwnd: HWND, vk: u16, up: bool) {
+        use windows_sys::Win32::UI::Input::KeyboardAndMouse::{MapVirtualKeyW, MAPVK_VK_TO_VSC};
+        use windows_sys::Win32::UI::WindowsAndMessaging::PostMessageW;
+
+        // lParam: repeat count 1, the scan code, and for a release the previous-state
+        // and transition bits
+        let scan = unsafe { MapVirtualKeyW(vk as u32, MAPVK_VK_TO_VSC) };
+        let mut lparam = 1 | (scan << 16);
+        if up {
+            lparam |= (1 << 30) | (1 << 31);
+        }
+        let msg = if up { WM_KEYUP } else { WM_KEYDOWN };
+        unsafe {
+            PostMessageW(hwnd, msg, vk as WPARAM, lparam as i32 as LPARAM);
+        }
+    }
+
+    /// Press a key (down + up) in a window through its message queue
+    pub fn post_key_press(hwnd: HWND, vk: u16, hold_ms: u64) {
+        post_key(hwnd, vk, false);
+        post_key(hwnd, vk, true);
+        std::thread::sleep(std::time::Duration::from_millis(hold_ms));
+    }
+
+    /// Post one mouse wheel notch up to a window, as if the cursor were in its
+    /// neutral zone (see `move_cursor_to_window`)
+    pub fn post_scroll_up(hwnd: HWND) {
+        use windows_sys::Win32::UI::WindowsAndMessaging::{PostMessageW, WM_MOUSEWHEEL};
+
+        unsafe {
+            let mut rect: RECT = std::mem::zeroed();
+            if GetWindowRect(hwnd, &mut rect) == FALSE {
+                return;
             }
+            let x = (rect.left + 50) as u16 as u32;
+            let y = (rect.top + 80) as u16 as u32;
+            // WHEEL_DELTA in the high word of wParam, screen position in lParam
+            PostMessageW(hwnd, WM_MOUSEWHEEL, (120u32 << 16) as WPARAM, ((y << 16) | x) as i32 as LPARAM);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
 
-            std::thread::sleep(std::time::Duration::from_millis(50));
+    /// Deliver events made by `typing::translate` through a window's message queue.
+    /// Characters go as `WM_CHAR`, which needs no keyboard state.
+    pub fn post_input(hwnd: HWND, events: &[InputEvent]) {
+        use windows_sys::Win32::UI::Input::KeyboardAndMouse::{MapVirtualKeyW, MAPVK_VSC_TO_VK};
+        use windows_sys::Win32::UI::WindowsAndMessaging::{PostMessageW, WM_CHAR};
+
+        for event in events {
+            match *event {
+                InputEvent::Key { vk, up } => post_key(hwnd, vk, up),
+                InputEvent::Scan { scan, up } => {
+                    let vk = unsafe { MapVirtualKeyW(scan as u32, MAPVK_VSC_TO_VK) };
+                    post_key(hwnd, vk as u16, up);
+                }
+                InputEvent::Unicode { unit, up: false } => unsafe {
+                    PostMessageW(hwnd, WM_CHAR, unit as WPARAM, 1);
+                },
+                InputEvent::Unicode { up: true, .. } => {}
+                InputEvent::Paste(_) => {
+                    log::warn!("Pasting needs the clipboard and Ctrl+V, which window messages can't send");
+                }
+                InputEvent::Pause(ms) => std::thread::sleep(std::time::Duration::from_millis(ms)),
+            }
         }
     }
 
     /// Paste text by setting the clipboard and pressing Ctrl+V
-    pub fn paste_text(text: &str) {
+    fn paste_text(text: &str) {
         use windows_sys::Win32::System::DataExchange::{
             OpenClipboard, CloseClipboard, EmptyClipboard, SetClipboardData,
         };
@@ -429,6 +623,14 @@ mod platform {
             SendInput(4, inputs.as_ptr(), std::mem::size_of::<INPUT>() as i32);
         }
         std::thread::sleep(std::time::Duration::from_millis(300));
+
+        // Don't leave the text (usually a password) on the clipboard
+        unsafe {
+            if OpenClipboard(std::ptr::null_mut()) != FALSE {
+                EmptyClipboard();
+                CloseClipboard();
+            }
+        }
     }
 
     /// Block or unblock user input (requires admin)
@@ -583,30 +785,69 @@ mod platform {
 #[cfg(not(windows))]
 mod platform {
     use super::RecordedKey;
+    use crate::layout::{Monitor, Rect};
+    use crate::typing::{InputEvent, KeyStroke};
     use std::path::Path;
 
-    // Stub type for HWND on non-Windows
+    // Stub type for HWND on non-Windows, named as windows-sys names it
+    #[allow(clippy::upper_case_acronyms)]
     pub type HWND = isize;
 
+    pub fn is_elevated() -> bool {
+        log::warn!("is_elevated is a stub on non-Windows");
+        true
+    }
+
+    pub fn elevate_self() -> ! {
+        log::warn!("elevate_self is a stub on non-Windows");
+        crate::exit_code::ExitCode::ElevationRefused.exit();
+    }
+
     pub fn launch_process(exe_path: &Path, _args: Option<&str>) -> Result<u32, String> {
         log::warn!("launch_process is a stub on non-Windows: {:?}", exe_path);
         Ok(0)
     }
 
-    pub fn find_window_by_title_prefix(_prefix: &str) -> Option<HWND> {
-        log::warn!("find_window_by_title_prefix is a stub on non-Windows");
-        None
+    pub fn list_processes() -> Vec<(u32, u32)> {
+        log::warn!("list_processes is a stub on non-Windows");
+        Vec::new()
     }
 
-    pub fn find_windows_by_title_prefix(_prefix: &str) -> Vec<HWND> {
-        log::warn!("find_windows_by_title_prefix is a stub on non-Windows");
+    pub fn list_windows() -> Vec<(HWND, u32, String)> {
+        log::warn!("list_windows is a stub on non-Windows");
         Vec::new()
     }
 
+    pub fn window_id(hwnd: HWND) -> isize {
+        hwnd
+    }
+
+    pub fn window_handle(id: isize) -> HWND {
+        id
+    }
+
     pub fn set_window_title(_hwnd: HWND, _title: &str) {
         log::warn!("set_window_title is a stub on non-Windows");
     }
 
+    pub fn move_window(_hwnd: HWND, _rect: Rect) {
+        log::warn!("move_window is a stub on non-Windows");
+    }
+
+    pub fn enable_dpi_awareness() {
+        log::warn!("enable_dpi_awareness is a stub on non-Windows");
+    }
+
+    pub fn list_monitors() -> Vec<Monitor> {
+        log::warn!("list_monitors is a stub on non-Windows");
+        Vec::new()
+    }
+
+    pub fn foreground_window() -> Option<HWND> {
+        log::warn!("foreground_window is a stub on non-Windows");
+        None
+    }
+
     pub fn focus_window(_hwnd: HWND) {
         log::warn!("focus_window is a stub on non-Windows");
     }
@@ -623,12 +864,25 @@ mod platform {
         log::warn!("press_key is a stub on non-Windows");
     }
 
-    pub fn type_text(_text: &str) {
-        log::warn!("type_text is a stub on non-Windows");
+    pub fn key_for_char(ch: char) -> Option<KeyStroke> {
+        // Type as if on a US layout, so configs can still be checked here
+        crate::typing::key_for(crate::config::KeyboardLayout::Us, ch)
+    }
+
+    pub fn send_input(_events: &[InputEvent]) {
+        log::warn!("send_input is a stub on non-Windows");
+    }
+
+    pub fn post_key_press(_hwnd: HWND, _vk: u16, _hold_ms: u64) {
+        log::warn!("post_key_press is a stub on non-Windows");
+    }
+
+    pub fn post_scroll_up(_hwnd: HWND) {
+        log::warn!("post_scroll_up is a stub on non-Windows");
     }
 
-    pub fn paste_text(_text: &str) {
-        log::warn!("paste_text is a stub on non-Windows");
+    pub fn post_input(_hwnd: HWND, _events: &[InputEvent]) {
+        log::warn!("post_input is a stub on non-Windows");
     }
 
     pub fn block_input(_block: bool) {
@@ -646,6 +900,36 @@ mod platform {
 
 pub use platform::*;
 
+/// Quote `arg` for a Windows command line so `CommandLineToArgvW` reads it back unchanged
+#[cfg_attr(not(windows), allow(dead_code))]
+pub fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '\n', '\x0B', '"']) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                // Backslashes before a quote are escapes, so double them and escape the quote
+                quoted.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    // Backslashes before the closing quote would escape it
+    quoted.extend(std::iter::repeat_n('\\', backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
 pub fn vk_name(vk: u16) -> String {
     match vk {
         0x08 => "BACKSPACE".into(),
@@ -685,3 +969,30 @@ pub fn vk_name(vk: u16) -> String {
         _ => format!("VK_0x{:02X}", vk),
     }
 }
+
+/// Inverse of `vk_name`: look up the virtual-key code for a recorded key name
+pub fn vk_from_name(name: &str) -> Option<u16> {
+    (0..=0xFFu16).find(|&vk| vk_name(vk) == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_arguments_are_not_quoted() {
+        assert_eq!(quote_arg("login"), "login");
+        assert_eq!(quote_arg("C:\\Windower4\\"), "C:\\Windower4\\");
+    }
+
+    #[test]
+    fn arguments_are_quoted_per_command_line_to_argv_rules() {
+        assert_eq!(quote_arg(""), r#""""#);
+        assert_eq!(quote_arg("My Warrior"), r#""My Warrior""#);
+        assert_eq!(quote_arg(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote_arg(r#"a\"b"#), r#""a\\\"b""#);
+        // Trailing backslashes are doubled so they don't escape the closing quote
+        assert_eq!(quote_arg(r"C:\Program Files\"), r#""C:\Program Files\\""#);
+        assert_eq!(quote_arg(r"a\b c"), r#""a\b c""#);
+    }
+}
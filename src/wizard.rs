@@ -0,0 +1,548 @@
+use crate::checks::FileSystem;
+use crate::config::{check_password, migrate, Config, ConfigFormat, InputConfig, REGIONS};
+use serde_json::{json, Map, Value};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Where the wizard's questions go and answers come from. The terminal
+/// implementation is `StdioPrompter`; a scripted one can drive the wizard in tests.
+pub trait Prompter {
+    /// Show `prompt` and read one line of input, without the trailing newline.
+    /// Returns `None` when input is exhausted.
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>>;
+
+    /// Like `read_line`, but the input is not shown as it is typed
+    fn read_secret(&mut self, prompt: &str) -> io::Result<Option<String>>;
+
+    /// Show an informational message
+    fn message(&mut self, text: &str);
+}
+
+pub struct StdioPrompter;
+
+impl Prompter for StdioPrompter {
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        print!("{}", prompt);
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+    }
+
+    fn read_secret(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        match rpassword::prompt_password(prompt) {
+            Ok(line) => Ok(Some(line)),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn message(&mut self, text: &str) {
+        println!("{}", text);
+    }
+}
+
+/// Common Windower install locations, checked in order after the folder login-rs runs from
+const WINDOWER_CANDIDATES: &[&str] = &[
+    r"C:\Windower4\Windower.exe",
+    r"C:\Windower\Windower.exe",
+    r"C:\Program Files (x86)\Windower4\Windower.exe",
+    r"C:\Program Files\Windower4\Windower.exe",
+    r"D:\Windower4\Windower.exe",
+];
+
+/// Windower executables that exist on this machine. `exe_dir` is the directory
+/// login-rs itself runs from; the README suggests extracting it inside the Windower folder.
+pub fn detect_windower(fs: &dyn FileSystem, exe_dir: Option<&Path>) -> Vec<PathBuf> {
+    let beside_us = exe_dir
+        .and_then(Path::parent)
+        .map(|windower_dir| windower_dir.join("Windower.exe"));
+
+    let mut found: Vec<PathBuf> = Vec::new();
+    for candidate in beside_us
+        .into_iter()
+        .chain(WINDOWER_CANDIDATES.iter().map(PathBuf::from))
+    {
+        if fs.is_file(&candidate) && !found.contains(&candidate) {
+            found.push(candidate);
+        }
+    }
+    found
+}
+
+pub enum WizardOutcome {
+    Written(PathBuf),
+    Cancelled,
+}
+
+/// Interactively build a config and write it to `path` in `format`
+pub fn run_wizard(
+    prompter: &mut dyn Prompter,
+    fs: &dyn FileSystem,
+    exe_dir: Option<&Path>,
+    path: &Path,
+    format: ConfigFormat,
+    force: bool,
+) -> Result<WizardOutcome, String> {
+    let mut w = Wizard { prompter };
+
+    if fs.is_file(path)
+        && !force
+        && !w.confirm(&format!("{} already exists. Overwrite it?", path.display()), false)?
+    {
+        return Ok(WizardOutcome::Cancelled);
+    }
+
+    w.say("Creating a login-rs config. Press Enter to accept the [default].\n");
+
+    let windower_path = w.ask_windower_path(fs, exe_dir)?;
+    let profile = w.ask("Windower profile (blank for the default profile)", Some(""), |_| Ok(()))?;
+    let region = w.ask("PlayOnline region (us, jp, eu)", Some("us"), |r| {
+        if REGIONS.contains(&r) {
+            Ok(())
+        } else {
+            Err(format!("Region must be one of {}", REGIONS.join(", ")))
+        }
+    })?;
+
+    let mut characters: Vec<Value> = Vec::new();
+    loop {
+        w.say(&format!("\nCharacter {}", characters.len() + 1));
+        characters.push(w.ask_character(&characters)?);
+        if characters.len() >= 20 || !w.confirm("Add another character?", false)? {
+            break;
+        }
+    }
+
+    let mut config = Map::new();
+    config.insert("version".into(), json!(migrate::CURRENT_VERSION));
+    config.insert("windower_path".into(), json!(windower_path));
+    if !profile.is_empty() {
+        config.insert("windower_profile".into(), json!(profile));
+    }
+    config.insert("region".into(), json!(region));
+    config.insert("characters".into(), Value::Array(characters));
+
+    let contents = format.serialize(&Value::Object(config))?;
+
+    // Round-trip through the real loader so we never write a config login-rs would reject
+    Config::parse(&contents, format)
+        .and_then(|c| c.validate().map(|_| c))
+        .map_err(|e| format!("Generated config is invalid: {}", e))?;
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(path, contents).map_err(|e| e.to_string())?;
+    Ok(WizardOutcome::Written(path.to_path_buf()))
+}
+
+struct Wizard<'a> {
+    prompter: &'a mut dyn Prompter,
+}
+
+impl Wizard<'_> {
+    fn say(&mut self, text: &str) {
+        self.prompter.message(text);
+    }
+
+    /// Ask until the answer passes `check`. An empty answer takes the default, if any.
+    fn ask(
+        &mut self,
+        question: &str,
+        default: Option<&str>,
+        check: impl Fn(&str) -> Result<(), String>,
+    ) -> Result<String, String> {
+        let prompt = match default {
+            Some(d) if !d.is_empty() => format!("{} [{}]: ", question, d),
+            _ => format!("{}: ", question),
+        };
+        loop {
+            let line = self
+                .prompter
+                .read_line(&prompt)
+                .map_err(|e| e.to_string())?
+                .ok_or("Input ended before the config was complete")?;
+            let answer = match (line.trim(), default) {
+                ("", Some(d)) => d.to_string(),
+                (a, _) => a.to_string(),
+            };
+            match check(&answer) {
+                Ok(()) => return Ok(answer),
+                Err(e) => self.say(&format!("  {}", e)),
+            }
+        }
+    }
+
+    /// Ask for a secret until it passes `check`. Taken exactly as typed, spaces included.
+    fn ask_secret(
+        &mut self,
+        question: &str,
+        check: impl Fn(&str) -> Result<(), String>,
+    ) -> Result<String, String> {
+        loop {
+            let answer = self
+                .prompter
+                .read_secret(&format!("{}: ", question))
+                .map_err(|e| e.to_string())?
+                .ok_or("Input ended before the config was complete")?;
+            match check(&answer) {
+                Ok(()) => return Ok(answer),
+                Err(e) => self.say(&format!("  {}", e)),
+            }
+        }
+    }
+
+    fn confirm(&mut self, question: &str, default: bool) -> Result<bool, String> {
+        let hint = if default { "Y/n" } else { "y/N" };
+        let answer = self.ask(&format!("{} ({})", question, hint), Some(""), |a| {
+            match a.to_ascii_lowercase().as_str() {
+                "" | "y" | "yes" | "n" | "no" => Ok(()),
+                _ => Err("Please answer y or n".into()),
+            }
+        })?;
+        Ok(match answer.to_ascii_lowercase().as_str() {
+            "y" | "yes" => true,
+            "n" | "no" => false,
+            _ => default,
+        })
+    }
+
+    fn ask_windower_path(
+        &mut self,
+        fs: &dyn FileSystem,
+        exe_dir: Option<&Path>,
+    ) -> Result<String, String> {
+        let detected = detect_windower(fs, exe_dir);
+        match detected.as_slice() {
+            [] => self.say("Windower was not found in any of the usual locations."),
+            [only] => self.say(&format!("Found Windower at {}", only.display())),
+            many => {
+                self.say("Found Windower at:");
+                for p in many {
+                    self.say(&format!("  {}", p.display()));
+                }
+            }
+        }
+
+        let default = detected
+            .first()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| WINDOWER_CANDIDATES[0].to_string());
+
+        loop {
+            let path = self.ask("Path to Windower.exe", Some(&default), |p| {
+                if p.to_ascii_lowercase().ends_with(".exe") {
+                    Ok(())
+                } else {
+                    Err("Enter the full path to the Windower executable, ending in .exe".into())
+                }
+            })?;
+            if fs.is_file(Path::new(&path))
+                || self.confirm(&format!("{} does not exist. Use it anyway?", path), false)?
+            {
+                return Ok(path);
+            }
+        }
+    }
+
+    fn ask_character(&mut self, existing: &[Value]) -> Result<Value, String> {
+        let taken = |field: &str| -> Vec<Value> {
+            existing.iter().filter_map(|c| c.get(field).cloned()).collect()
+        };
+        let names = taken("name");
+        let slots = taken("slot");
+
+        let name = self.ask("  Name", None, |n| {
+            if n.is_empty() {
+                Err("Name cannot be empty".into())
+            } else if names
+                .iter()
+                .filter_map(Value::as_str)
+                .any(|existing| existing.eq_ignore_ascii_case(n))
+            {
+                Err(format!("{} is already in this config", n))
+            } else {
+                Ok(())
+            }
+        })?;
+
+        let next_slot = (1..=20u8)
+            .find(|s| !slots.contains(&json!(s)))
+            .unwrap_or(1)
+            .to_string();
+        let slot: u8 = self
+            .ask("  PlayOnline slot (1-20)", Some(&next_slot), |s| match s.parse::<u8>() {
+                Ok(n) if (1..=20).contains(&n) && slots.contains(&json!(n)) => {
+                    Err(format!("Slot {} is already used by another character", n))
+                }
+                Ok(n) if (1..=20).contains(&n) => Ok(()),
+                _ => Err("Slot must be a number from 1 to 20".into()),
+            })?
+            .parse()
+            .map_err(|_| "invalid slot")?;
+
+        self.say("  Password source:");
+        self.say("    1) Store the password in the config file");
+        self.say("    2) Read it from an environment variable");
+        let source = self.ask("  Choose", Some("1"), |c| match c {
+            "1" | "2" => Ok(()),
+            _ => Err("Choose 1 or 2".into()),
+        })?;
+
+        let mut character = Map::new();
+        character.insert("name".into(), json!(name));
+        character.insert("slot".into(), json!(slot));
+        if source == "1" {
+            // The wizard doesn't write an `input` section, so check against the defaults
+            let password = self.ask_secret("  Password", |p| {
+                check_password(p, &InputConfig::default())
+            })?;
+            character.insert("password".into(), json!(password));
+        } else {
+            let default_var = format!(
+                "LOGIN_RS_{}_PASSWORD",
+                name.to_ascii_uppercase().replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+            );
+            let var = self.ask("  Environment variable", Some(&default_var), |v| {
+                if v.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                    Ok(())
+                } else {
+                    Err("Use letters, digits and underscores only".into())
+                }
+            })?;
+            character.insert("password_env".into(), json!(var));
+        }
+
+        Ok(Value::Object(character))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::fs;
+
+    const WINDOWER: &str = r"C:\Windower4\Windower.exe";
+
+    /// Answers queued in order; everything shown is recorded
+    #[derive(Default)]
+    struct Scripted {
+        lines: VecDeque<&'static str>,
+        secrets: VecDeque<&'static str>,
+        shown: Vec<String>,
+    }
+
+    impl Scripted {
+        fn new(lines: &[&'static str], secrets: &[&'static str]) -> Self {
+            Self {
+                lines: lines.iter().copied().collect(),
+                secrets: secrets.iter().copied().collect(),
+                shown: Vec::new(),
+            }
+        }
+    }
+
+    impl Prompter for Scripted {
+        fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+            self.shown.push(prompt.to_string());
+            let answer = self.lines.pop_front().map(String::from);
+            if let Some(a) = &answer {
+                self.shown.push(a.clone());
+            }
+            Ok(answer)
+        }
+
+        fn read_secret(&mut self, prompt: &str) -> io::Result<Option<String>> {
+            self.shown.push(prompt.to_string());
+            Ok(self.secrets.pop_front().map(String::from))
+        }
+
+        fn message(&mut self, text: &str) {
+            self.shown.push(text.to_string());
+        }
+    }
+
+    /// Only `is_file` is asked by the wizard
+    struct Files(Vec<PathBuf>);
+
+    impl FileSystem for Files {
+        fn is_file(&self, path: &Path) -> bool {
+            self.0.iter().any(|p| p == path)
+        }
+
+        fn is_dir(&self, _: &Path) -> bool {
+            false
+        }
+
+        fn read(&self, _: &Path) -> io::Result<Vec<u8>> {
+            Err(io::ErrorKind::NotFound.into())
+        }
+
+        fn probe_writable(&self, _: &Path) -> io::Result<()> {
+            Err(io::ErrorKind::NotFound.into())
+        }
+    }
+
+    fn windower_installed() -> Files {
+        Files(vec![PathBuf::from(WINDOWER)])
+    }
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("login-rs-wizard-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("config.json")
+    }
+
+    fn run(
+        prompter: &mut Scripted,
+        fs: &Files,
+        path: &Path,
+        force: bool,
+    ) -> Result<WizardOutcome, String> {
+        run_wizard(prompter, fs, None, path, ConfigFormat::Json, force)
+    }
+
+    fn written(path: &Path) -> Value {
+        serde_json::from_slice(&fs::read(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn writes_a_config_from_the_defaults_and_answers() {
+        let path = scratch("defaults");
+        // Windower path, profile, region, name, slot, password source, add another
+        let mut p = Scripted::new(&["", "", "", "MyWarrior", "", "", ""], &["Passw0rd!"]);
+        let outcome = run(&mut p, &windower_installed(), &path, false).unwrap();
+        assert!(matches!(outcome, WizardOutcome::Written(ref p) if *p == path));
+
+        assert_eq!(
+            written(&path),
+            json!({
+                "version": migrate::CURRENT_VERSION,
+                "windower_path": WINDOWER,
+                "region": "us",
+                "characters": [{"name": "MyWarrior", "slot": 1, "password": "Passw0rd!"}],
+            })
+        );
+        assert!(p.shown.contains(&format!("Found Windower at {}", WINDOWER)));
+        assert!(p.shown.iter().all(|s| !s.contains("Passw0rd!")));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn password_is_taken_as_typed_not_trimmed() {
+        let path = scratch("password");
+        let mut p = Scripted::new(
+            &["", "", "", "MyWarrior", "", "", ""],
+            &[" Passw0rd! ", "short", "Passw0rd!"],
+        );
+        run(&mut p, &windower_installed(), &path, false).unwrap();
+
+        assert_eq!(written(&path)["characters"][0]["password"], "Passw0rd!");
+        let rejections: Vec<&String> =
+            p.shown.iter().filter(|s| s.starts_with("  password")).collect();
+        assert_eq!(rejections.len(), 2, "{:?}", p.shown);
+        assert!(rejections[0].contains("characters PlayOnline does not accept"));
+        assert!(rejections[1].contains("8 to 16 characters"));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn invalid_answers_are_asked_again() {
+        let path = scratch("reask");
+        let mut p = Scripted::new(
+            &[
+                "", "main", "xx", "eu", // Windower path, profile, region twice
+                "", "MyWarrior", "25", "3", "", "maybe", "y", // first character
+                "mywarrior", "MyMage", "3", "", "", // second: duplicate name, taken slot
+                "n",
+            ],
+            &["Passw0rd!", "An0ther!pw"],
+        );
+        run(&mut p, &windower_installed(), &path, false).unwrap();
+
+        let config = written(&path);
+        assert_eq!(config["windower_profile"], "main");
+        assert_eq!(config["region"], "eu");
+        assert_eq!(config["characters"][0]["slot"], 3);
+        assert_eq!(config["characters"][1]["name"], "MyMage");
+        assert_eq!(config["characters"][1]["slot"], 1);
+        for message in [
+            "  Region must be one of us, jp, eu",
+            "  Name cannot be empty",
+            "  Slot must be a number from 1 to 20",
+            "  Please answer y or n",
+            "  mywarrior is already in this config",
+            "  Slot 3 is already used by another character",
+        ] {
+            assert!(p.shown.iter().any(|s| s == message), "missing {:?}", message);
+        }
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn password_can_come_from_an_environment_variable() {
+        let path = scratch("env");
+        let mut p = Scripted::new(&["", "", "", "My-Warrior", "", "2", "", ""], &[]);
+        run(&mut p, &windower_installed(), &path, false).unwrap();
+
+        let character = &written(&path)["characters"][0];
+        assert_eq!(character["password_env"], "LOGIN_RS_MY_WARRIOR_PASSWORD");
+        assert!(character.get("password").is_none());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn a_missing_windower_path_needs_confirming() {
+        let path = scratch("missing");
+        let mut p = Scripted::new(
+            &[
+                r"D:\Games\Windower.exe", "", r"D:\Games\Windower.exe", "y", // path
+                "", "", "MyWarrior", "", "", "",
+            ],
+            &["Passw0rd!"],
+        );
+        run(&mut p, &Files(Vec::new()), &path, false).unwrap();
+
+        assert_eq!(written(&path)["windower_path"], r"D:\Games\Windower.exe");
+        assert!(p.shown.iter().any(|s| s == "Windower was not found in any of the usual locations."));
+        assert!(p.shown.iter().any(|s| s == &format!("Path to Windower.exe [{}]: ", WINDOWER)));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn existing_config_is_kept_unless_overwrite_is_confirmed() {
+        let path = scratch("existing");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "{}").unwrap();
+        let fs_with_config = Files(vec![PathBuf::from(WINDOWER), path.clone()]);
+
+        let mut p = Scripted::new(&[""], &[]);
+        let outcome = run(&mut p, &fs_with_config, &path, false);
+        assert!(matches!(outcome, Ok(WizardOutcome::Cancelled)));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{}");
+
+        // --force skips the question
+        let mut p = Scripted::new(&["", "", "", "MyWarrior", "", "", ""], &["Passw0rd!"]);
+        run(&mut p, &fs_with_config, &path, true).unwrap();
+        assert_eq!(written(&path)["characters"][0]["name"], "MyWarrior");
+        assert!(p.shown.iter().all(|s| !s.contains("Overwrite")));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn input_ending_early_is_an_error() {
+        let path = scratch("eof");
+        let mut p = Scripted::new(&["", "", ""], &[]);
+        let err = run(&mut p, &windower_installed(), &path, false).err().unwrap();
+        assert_eq!(err, "Input ended before the config was complete");
+
+        let mut p = Scripted::new(&["", "", "", "MyWarrior", "", ""], &[]);
+        assert!(run(&mut p, &windower_installed(), &path, false).is_err());
+        assert!(!path.exists());
+    }
+}